@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use error_stack::{Report, ResultExt};
+use stacked_portrayals::SPError;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::batch::{decode_input, path_next_to, report_failures};
+
+/// Archive entries with these extensions are mapped, others are copied as-is.
+const MAPPED_EXTENSIONS: &[&str] = &[".txt", ".log", ".log.gz"];
+
+/// Check if the file is a zip archive, e.g. a zipped `crash-reports` folder or a support bundle.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+}
+
+/// The suffix for mapped copies of archives, which keeps the `.zip` extension.
+pub fn archive_suffix(suffix: &str) -> String {
+    match suffix.rsplit_once('.') {
+        Some((base, _)) if !base.is_empty() => format!("{}.zip", base),
+        _ => format!("{}.zip", suffix),
+    }
+}
+
+/// Map the crash reports and logs in a zip archive, writing them to stdout with separators, or
+/// to a mapped copy of the archive if a `suffix` is given.
+///
+/// A failure to map one entry doesn't stop the others from being mapped. In the mapped copy, such
+/// entries are left as-is.
+pub fn map_archive(
+    path: &Path,
+    suffix: Option<&str>,
+    map_input: &mut impl FnMut(&str) -> Result<String, Report<SPError>>,
+) -> Result<(), Report<SPError>> {
+    let mut archive = File::open(path)
+        .change_context(SPError::IoError)
+        .and_then(|f| ZipArchive::new(f).change_context(SPError::IoError))
+        .attach_printable_lazy(|| format!("Failed to open archive {}", path.display()))?;
+    let output_path = suffix.map(|s| path_next_to(path, &archive_suffix(s)));
+    let mut writer = match &output_path {
+        Some(output_path) => Some(ZipWriter::new(
+            File::create(output_path)
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to create {}", output_path.display()))?,
+        )),
+        None => None,
+    };
+
+    // Entries copied as-is keep their names, so only the gzipped logs written back as plain text
+    // can clash, e.g. `a.log.gz` with `a.log`
+    let mut taken = archive
+        .file_names()
+        .map(str::to_string)
+        .collect::<HashSet<_>>();
+    let mut failures = Vec::new();
+    for i in 0..archive.len() {
+        let (name, mapped) = {
+            let mut entry = archive
+                .by_index(i)
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to read {}", path.display()))?;
+            let name = entry.name().to_string();
+            if !entry.is_file() || !MAPPED_EXTENSIONS.iter().any(|e| name.ends_with(e)) {
+                (name, None)
+            } else {
+                tracing::info!("Mapping {}!{}...", path.display(), name);
+                let mut bytes = Vec::new();
+                let result = entry
+                    .read_to_end(&mut bytes)
+                    .change_context(SPError::IoError)
+                    .and_then(|_| decode_input(bytes))
+                    .and_then(|input| map_input(&input));
+                match result {
+                    Ok(output) => (name, Some(output)),
+                    Err(e) => {
+                        tracing::error!("Failed to map {}!{}: {:?}", path.display(), name, e);
+                        failures.push(*e.current_context());
+                        (name, None)
+                    }
+                }
+            }
+        };
+        match (&mut writer, mapped) {
+            (Some(writer), Some(output)) => {
+                // Gzipped logs are written back as plain text
+                let name = match name.strip_suffix(".gz") {
+                    Some(plain) => unique_entry_name(plain, &mut taken),
+                    None => name,
+                };
+                writer
+                    .start_file(name.as_str(), FileOptions::default())
+                    .change_context(SPError::IoError)
+                    .and_then(|_| {
+                        writer
+                            .write_all(output.as_bytes())
+                            .change_context(SPError::IoError)
+                    })
+                    .attach_printable_lazy(|| format!("Failed to write {}", name))?;
+            }
+            (Some(writer), None) => {
+                let entry = archive
+                    .by_index(i)
+                    .change_context(SPError::IoError)
+                    .attach_printable_lazy(|| format!("Failed to read {}", path.display()))?;
+                writer
+                    .raw_copy_file(entry)
+                    .change_context(SPError::IoError)
+                    .attach_printable_lazy(|| format!("Failed to copy {}", name))?;
+            }
+            (None, Some(output)) => {
+                println!("==> {}!{} <==", path.display(), name);
+                println!("{}", output);
+            }
+            (None, None) => {}
+        }
+    }
+    if let (Some(writer), Some(output_path)) = (&mut writer, &output_path) {
+        writer
+            .finish()
+            .change_context(SPError::IoError)
+            .attach_printable_lazy(|| format!("Failed to write {}", output_path.display()))?;
+    }
+
+    report_failures(&failures, |count| {
+        format!("Failed to map {} entries of {}", count, path.display())
+    })
+}
+
+/// Reserve `name` in `taken`, numbering it before its extension if it is already taken, e.g.
+/// `logs/a.log` becomes `logs/a-1.log`.
+fn unique_entry_name(name: &str, taken: &mut HashSet<String>) -> String {
+    if taken.insert(name.to_string()) {
+        return name.to_string();
+    }
+    let file_start = name.rfind('/').map_or(0, |i| i + 1);
+    let (stem, extension) = match name[file_start..].find('.') {
+        Some(i) if i > 0 => name.split_at(file_start + i),
+        _ => (name, ""),
+    };
+    (1..)
+        .map(|n| format!("{}-{}{}", stem, n, extension))
+        .find(|candidate| taken.insert(candidate.clone()))
+        .expect("ran out of numbers for entry names")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_names_are_numbered_when_taken() {
+        let mut taken = HashSet::from(["logs/a.log".to_string(), "logs/a.log.gz".to_string()]);
+        assert_eq!(unique_entry_name("logs/b.log", &mut taken), "logs/b.log");
+        assert_eq!(unique_entry_name("logs/a.log", &mut taken), "logs/a-1.log");
+        assert_eq!(unique_entry_name("logs/a.log", &mut taken), "logs/a-2.log");
+        assert_eq!(unique_entry_name("v1.2/latest", &mut taken), "v1.2/latest");
+        assert_eq!(
+            unique_entry_name("v1.2/latest", &mut taken),
+            "v1.2/latest-1"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use error_stack::{Report, ResultExt};
+use flate2::read::GzDecoder;
+use stacked_portrayals::parsing::normalize_line_endings;
+use stacked_portrayals::SPError;
+
+use crate::archive::{archive_suffix, is_archive, map_archive};
+
+/// The suffix used for mapped copies of files, if no other suffix is given.
+pub const DEFAULT_SUFFIX: &str = ".mapped.txt";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const UTF16_LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16_BE_BOM: &[u8] = &[0xfe, 0xff];
+const UTF8_BOM: &[u8] = "\u{feff}".as_bytes();
+
+/// Read a file to map, decompressing it if it is gzipped, e.g. a rotated `.log.gz`.
+pub fn read_file(path: &Path) -> Result<String, Report<SPError>> {
+    std::fs::read(path)
+        .change_context(SPError::IoError)
+        .and_then(decode_input)
+        .attach_printable_lazy(|| format!("Failed to read {}", path.display()))
+}
+
+/// Turn raw input into text, decompressing it if it is gzipped. Lone `\r` line endings are
+/// replaced with `\n`.
+pub fn decode_input(bytes: Vec<u8>) -> Result<String, Report<SPError>> {
+    let text = if bytes.starts_with(GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to decompress gzipped input")?;
+        decode_text(decompressed)?
+    } else {
+        decode_text(bytes)?
+    };
+    Ok(match normalize_line_endings(&text) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(normalized) => normalized,
+    })
+}
+
+/// Whether raw input starting with `head` is plain UTF-8 text, which [`decode_input`] would only
+/// have to check, i.e. it isn't gzipped and has no byte order mark.
+pub fn is_plain_text(head: &[u8]) -> bool {
+    ![GZIP_MAGIC, UTF16_LE_BOM, UTF16_BE_BOM, UTF8_BOM]
+        .iter()
+        .any(|magic| head.starts_with(magic))
+}
+
+/// Decode text as UTF-8, or as UTF-16 if it starts with its byte order mark, e.g. crash reports
+/// saved by Notepad. Any byte order mark is dropped.
+fn decode_text(bytes: Vec<u8>) -> Result<String, Report<SPError>> {
+    let from_bytes: fn([u8; 2]) -> u16 = match bytes.get(..2) {
+        Some(UTF16_LE_BOM) => u16::from_le_bytes,
+        Some(UTF16_BE_BOM) => u16::from_be_bytes,
+        _ => {
+            let mut text = String::from_utf8(bytes)
+                .change_context(SPError::IoError)
+                .attach_printable("Input is not valid UTF-8")?;
+            if text.starts_with('\u{feff}') {
+                text.drain(..'\u{feff}'.len_utf8());
+            }
+            return Ok(text);
+        }
+    };
+    let pairs = bytes[2..].chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err(Report::new(SPError::IoError)
+            .attach_printable("Input is not valid UTF-16, it has an odd number of bytes"));
+    }
+    let units: Vec<u16> = pairs.map(|pair| from_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16(&units)
+        .change_context(SPError::IoError)
+        .attach_printable("Input is not valid UTF-16")
+}
+
+/// Expand the given paths and glob patterns into the files to map, in order.
+pub fn expand_paths(patterns: &[String]) -> Result<Vec<PathBuf>, Report<SPError>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        // Shells usually expand globs for us, but not on Windows
+        if !pattern.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(pattern));
+            continue;
+        }
+        let matches = glob::glob(pattern)
+            .change_context(SPError::ParseError)
+            .attach_printable_lazy(|| format!("Invalid glob pattern {}", pattern))?;
+        let len_before = paths.len();
+        for path in matches {
+            let path = path
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to expand glob pattern {}", pattern))?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        if paths.len() == len_before {
+            tracing::warn!("No files matched {}", pattern);
+        }
+    }
+    Ok(paths)
+}
+
+/// Map each file, writing the results to stdout with separators, or next to the original files
+/// if a `suffix` is given. The logs in zip archives are mapped as well.
+///
+/// A failure to map one file doesn't stop the others from being mapped.
+pub fn map_files(
+    paths: &[PathBuf],
+    suffix: Option<&str>,
+    mut map_input: impl FnMut(&str) -> Result<String, Report<SPError>>,
+) -> Result<(), Report<SPError>> {
+    let mut failures = Vec::new();
+    for path in paths {
+        if suffix.is_some_and(|s| {
+            let path = path.to_string_lossy();
+            path.ends_with(s) || path.ends_with(&archive_suffix(s))
+        }) {
+            tracing::debug!("Skipping already mapped file {}", path.display());
+            continue;
+        }
+        if is_archive(path) {
+            if let Err(e) = map_archive(path, suffix, &mut map_input) {
+                tracing::error!("Failed to map {}: {:?}", path.display(), e);
+                failures.push(*e.current_context());
+            }
+            continue;
+        }
+        tracing::info!("Mapping {}...", path.display());
+        let result = read_file(path)
+            .and_then(|input| map_input(&input))
+            .and_then(|output| match suffix {
+                Some(suffix) => write_next_to(path, suffix, &output),
+                None => {
+                    println!("==> {} <==", path.display());
+                    println!("{}", output);
+                    Ok(())
+                }
+            });
+        if let Err(e) = result {
+            tracing::error!("Failed to map {}: {:?}", path.display(), e);
+            failures.push(*e.current_context());
+        }
+    }
+    report_failures(&failures, |count| {
+        format!("Failed to map {} of {} files", count, paths.len())
+    })
+}
+
+/// Group the files by the fingerprint of their crash, printing each group with a description of
+/// the crash and its files, the largest groups first.
+///
+/// `fingerprint` returns the fingerprint and the description of a file. Files that fail to
+/// fingerprint are logged and left out. Archives are skipped, as they hold several reports.
+pub fn dedupe_files(
+    paths: &[PathBuf],
+    mut fingerprint: impl FnMut(&str) -> Result<(String, String), Report<SPError>>,
+) -> Result<(), Report<SPError>> {
+    let mut groups: Vec<(String, String, Vec<&Path>)> = Vec::new();
+    let mut failures = Vec::new();
+    for path in paths {
+        if is_archive(path) {
+            tracing::warn!(
+                "Skipping archive {}, extract it to dedupe its reports",
+                path.display()
+            );
+            continue;
+        }
+        tracing::info!("Fingerprinting {}...", path.display());
+        match read_file(path).and_then(|input| fingerprint(&input)) {
+            Ok((fingerprint, description)) => {
+                match groups.iter_mut().find(|(f, _, _)| *f == fingerprint) {
+                    Some((_, _, files)) => files.push(path),
+                    None => groups.push((fingerprint, description, vec![path])),
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to fingerprint {}: {:?}", path.display(), e);
+                failures.push(*e.current_context());
+            }
+        }
+    }
+    // Stable, so groups of the same size stay in the order they were first seen
+    groups.sort_by_key(|(_, _, files)| std::cmp::Reverse(files.len()));
+    for (fingerprint, description, files) in &groups {
+        let plural = if files.len() == 1 { "" } else { "s" };
+        println!(
+            "{} ({} report{}): {}",
+            fingerprint,
+            files.len(),
+            plural,
+            description
+        );
+        for file in files {
+            println!("\t{}", file.display());
+        }
+    }
+    report_failures(&failures, |count| {
+        format!("Failed to fingerprint {} of {} files", count, paths.len())
+    })
+}
+
+/// Fail with the kind of the first of the `failures` of a batch, and the `summary` of their count,
+/// if there are any.
+///
+/// Each failure is logged in full where it happens, so the rest of the batch can go on. Reporting
+/// them all again would repeat those logs, so this only gives the kind for the exit code.
+pub fn report_failures(
+    failures: &[SPError],
+    summary: impl FnOnce(usize) -> String,
+) -> Result<(), Report<SPError>> {
+    match failures.first() {
+        Some(&kind) => Err(Report::new(kind).attach_printable(summary(failures.len()))),
+        None => Ok(()),
+    }
+}
+
+/// The path next to `path`, with the `suffix` replacing its extension.
+pub fn path_next_to(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or(path.as_os_str());
+    let mut file_name = stem.to_os_string();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Write the `output` next to `path`, with the `suffix` replacing its extension.
+pub fn write_next_to(path: &Path, suffix: &str, output: &str) -> Result<(), Report<SPError>> {
+    let output_path = path_next_to(path, suffix);
+    std::fs::File::create(&output_path)
+        .and_then(|mut f| f.write_all(output.as_bytes()))
+        .change_context(SPError::IoError)
+        .attach_printable_lazy(|| format!("Failed to write {}", output_path.display()))
+}
@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+use error_stack::{Report, ResultExt};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use stacked_portrayals::mappings::hints::DescriptorHint;
+use stacked_portrayals::SPError;
+
+/// The settings of the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Rewrite and suppression rules, applied in order.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Descriptor hints, in the same form as `--hint`.
+    #[serde(default, deserialize_with = "deserialize_hints")]
+    pub hints: Vec<DescriptorHint>,
+}
+
+impl Config {
+    /// Load the config from `path`, or from `config.json` in the config directory if none is
+    /// given. A missing default config is the same as an empty one.
+    pub fn load(path: Option<&Path>) -> Result<Self, Report<SPError>> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(Report::new(e)
+                    .change_context(SPError::IoError)
+                    .attach_printable(format!("Failed to read config {}", path.display())))
+            }
+        };
+        let config: Self = serde_json::from_str(&text)
+            .change_context(SPError::ParseError)
+            .attach_printable_lazy(|| format!("Invalid config {}", path.display()))?;
+        tracing::debug!(
+            "Loaded {} rules and {} hints from {}",
+            config.rules.len(),
+            config.hints.len(),
+            path.display()
+        );
+        Ok(config)
+    }
+}
+
+fn deserialize_hints<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<DescriptorHint>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|hint| {
+            hint.parse().map_err(|_| {
+                serde::de::Error::custom(format!(
+                    "invalid hint {}, expected e.g. abc.d.e=(int,java.lang.String)",
+                    hint
+                ))
+            })
+        })
+        .collect()
+}
+
+fn default_path() -> Option<PathBuf> {
+    ProjectDirs::from("net", "octyl", "stacked-portrayals")
+        .map(|dirs| dirs.config_dir().join("config.json"))
+}
+
+/// When a [`Rule`] is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// To the input, before it is parsed.
+    Input,
+    /// To the output, after it is mapped.
+    Output,
+}
+
+/// A regex applied to each line at a [`Stage`], either replacing its matches or dropping the
+/// lines it matches, e.g.
+/// `{"stage": "input", "pattern": "^\\[Panel\\] ", "replace": ""}` or
+/// `{"stage": "output", "pattern": "at com\\.example\\.agent\\.", "drop": true}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawRule")]
+pub struct Rule {
+    stage: Stage,
+    pattern: Regex,
+    /// `None` to drop matching lines.
+    replace: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    stage: Stage,
+    pattern: String,
+    replace: Option<String>,
+    #[serde(default)]
+    drop: bool,
+}
+
+impl TryFrom<RawRule> for Rule {
+    type Error = String;
+
+    fn try_from(raw: RawRule) -> Result<Self, Self::Error> {
+        let pattern = Regex::new(&raw.pattern).map_err(|e| e.to_string())?;
+        let replace = match (raw.replace, raw.drop) {
+            (Some(replace), false) => Some(replace),
+            (None, true) => None,
+            _ => {
+                return Err(format!(
+                    "rule for `{}` needs either `replace` or `drop`",
+                    raw.pattern
+                ))
+            }
+        };
+        Ok(Self {
+            stage: raw.stage,
+            pattern,
+            replace,
+        })
+    }
+}
+
+/// Apply the `rules` of a `stage` to each line of the `text`, in order. Line endings are kept,
+/// and aren't seen by the patterns.
+pub fn apply_rules<'a>(rules: &[Rule], stage: Stage, text: &'a str) -> Cow<'a, str> {
+    let rules: Vec<&Rule> = rules.iter().filter(|r| r.stage == stage).collect();
+    if rules.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    let mut output = String::with_capacity(text.len());
+    'lines: for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let mut content = Cow::Borrowed(content);
+        for rule in &rules {
+            match &rule.replace {
+                Some(replace) => {
+                    if let Cow::Owned(replaced) = rule.pattern.replace_all(&content, replace) {
+                        content = Cow::Owned(replaced);
+                    }
+                }
+                None if rule.pattern.is_match(&content) => continue 'lines,
+                None => {}
+            }
+        }
+        output.push_str(&content);
+        output.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+    }
+    Cow::Owned(output)
+}
@@ -0,0 +1,197 @@
+//! Guessing which mod most plausibly caused a crash, from its mapped stacktrace.
+
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+
+use crate::stacktrace::{Frame, Stacktrace};
+
+/// Packages of the JDK, common libraries, the game and its loaders. Their frames are where mods
+/// are called from, or where the crash surfaced, so they are never the culprit.
+const INNOCENT_PACKAGES: &[&str] = &[
+    "java.",
+    "javax.",
+    "jdk.",
+    "sun.",
+    "com.sun.",
+    "kotlin.",
+    "io.netty.",
+    "com.google.",
+    "org.apache.",
+    "org.slf4j.",
+    "org.lwjgl.",
+    "org.joml.",
+    "it.unimi.",
+    "net.minecraft.",
+    "com.mojang.",
+    "net.fabricmc.",
+    "org.quiltmc.",
+    "net.minecraftforge.",
+    "net.neoforged.",
+    "cpw.mods.",
+    "org.spongepowered.",
+    "com.llamalad7.mixinextras.",
+];
+
+/// Prefixes of the JARs of the game and its loaders, as printed by Log4j.
+const INNOCENT_JARS: &[&str] = &[
+    "minecraft",
+    "client",
+    "server",
+    "intermediary",
+    "named",
+    "forge-",
+    "fmlcore",
+    "fmlloader",
+    "javafmllanguage",
+    "neoforge-",
+    "fabric-loader",
+    "sponge-mixin",
+    "mixinextras",
+];
+
+/// The prefixes of the methods Mixin and MixinExtras generate for handlers.
+const MIXIN_PREFIXES: &[&str] = &[
+    "handler",
+    "redirect",
+    "modify",
+    "args",
+    "localvar",
+    "constant",
+    "wrapOperation",
+    "wrapWithCondition",
+    "modifyExpressionValue",
+    "modifyReturnValue",
+    "modifyReceiver",
+];
+
+/// A guess at what caused a crash, see [`suspect_culprit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Culprit {
+    /// The mod or class suspected, e.g. `mod examplemod`.
+    pub suspect: String,
+    /// Why it is suspected.
+    pub reason: String,
+}
+
+impl Display for Culprit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.suspect, self.reason)
+    }
+}
+
+/// Guess which mod most plausibly caused the crash of a mapped stacktrace.
+///
+/// The frames of the root cause are checked from the top, then those of the enclosing traces. The
+/// first frame that is a mixin handler names its mod, as Fabric's Mixin puts the mod id in the
+/// handler's name, e.g. `handler$zza000$examplemod$onTick`. Otherwise the first frame outside the
+/// JDK, common libraries and the game is suspected, by its class or by the JAR Log4j printed for
+/// it. As a last resort, the mods whose mixins Forge lists for a frame are suspected, e.g.
+/// `{re:mixin,pl:mixin:APP:examplemod.mixins.json:SomeMixin}`.
+pub fn suspect_culprit(stacktrace: &Stacktrace) -> Option<Culprit> {
+    let root = stacktrace.root_cause();
+    let mut mixin_mods = None;
+    for frame in root
+        .frames
+        .iter()
+        .chain(stacktrace.all_frames())
+        .filter(|f| !f.is_generated())
+    {
+        let location = format!("{}.{}", frame.class, frame.method);
+        if let Some(mod_id) = mixin_handler_mod(&frame.method) {
+            return Some(Culprit {
+                suspect: format!("mod {}", mod_id),
+                reason: format!("its mixin handler is at {}", location),
+            });
+        }
+        let jar = frame
+            .jar
+            .as_deref()
+            .and_then(jar_name)
+            .filter(|jar| !is_innocent_jar(jar));
+        // Obfuscated classes of the game have no package, mods' classes always do
+        if frame.class.contains('.') && !is_innocent_class(&frame.class) {
+            return Some(Culprit {
+                suspect: match jar {
+                    Some(jar) => format!("class {} from {}", frame.class, jar),
+                    None => format!("class {}", frame.class),
+                },
+                reason: format!(
+                    "the topmost frame outside the JDK, libraries and the game is at {}",
+                    location
+                ),
+            });
+        }
+        if let Some(jar) = jar {
+            return Some(Culprit {
+                suspect: jar.to_string(),
+                reason: format!(
+                    "the topmost frame from a JAR other than the game's is at {}",
+                    location
+                ),
+            });
+        }
+        if mixin_mods.is_none() {
+            mixin_mods = forge_mixin_mods(frame).map(|mods| (mods, location));
+        }
+    }
+    mixin_mods.map(|(mods, location)| Culprit {
+        suspect: match mods.as_slice() {
+            [mod_id] => format!("mod {}", mod_id),
+            mods => format!("one of the mods {}", mods.join(", ")),
+        },
+        reason: format!("its mixins were applied to {}", location),
+    })
+}
+
+/// The mod id in the name of a mixin handler, e.g. `examplemod` in
+/// `handler$zza000$examplemod$onTick`. Handlers without one, from older versions of Mixin or from
+/// Forge, have only three parts.
+fn mixin_handler_mod(method: &str) -> Option<&str> {
+    let (prefix, _, mod_id, _) = method.splitn(4, '$').collect_tuple()?;
+    (MIXIN_PREFIXES.contains(&prefix) && is_mod_id(mod_id)).then_some(mod_id)
+}
+
+/// The mods of the mixin configs Forge lists after a frame, e.g. `examplemod` for
+/// `pl:mixin:APP:examplemod.mixins.json:SomeMixin`.
+fn forge_mixin_mods(frame: &Frame) -> Option<Vec<&str>> {
+    let suffix = frame.suffix.as_deref()?;
+    let mods: Vec<&str> = suffix
+        .split([',', '{', '}'])
+        .filter_map(|entry| entry.trim().strip_prefix("pl:mixin:APP:"))
+        .filter_map(|entry| {
+            let config = entry.split(':').next()?;
+            // Configs are usually `modid.mixins.json` or `mixins.modid.json`
+            config
+                .split('.')
+                .find(|part| !matches!(*part, "mixins" | "mixin" | "json"))
+        })
+        .filter(|mod_id| is_mod_id(mod_id))
+        .unique()
+        .collect();
+    (!mods.is_empty()).then_some(mods)
+}
+
+/// The name of the JAR in the contents of a `~[...]` suffix, e.g. `examplemod-1.0.jar` for
+/// `examplemod-1.0.jar:?` or Forge's `examplemod-1.0.jar%23123!/:?`.
+fn jar_name(jar: &str) -> Option<&str> {
+    let name = jar.rsplit_once(':').map_or(jar, |(name, _)| name);
+    let name = name.split(['%', '!']).next().unwrap_or(name);
+    (!name.is_empty() && name != "?").then_some(name)
+}
+
+fn is_innocent_class(class: &str) -> bool {
+    INNOCENT_PACKAGES.iter().any(|p| class.starts_with(p))
+}
+
+fn is_innocent_jar(jar: &str) -> bool {
+    let jar = jar.to_ascii_lowercase();
+    INNOCENT_JARS.iter().any(|p| jar.starts_with(p))
+}
+
+fn is_mod_id(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
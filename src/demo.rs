@@ -0,0 +1,102 @@
+//! A small sample of mappings and a trace to map with them, bundled so mapping can be tried
+//! without network access, e.g. to check that an installation works, or in tests of tools that
+//! wrap this one.
+
+use error_stack::{Report, ResultExt};
+
+use crate::mappings::proguard::parse_proguard;
+use crate::mappings::source::{register_source, Content, MappingSource, RawMappings};
+use crate::mappings::tiny::parse_tiny_v2;
+use crate::names::NamesType;
+use crate::SPError;
+
+/// The version that the sample mappings are for. No real version has this id.
+pub const VERSION: &str = "demo";
+
+/// A trace in obfuscated names that the sample mappings cover.
+pub const TRACE: &str = include_str!("demo/trace.txt");
+
+/// Register sources with the sample mappings between obfuscated and Mojang names, and between
+/// obfuscated and Fabric intermediary names. They only have mappings for [`VERSION`], so other
+/// versions still use the other sources.
+pub fn register_demo_sources() {
+    register_source(EmbeddedSource {
+        name: "demo_mojang",
+        primary: NamesType::Mojang,
+        secondary: NamesType::Obfuscated,
+        format: Format::ProGuard,
+        content: include_str!("demo/client.txt"),
+    });
+    register_source(EmbeddedSource {
+        name: "demo_fabric_intermediary",
+        primary: NamesType::Obfuscated,
+        secondary: NamesType::FabricIntermediary,
+        format: Format::TinyV2 {
+            from: "official",
+            to: "intermediary",
+        },
+        content: include_str!("demo/intermediary.tiny"),
+    });
+}
+
+#[derive(Debug)]
+enum Format {
+    ProGuard,
+    TinyV2 {
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+/// A source of mappings that are part of the binary, for [`VERSION`] only.
+#[derive(Debug)]
+struct EmbeddedSource {
+    name: &'static str,
+    primary: NamesType,
+    secondary: NamesType,
+    format: Format,
+    content: &'static str,
+}
+
+impl MappingSource for EmbeddedSource {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn primary_names(&self) -> NamesType {
+        self.primary
+    }
+
+    fn secondary_names(&self) -> NamesType {
+        self.secondary
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        if version != VERSION {
+            return Err(
+                Report::new(SPError::UnknownVersion).attach_printable(format!(
+                    "The sample mappings are only for version {}",
+                    VERSION
+                )),
+            );
+        }
+        Ok(Content::from(self.content.to_string()))
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        match self.format {
+            Format::ProGuard => Ok(parse_proguard(content)?.into_raw()),
+            Format::TinyV2 { from, to } => parse_tiny_v2(content)?
+                .to_raw(from, to)
+                .attach_printable_lazy(|| format!("Invalid tiny mappings for {}", version)),
+        }
+    }
+
+    fn is_cached(&self, version: &str) -> bool {
+        version == VERSION
+    }
+
+    fn has_version(&self, version: &str) -> Option<bool> {
+        Some(version == VERSION)
+    }
+}
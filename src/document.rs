@@ -0,0 +1,350 @@
+use std::io::BufRead;
+use std::iter::once;
+
+use itertools::Itertools;
+
+use crate::mappings::{MapSelf, MethodMapper};
+use crate::messages::map_internal_class_tokens;
+use crate::mixin_dump::{is_box_line, MixinDump};
+use crate::parsing::is_java_letter;
+use crate::render::{write_ide_frame, RenderOptions};
+use crate::rewrite::{rewrite_frame, rewrite_stacktrace};
+use crate::stacktrace::{parse_frame, parse_stacktrace, Frame, Stacktrace};
+
+/// Map every stacktrace in a document such as a log or crash report, leaving the rest of the
+/// text as-is.
+///
+/// Blocks that parse as a full stacktrace are mapped as a whole, even if their first line starts
+/// with a logger prefix, e.g. `[12:00:00] [main/INFO]: [STDERR]: java.lang.Exception`. Any other
+/// frame lines, e.g. in the `Stacktrace:` sections of a crash report, are mapped individually, and
+/// so are the classes in the details of a crash report, e.g. `Entity class: aqz`, and in the lock
+/// lines of a thread dump, e.g. `- waiting to lock <0x...> (a abc.d)`, and the names in the boxes
+/// Mixin prints about callback injectors, e.g. their target and its locals. In the remaining
+/// lines, class names in internal form are mapped, e.g. the targets of mixins in
+/// `Mixin config modid.mixins.json ... target net/minecraft/class_310`.
+pub fn map_document(input: &str, mapper: &impl MethodMapper, options: &RenderOptions) -> String {
+    let mut mixin_dump = MixinDump::default();
+    map_traces(
+        input,
+        true,
+        |block, stacktrace| {
+            let mapped = stacktrace.map_self(mapper);
+            if options.exact {
+                rewrite_stacktrace(block, &mapped)
+            } else {
+                mapped.render(options).to_string()
+            }
+        },
+        |line| {
+            mixin_dump
+                .map_line(line, mapper)
+                .or_else(|| map_frame_line(line, mapper, options))
+                .or_else(|| map_detail_line(line, mapper))
+                .or_else(|| map_monitor_line(line, mapper))
+                .unwrap_or_else(|| map_internal_class_tokens(line, mapper))
+        },
+    )
+}
+
+/// Reformat every stacktrace in a document canonically, without mapping it, so traces from
+/// different sources can be diffed.
+///
+/// Traces are rendered like mapped ones, with a tab before each frame and each cause on its own
+/// `Caused by: ` line. Logger prefixes are stripped from their lines, e.g.
+/// `[12:00:00] [main/INFO]: [STDERR]: ` before a header or a frame, and so are trailing spaces
+/// and carriage returns. The rest of the text is left as-is.
+pub fn normalize_document(input: &str) -> String {
+    let mut stripped = input
+        .lines()
+        .map(|line| strip_log_prefix(line).trim_end())
+        .join("\n");
+    if input.ends_with('\n') {
+        stripped.push('\n');
+    }
+    map_traces(
+        &stripped,
+        false,
+        |_, stacktrace| stacktrace.to_string(),
+        |line| match parse_frame(line) {
+            Some(frame) if is_frame_line(line) => {
+                format!("\tat {}{}", frame, &line[line.trim_end().len()..])
+            }
+            _ => line.to_string(),
+        },
+    )
+}
+
+/// Find every full stacktrace in a document, like [`map_document`] but without mapping them. Lone
+/// frame lines are ignored.
+pub fn find_stacktraces(input: &str) -> Vec<Stacktrace> {
+    let mut stacktraces = Vec::new();
+    map_traces(
+        input,
+        false,
+        |_, stacktrace| {
+            stacktraces.push(stacktrace);
+            String::new()
+        },
+        |_| String::new(),
+    );
+    stacktraces
+}
+
+/// The chunks of a document read line by line, each of which [`map_document`] maps the same as
+/// it would as part of the whole document, so large logs can be mapped as they are read.
+///
+/// A chunk is a line and the stacktrace lines after it, so only one stacktrace is held in memory
+/// at a time. The lines of a box Mixin printed are kept together too, as they name the members of
+/// the same target. Line endings are kept, but only `\n` ends a line.
+pub struct DocumentChunks<R> {
+    reader: R,
+    pending: String,
+    done: bool,
+}
+
+impl<R: BufRead> DocumentChunks<R> {
+    /// Read the chunks of the document `reader` reads.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: String::new(),
+            done: false,
+        }
+    }
+}
+
+impl<R> DocumentChunks<R> {
+    /// Check if the pending lines are complete before the `line`, i.e. a stacktrace can't span
+    /// it, and neither does a box Mixin printed.
+    fn ends_chunk(&self, line: &str) -> bool {
+        let continues_box =
+            is_box_line(line) && self.pending.lines().last().is_some_and(is_box_line);
+        !self.pending.is_empty() && !is_trace_line(line) && !continues_box
+    }
+}
+
+impl<R: BufRead> Iterator for DocumentChunks<R> {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    self.done = true;
+                    return (!self.pending.is_empty())
+                        .then(|| Ok(std::mem::take(&mut self.pending)));
+                }
+                Ok(_) if self.ends_chunk(&line) => {
+                    return Some(Ok(std::mem::replace(&mut self.pending, line)));
+                }
+                Ok(_) => self.pending.push_str(&line),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Strip the logger prefix of a line that continues a stacktrace, e.g. a frame printed through
+/// `[12:00:00] [main/INFO]: [STDERR]: `.
+fn strip_log_prefix(line: &str) -> &str {
+    if is_trace_line(line) {
+        return line;
+    }
+    line.match_indices(": ")
+        .map(|(j, _)| &line[j + 2..])
+        .find(|rest| is_trace_line(rest))
+        .unwrap_or(line)
+}
+
+/// Pass each block of lines that parses as a full stacktrace to `map_trace` with its text, and
+/// each other line to `map_line`, joining what they return.
+///
+/// The header of a block may follow a logger prefix, which is kept if `keep_prefix` is set.
+fn map_traces(
+    input: &str,
+    keep_prefix: bool,
+    mut map_trace: impl FnMut(&str, Stacktrace) -> String,
+    mut map_line: impl FnMut(&str) -> String,
+) -> String {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if !is_frame_line(line) && lines.get(i + 1).is_some_and(|l| is_frame_line(l)) {
+            let end = i
+                + 1
+                + lines[i + 1..]
+                    .iter()
+                    .take_while(|l| is_trace_line(l))
+                    .count();
+            // The header may follow a logger prefix, e.g. `[12:00:00] [main/INFO]: [STDERR]: `
+            let mapped = once(0)
+                .chain(
+                    line.match_indices(": ")
+                        .map(|(j, _)| j + 2)
+                        // Not e.g. `[STDERR]`, which would parse as an array type
+                        .filter(|&j| line[j..].starts_with(is_java_letter)),
+                )
+                .find_map(|start| {
+                    let mut block = line[start..].to_string();
+                    block.extend(lines[i + 1..end].iter().copied());
+                    map_block(block, &mut map_trace).map(|mapped| (start, mapped))
+                });
+            if let Some((start, mapped)) = mapped {
+                if keep_prefix {
+                    output.push_str(&line[..start]);
+                }
+                output.push_str(&mapped);
+                i = end;
+                continue;
+            }
+        }
+        output.push_str(&map_line(line));
+        i += 1;
+    }
+    output
+}
+
+/// Map a block of lines if it parses as a full stacktrace.
+fn map_block(
+    mut block: String,
+    map_trace: &mut impl FnMut(&str, Stacktrace) -> String,
+) -> Option<String> {
+    let terminated = block.ends_with('\n');
+    if !terminated {
+        block.push('\n');
+    }
+    let stacktrace = match parse_stacktrace(&block) {
+        Ok(stacktrace) => stacktrace,
+        Err(e) => {
+            tracing::debug!("Not a stacktrace: {:?}", e);
+            return None;
+        }
+    };
+    let rendered = map_trace(&block, stacktrace);
+    Some(if terminated {
+        rendered
+    } else {
+        rendered.trim_end_matches('\n').to_string()
+    })
+}
+
+fn is_frame_line(line: &str) -> bool {
+    line.trim_start().starts_with("at ")
+}
+
+fn is_trace_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    is_frame_line(line)
+        || (trimmed.starts_with("... ")
+            && (trimmed.ends_with(" more") || trimmed.ends_with(" common frames omitted")))
+        || trimmed.starts_with("Caused by: ")
+}
+
+fn map_frame_line(
+    line: &str,
+    mapper: &impl MethodMapper,
+    options: &RenderOptions,
+) -> Option<String> {
+    if !is_frame_line(line) {
+        return None;
+    }
+    let frame = parse_frame(line)?.map_self(mapper);
+    if options.exact {
+        return Some(rewrite_frame(line, &frame));
+    }
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let line_ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+    let rendered = match &options.frame_template {
+        Some(template) => template.render(&Frame {
+            class: options.class_names.apply(&frame.class).into_owned(),
+            ..frame
+        }),
+        None if options.ide => {
+            let mut rendered = "at ".to_string();
+            write_ide_frame(&mut rendered, &frame).expect("writing to a String can't fail");
+            rendered
+        }
+        None => {
+            let method = options.method_text(&frame).into_owned();
+            let class = options.class_names.apply(&frame.class).into_owned();
+            format!(
+                "at {}",
+                Frame {
+                    class,
+                    method,
+                    ..frame
+                }
+            )
+        }
+    };
+    Some(format!("{}{}{}", indent, rendered, line_ending))
+}
+
+/// Map a detail of a crash report that names a class, e.g. `Entity class: aqz`, or an entity or
+/// block type with its class, e.g. `Entity Type: minecraft:zombie (aqz)`.
+///
+/// Obfuscated classes aren't qualified, so only the details known to hold classes are mapped,
+/// not every short word.
+fn map_detail_line(line: &str, mapper: &impl MethodMapper) -> Option<String> {
+    if !line.starts_with([' ', '\t']) {
+        return None;
+    }
+    let content = line.trim_end_matches(['\r', '\n']);
+    let (key, value) = content.split_once(": ")?;
+    let key_name = key.trim_start().to_ascii_lowercase();
+    let value_start = key.len() + 2;
+    let (start, end) = if key_name.ends_with("class") {
+        (value_start, content.len())
+    } else if key_name.ends_with("type") {
+        let class = value.strip_suffix(')')?;
+        let open = class.rfind('(')?;
+        (value_start + open + 1, value_start + class.len())
+    } else {
+        return None;
+    };
+    let mapped = mapper.map_class(&content[start..end])?;
+    Some(format!("{}{}{}", &line[..start], mapped, &line[end..]))
+}
+
+/// Map the classes of the objects in a lock line of a thread dump, e.g.
+/// `- waiting to lock <0x...> (a abc.d)`, or of its deadlock section, e.g.
+/// `waiting to lock monitor 0x... (object 0x..., a abc.d),`.
+fn map_monitor_line(line: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let trimmed = line.trim_start();
+    if !trimmed.starts_with("- ") && !trimmed.starts_with("waiting ") {
+        return None;
+    }
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    let mut mapped_any = false;
+    while let Some(marker) = ["(a ", ", a "]
+        .iter()
+        .filter_map(|m| rest.find(m).map(|i| i + m.len()))
+        .min()
+    {
+        let end = marker
+            + rest[marker..]
+                .find([')', ','])
+                .unwrap_or(rest.len() - marker);
+        output.push_str(&rest[..marker]);
+        match mapper.map_class(&rest[marker..end]) {
+            Some(mapped) => {
+                output.push_str(mapped);
+                mapped_any = true;
+            }
+            None => output.push_str(&rest[marker..end]),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    mapped_any.then_some(output)
+}
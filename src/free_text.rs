@@ -0,0 +1,152 @@
+//! Mapping the names in arbitrary text, such as a forum post, a mixin config, or decompiled code.
+//!
+//! Instead of parsing stacktraces, the text is split into name-like tokens, and each token that
+//! is a known class, method or field is rewritten.
+
+use itertools::Itertools;
+
+use crate::mappings::MethodMapper;
+use crate::messages::{map_class_token, map_selector};
+use crate::parsing::is_java_identifier_part;
+
+/// Map every class, method and field name in the text, leaving everything else as-is.
+///
+/// Classes must be qualified, in source or internal form, unless followed by a member. Methods may
+/// be qualified by their class, e.g. `net.minecraft.class_310.method_1234` or `abc.a`, or stand
+/// alone if their name is unique and can't be an ordinary word, e.g. `method_1234` or
+/// `getWindow`, but not `a` or `tick`. Fields must be qualified, by their class or a variable,
+/// e.g. `abc.b` or `client.field_1724`, and a member that is called, e.g. `abc.b()`, is never a
+/// field.
+pub fn map_free_text(text: &str, mapper: &impl MethodMapper) -> String {
+    let is_token_char = |c: char| is_java_identifier_part(c) || c == '.' || c == '/';
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_token_char) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_token_char(c)).unwrap_or(rest.len());
+        let (token, after) = rest.split_at(end);
+        // Sentences may end right after a name
+        let name = token.trim_end_matches('.');
+        let is_call = after.starts_with('(');
+        match map_token(name, is_call, mapper) {
+            Some(mapped) => {
+                output.push_str(&mapped);
+                output.push_str(&token[name.len()..]);
+            }
+            None => output.push_str(token),
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    output
+}
+
+fn map_token(token: &str, is_call: bool, mapper: &impl MethodMapper) -> Option<String> {
+    if let Some(mapped) = map_class_token(token, mapper) {
+        return Some(mapped);
+    }
+    let Some((owner, name)) = token.rsplit_once('.') else {
+        return map_bare_method(token, mapper);
+    };
+    // Being followed by a member is enough to tell that an unqualified owner is a class
+    let mapped_owner =
+        map_class_token(owner, mapper).or_else(|| mapper.map_class(owner).map(String::from));
+    match mapped_owner {
+        Some(mapped_owner) => {
+            let mapped_field = (!is_call)
+                .then(|| map_field(Some(owner), name, mapper))
+                .flatten();
+            let mapped_name = mapped_field.or_else(|| {
+                mapper
+                    .map_method(owner, name, None)
+                    .into_iter()
+                    .map(|(_, id)| id.name.as_str())
+                    .unique()
+                    .exactly_one()
+                    .ok()
+            });
+            Some(format!("{}.{}", mapped_owner, mapped_name.unwrap_or(name)))
+        }
+        // e.g. `client.method_1234()` or `client.field_1724` in decompiled code, where the owner
+        // is a variable
+        None => {
+            let mapped_field = (!is_call && is_unlike_word(name))
+                .then(|| map_field(None, name, mapper).map(String::from))
+                .flatten();
+            let mapped = mapped_field.or_else(|| map_bare_method(name, mapper));
+            mapped.map(|mapped| format!("{}.{}", owner, mapped))
+        }
+    }
+}
+
+/// Map a field name, in its class if that's known, if it has a single mapping.
+fn map_field<'m>(
+    class: Option<&str>,
+    name: &str,
+    mapper: &'m impl MethodMapper,
+) -> Option<&'m str> {
+    mapper
+        .map_field(class, name)
+        .into_iter()
+        .map(|(_, name)| name)
+        .unique()
+        .exactly_one()
+        .ok()
+}
+
+fn map_bare_method(name: &str, mapper: &impl MethodMapper) -> Option<String> {
+    if !is_unlike_word(name) {
+        return None;
+    }
+    map_selector(name, mapper)
+}
+
+/// Check if the name can't be an ordinary word, because it has digits, underscores, `$`, or
+/// uppercase letters after the first.
+fn is_unlike_word(name: &str) -> bool {
+    name.chars()
+        .enumerate()
+        .any(|(i, c)| c.is_ascii_digit() || c == '_' || c == '$' || (i > 0 && c.is_uppercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mappings::proguard::parse_proguard;
+    use crate::mappings::MapperBuilder;
+    use crate::names::NamesType;
+
+    fn mapper() -> impl MethodMapper {
+        let mappings = parse_proguard(
+            "net.minecraft.client.Minecraft -> enn:\n\
+             \x20   net.minecraft.client.Minecraft instance -> b\n\
+             \x20   775:804:void run() -> e\n\
+             \x20   1195:1240:void runTick(boolean) -> b\n",
+        )
+        .unwrap();
+        MapperBuilder::new("1.20.1")
+            .proguard_step(NamesType::Obfuscated, NamesType::Mojang, mappings)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn maps_qualified_fields() {
+        let mapper = mapper();
+        assert_eq!(
+            map_free_text("enn.b is null in enn.e", &mapper),
+            "net.minecraft.client.Minecraft.instance is null in net.minecraft.client.Minecraft.run"
+        );
+    }
+
+    #[test]
+    fn called_members_are_methods() {
+        let mapper = mapper();
+        assert_eq!(
+            map_free_text("enn.b(true)", &mapper),
+            "net.minecraft.client.Minecraft.runTick(true)"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Guessing the version of Minecraft a stacktrace came from, by how much of it each version's
+//! mappings cover, and checking the version it is mapped with.
+
+use std::collections::HashSet;
+
+use error_stack::Report;
+
+use crate::mappings::{generate_mapper, MethodMapper};
+use crate::names::NamesType;
+use crate::stacktrace::{parse_frame, Frame};
+use crate::SPError;
+
+/// How well a version's mappings cover the frames of an input, see [`guess_version`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionScore {
+    pub version: String,
+    /// The distinct frames that could be mapped, not counting generated classes.
+    pub frames: usize,
+    /// Frames whose class and method are both in the mappings.
+    pub mapped: usize,
+}
+
+impl VersionScore {
+    /// The fraction of frames that were mapped, between 0 and 1.
+    pub fn coverage(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.mapped as f64 / self.frames as f64
+        }
+    }
+}
+
+/// Score each of the `versions` by how many frames in `input` its mappings cover, best first.
+///
+/// The input can be a stacktrace or any text containing one, such as a log. Versions with equal
+/// scores keep their order, and versions whose mappings fail to load are skipped with a warning.
+pub fn guess_version(
+    input: &str,
+    versions: &[String],
+    from: NamesType,
+    to: NamesType,
+) -> Result<Vec<VersionScore>, Report<SPError>> {
+    let frames = distinct_frames(input);
+    if frames.is_empty() {
+        return Err(Report::new(SPError::ParseError)
+            .attach_printable("No frames to guess the version from"));
+    }
+    score_versions(&frames, versions, from, to, true)
+}
+
+/// The fraction of the Minecraft frames of an input that the version it is mapped with must
+/// cover, see [`check_version`].
+#[cfg(feature = "mojang")]
+const EXPECTED_COVERAGE: f64 = 0.5;
+
+/// How many Minecraft frames an input needs for [`check_version`] to judge it.
+#[cfg(feature = "mojang")]
+const MIN_FRAMES: usize = 3;
+
+/// A version that covers too little of an input, and the neighboring version that fits it
+/// better, see [`check_version`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// How well the version the input is mapped with covers its Minecraft frames.
+    pub given: VersionScore,
+    /// The neighboring version that covers them best, if the neighbors were compared.
+    pub better: Option<VersionScore>,
+}
+
+/// Check that `version` fits the input, as a mostly unmapped result is usually from mapping with
+/// the wrong version. The `mapper` is the one for `version`.
+///
+/// Only the frames whose classes look like Minecraft's in the `from` names are considered. If
+/// less than half of them are mapped, that is a mismatch. With `compare_neighbors`, the two
+/// versions on either side of `version` in Mojang's manifest are scored too, which downloads their
+/// mappings, and it is only a mismatch if the best of them maps more.
+#[cfg(feature = "mojang")]
+pub fn check_version(
+    input: &str,
+    version: &str,
+    mapper: &impl MethodMapper,
+    from: NamesType,
+    to: NamesType,
+    compare_neighbors: bool,
+) -> Result<Option<VersionMismatch>, Report<SPError>> {
+    let frames: Vec<_> = distinct_frames(input)
+        .into_iter()
+        .filter(|f| is_minecraft_class(&f.class, from))
+        .collect();
+    if frames.len() < MIN_FRAMES {
+        return Ok(None);
+    }
+    let given = VersionScore {
+        version: version.to_string(),
+        frames: frames.len(),
+        mapped: count_mapped(&frames, mapper),
+    };
+    if given.coverage() >= EXPECTED_COVERAGE {
+        return Ok(None);
+    }
+    if !compare_neighbors {
+        return Ok(Some(VersionMismatch {
+            given,
+            better: None,
+        }));
+    }
+    let neighbors = crate::mappings::neighboring_versions(version, 2)?;
+    // Neighbors often lack mappings, e.g. old versions, so they are skipped quietly
+    let scores = score_versions(&frames, &neighbors, from, to, false)?;
+    Ok(scores
+        .into_iter()
+        .next()
+        .filter(|best| best.mapped > given.mapped)
+        .map(|better| VersionMismatch {
+            given,
+            better: Some(better),
+        }))
+}
+
+/// Check if a class looks like one of Minecraft's in the `names`, so its version's mappings
+/// should have it. Obfuscated classes have no package.
+#[cfg(feature = "mojang")]
+fn is_minecraft_class(class: &str, names: NamesType) -> bool {
+    match names {
+        NamesType::Obfuscated => !class.contains('.'),
+        NamesType::Mojang => {
+            class.starts_with("net.minecraft.") || class.starts_with("com.mojang.")
+        }
+        NamesType::FabricIntermediary => class.starts_with("net.minecraft.class_"),
+    }
+}
+
+/// Score each of the `versions` by how many of the `frames` its mappings cover, best first.
+/// Versions whose mappings fail to load are skipped, with a warning if `warn_skipped` is set.
+fn score_versions(
+    frames: &[Frame],
+    versions: &[String],
+    from: NamesType,
+    to: NamesType,
+    warn_skipped: bool,
+) -> Result<Vec<VersionScore>, Report<SPError>> {
+    let results = std::thread::scope(|s| {
+        let handles = versions
+            .iter()
+            .map(|version| {
+                s.spawn(move || {
+                    let mapper = generate_mapper(version.clone(), from, to)?;
+                    Ok::<_, Report<SPError>>(VersionScore {
+                        version: version.clone(),
+                        frames: frames.len(),
+                        mapped: count_mapped(frames, &mapper),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("mapper generation panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut scores = Vec::new();
+    let mut failure: Option<Report<SPError>> = None;
+    for (version, result) in versions.iter().zip(results) {
+        match result {
+            Ok(score) => scores.push(score),
+            Err(e) => {
+                if warn_skipped {
+                    tracing::warn!("Skipping {} for the guess: {:?}", version, e);
+                } else {
+                    tracing::debug!("Skipping {}: {:?}", version, e);
+                }
+                match &mut failure {
+                    Some(failure) => failure.extend_one(e),
+                    None => failure = Some(e),
+                }
+            }
+        }
+    }
+    if scores.is_empty() {
+        return Err(failure.unwrap_or_else(|| {
+            Report::new(SPError::UnknownVersion).attach_printable("No versions to guess from")
+        }));
+    }
+    scores.sort_by_key(|s| std::cmp::Reverse(s.mapped));
+    Ok(scores)
+}
+
+/// Count the frames whose class and method are both in the mappings.
+fn count_mapped(frames: &[Frame], mapper: &impl MethodMapper) -> usize {
+    frames
+        .iter()
+        .filter(|f| {
+            mapper.map_class(&f.class).is_some()
+                && !mapper.map_method(&f.class, &f.method, None).is_empty()
+        })
+        .count()
+}
+
+/// The distinct frames of every stacktrace in `input`, leaving out generated classes.
+fn distinct_frames(input: &str) -> Vec<Frame> {
+    let mut seen = HashSet::new();
+    input
+        .lines()
+        .filter(|line| line.trim_start().starts_with("at "))
+        .filter_map(parse_frame)
+        .filter(|f| !f.is_generated())
+        .filter(|f| seen.insert((f.class.clone(), f.method.clone())))
+        .collect()
+}
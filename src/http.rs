@@ -1,21 +1,86 @@
 use error_stack::{Report, ResultExt};
-use reqwest::blocking::Response;
-use serde::Deserialize;
 
+#[cfg(feature = "fabric")]
+use crate::transport::Method;
+use crate::transport::{transport, Request, Response};
 use crate::SPError;
 
-pub fn good_error_request_json<T: for<'de> Deserialize<'de>>(
+/// The status of a failed request, attached to its report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HttpStatus(u16);
+
+#[cfg(any(feature = "mojang", feature = "fabric"))]
+pub fn good_error_request_json<T: for<'de> serde::Deserialize<'de>>(
     url: &str,
 ) -> Result<T, Report<SPError>> {
-    good_error_request(url)?
-        .json()
-        .change_context(SPError)
+    serde_json::from_reader(good_error_request(url)?.body)
+        .change_context(SPError::NetworkError)
         .attach_printable_lazy(|| format!("Failed to parse JSON from {}", url))
 }
 
 pub fn good_error_request(url: &str) -> Result<Response, Report<SPError>> {
-    reqwest::blocking::get(url)
-        .and_then(|r| r.error_for_status())
-        .change_context(SPError)
-        .attach_printable_lazy(|| format!("Failed to make request to {}", url))
+    good_error_send(Request::get(url))
+}
+
+/// Like [`good_error_request`], but for the bytes from `offset` on. Servers that don't support
+/// this send everything, so check for `206 Partial Content`.
+pub fn good_error_request_from(url: &str, offset: u64) -> Result<Response, Report<SPError>> {
+    let mut request = Request::get(url);
+    if offset > 0 {
+        request
+            .headers
+            .push(("Range".to_string(), format!("bytes={}-", offset)));
+    }
+    good_error_send(request)
+}
+
+/// Like [`good_error_request`], but gives nothing if the resource still has the `etag`, i.e. the
+/// server responds `304 Not Modified`.
+#[cfg(any(feature = "mojang", feature = "fabric"))]
+pub fn good_error_request_if_changed(
+    url: &str,
+    etag: Option<&str>,
+) -> Result<Option<Response>, Report<SPError>> {
+    let mut request = Request::get(url);
+    if let Some(etag) = etag {
+        request
+            .headers
+            .push(("If-None-Match".to_string(), etag.to_string()));
+    }
+    let response = good_error_send(request)?;
+    Ok(Some(response).filter(|r| r.status != 304))
+}
+
+/// Check if a request from an offset failed because the offset is past the end.
+pub fn is_range_not_satisfiable(report: &Report<SPError>) -> bool {
+    report.downcast_ref::<HttpStatus>() == Some(&HttpStatus(416))
+}
+
+/// Like [`good_error_request`], but only for the headers.
+#[cfg(feature = "fabric")]
+pub fn good_error_head_request(url: &str) -> Result<Response, Report<SPError>> {
+    good_error_send(Request {
+        method: Method::Head,
+        ..Request::get(url)
+    })
+}
+
+/// Check if a request failed because the server doesn't have the resource.
+#[cfg(feature = "fabric")]
+pub fn is_not_found(report: &Report<SPError>) -> bool {
+    report.downcast_ref::<HttpStatus>() == Some(&HttpStatus(404))
+}
+
+/// Send the `request` with the [transport](crate::transport), failing for error statuses.
+fn good_error_send(request: Request) -> Result<Response, Report<SPError>> {
+    let response = transport()
+        .send(&request)
+        .attach_printable_lazy(|| format!("Failed to make request to {}", request.url))?;
+    if response.status >= 400 {
+        return Err(Report::new(SPError::NetworkError)
+            .attach(HttpStatus(response.status))
+            .attach_printable(format!("HTTP status {}", response.status))
+            .attach_printable(format!("Failed to make request to {}", request.url)));
+    }
+    Ok(response)
 }
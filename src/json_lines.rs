@@ -0,0 +1,100 @@
+use error_stack::{Report, ResultExt};
+use serde_json::Value;
+
+use crate::SPError;
+
+/// Field names that structured logging layouts use for the stacktrace text.
+const TRACE_FIELDS: &[&str] = &["stack_trace", "throwable"];
+
+/// Check if the input looks like JSON lines, i.e. every non-blank line is a JSON object.
+pub fn is_json_lines(input: &str) -> bool {
+    let mut lines = input.lines().filter(|l| !l.trim().is_empty()).peekable();
+    lines.peek().is_some()
+        && lines.all(|l| {
+            l.trim_start().starts_with('{')
+                && serde_json::from_str::<Value>(l).is_ok_and(|v| v.is_object())
+        })
+}
+
+/// Map every stacktrace embedded in the JSON lines, re-emitting each line with the mapped text.
+///
+/// Traces that fail to map are left as-is, since a log will often contain traces we don't
+/// understand.
+pub fn map_json_lines(
+    input: &str,
+    mut map_trace: impl FnMut(&str) -> Result<String, Report<SPError>>,
+) -> Result<String, Report<SPError>> {
+    let mut output = String::with_capacity(input.len());
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        }
+        let mut value: Value = serde_json::from_str(line)
+            .change_context(SPError::ParseError)
+            .attach_printable_lazy(|| format!("Failed to parse JSON on line {}", i + 1))?;
+        let mut mapped_any = false;
+        map_trace_fields(
+            &mut value,
+            &mut |trace| match map_embedded_trace(trace, &mut map_trace) {
+                Ok(mapped) => {
+                    mapped_any = true;
+                    Some(mapped)
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to map stacktrace on line {}: {:?}", i + 1, e);
+                    None
+                }
+            },
+        );
+        if mapped_any {
+            output.push_str(
+                &serde_json::to_string(&value)
+                    .change_context(SPError::ParseError)
+                    .attach_printable_lazy(|| format!("Failed to write JSON for line {}", i + 1))?,
+            );
+        } else {
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn map_trace_fields(value: &mut Value, map: &mut impl FnMut(&str) -> Option<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                match field {
+                    Value::String(trace) if TRACE_FIELDS.contains(&key.as_str()) => {
+                        if let Some(mapped) = map(trace) {
+                            *trace = mapped;
+                        }
+                    }
+                    _ => map_trace_fields(field, map),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for v in values {
+                map_trace_fields(v, map);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Map a trace embedded in a structured log, which may lack the final line ending.
+pub(crate) fn map_embedded_trace(
+    trace: &str,
+    map_trace: &mut impl FnMut(&str) -> Result<String, Report<SPError>>,
+) -> Result<String, Report<SPError>> {
+    // The parser requires the last frame to be terminated, but log fields often aren't.
+    if trace.ends_with('\n') {
+        map_trace(trace)
+    } else {
+        let mapped = map_trace(&format!("{}\n", trace))?;
+        Ok(mapped.trim_end_matches('\n').to_string())
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use stacked_portrayals::names::NamesType;
+
+/// The version and names of the last successful run, reused when none are given.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastRun {
+    pub version: String,
+    pub from_names: String,
+    pub to_names: String,
+}
+
+impl LastRun {
+    pub fn new(version: &str, from_names: NamesType, to_names: NamesType) -> Self {
+        Self {
+            version: version.to_string(),
+            from_names: from_names.to_string(),
+            to_names: to_names.to_string(),
+        }
+    }
+
+    /// The names of this run, if they are still known.
+    pub fn names(&self) -> Option<(NamesType, NamesType)> {
+        Some((self.from_names.parse().ok()?, self.to_names.parse().ok()?))
+    }
+
+    /// Load the last run from `last_run.json` in the data directory. A missing or unreadable file
+    /// is the same as no last run, as it only saves typing.
+    pub fn load() -> Option<Self> {
+        let path = state_path()?;
+        let text = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(last_run) => Some(last_run),
+            Err(e) => {
+                tracing::debug!("Ignoring invalid last run {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Save this as the last run. Failures are only logged, as the run itself succeeded.
+    pub fn save(&self) {
+        let Some(path) = state_path() else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| {
+                let json = serde_json::to_string(self).map_err(std::io::Error::other)?;
+                std::fs::write(&path, json)
+            });
+        if let Err(e) = result {
+            tracing::debug!("Failed to save the last run to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    ProjectDirs::from("net", "octyl", "stacked-portrayals")
+        .map(|dirs| dirs.data_local_dir().join("last_run.json"))
+}
@@ -0,0 +1,84 @@
+/// Normalize a mangled paste into something the stacktrace parser accepts.
+///
+/// This strips Markdown quote markers and code fences, re-indents frames with a tab,
+/// drops blank lines, and rejoins frames that were hard-wrapped across multiple lines.
+pub fn normalize(input: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    // Whether the previous line ended with whitespace before it was trimmed
+    let mut trailing_space = false;
+    for line in input.lines() {
+        let line = strip_quote_markers(line).replace('\u{a0}', " ");
+        let had_space = trailing_space || line.starts_with(char::is_whitespace);
+        trailing_space = line.ends_with(char::is_whitespace);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("```") {
+            continue;
+        }
+        if let Some(last) = lines.last_mut() {
+            if line.starts_with("~[") {
+                last.push(' ');
+                last.push_str(line);
+                continue;
+            }
+            if is_incomplete_frame(last) {
+                // Names can't have spaces, but locations can, e.g. `(Unknown Source)`
+                if had_space && last.contains('(') {
+                    last.push(' ');
+                }
+                last.push_str(line);
+                continue;
+            }
+        }
+        lines.push(match line.strip_prefix("at ") {
+            Some(frame) => format!("\tat {}", frame.trim_start()),
+            None => line.to_string(),
+        });
+    }
+    let mut output = lines.join("\n");
+    output.push('\n');
+    output
+}
+
+fn strip_quote_markers(mut line: &str) -> &str {
+    while let Some(rest) = line.trim_start().strip_prefix('>') {
+        line = rest;
+    }
+    line
+}
+
+/// A frame is incomplete if it hasn't opened and closed its location parentheses yet, or if it
+/// has an unterminated `~[...]` suffix.
+fn is_incomplete_frame(line: &str) -> bool {
+    if !line.starts_with("\tat ") {
+        return false;
+    }
+    let balance = |open, close| {
+        line.chars().filter(|&c| c == open).count() != line.chars().filter(|&c| c == close).count()
+    };
+    !line.contains('(') || balance('(', ')') || balance('[', ']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejoins_wrapped_names() {
+        assert_eq!(
+            normalize("\tat net.minecraft.client.Mine\ncraft.run(Minecraft.java:123)"),
+            "\tat net.minecraft.client.Minecraft.run(Minecraft.java:123)\n"
+        );
+    }
+
+    #[test]
+    fn keeps_spaces_in_wrapped_locations() {
+        assert_eq!(
+            normalize("\tat a.b.c(Unknown\n  Source)\n\tat a.b.d(Native \nMethod)"),
+            "\tat a.b.c(Unknown Source)\n\tat a.b.d(Native Method)\n"
+        );
+        assert_eq!(
+            normalize("\tat a.b.c(Unknown So\nurce)"),
+            "\tat a.b.c(Unknown Source)\n"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Mapping of Minecraft stacktraces between names, as used by the `stackp` tool.
+
+use derive_more::Display;
+use error_stack::Context;
+
+pub mod culprit;
+pub mod demo;
+pub mod document;
+pub mod free_text;
+pub mod guess;
+mod http;
+pub mod json_lines;
+pub mod lenient;
+pub mod log4j_xml;
+pub mod mappings;
+mod messages;
+mod mixin_dump;
+#[cfg(feature = "mojang")]
+mod mojang_api;
+pub mod names;
+pub mod parsing;
+pub mod progress;
+pub mod refmap;
+pub mod render;
+pub mod rewrite;
+pub mod sentry;
+pub mod stacktrace;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod transport;
+
+/// The kind of failure, carried as the context of an [`error_stack::Report`].
+///
+/// Match on [`Report::current_context`](error_stack::Report::current_context) to handle failures
+/// programmatically; the attachments describe the specific failure.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SPError {
+    /// The input or the mappings could not be parsed.
+    #[display(fmt = "failed to parse")]
+    ParseError,
+    /// A download failed.
+    #[display(fmt = "network request failed")]
+    NetworkError,
+    /// The version doesn't exist, or has no mappings of the requested kind.
+    #[display(fmt = "unknown version")]
+    UnknownVersion,
+    /// There is no way to map between the requested names.
+    #[display(fmt = "unsupported mapping path")]
+    UnsupportedPath,
+    /// The mappings cache could not be read or written.
+    #[display(fmt = "mappings cache failed")]
+    CacheError,
+    /// Reading or writing files other than the cache failed.
+    #[display(fmt = "I/O failed")]
+    IoError,
+}
+
+impl Context for SPError {}
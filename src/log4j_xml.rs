@@ -0,0 +1,179 @@
+//! Logs in Log4j's XML layout, which the vanilla launcher and other launchers export, e.g.
+//! `<log4j:Event logger="ekv" timestamp="1700000000000" level="ERROR" thread="Render thread">`
+//! with the stacktrace of the event in its `<log4j:Throwable><![CDATA[...]]></log4j:Throwable>`.
+
+use error_stack::Report;
+
+use crate::json_lines::map_embedded_trace;
+use crate::SPError;
+
+const EVENT_START: &str = "<log4j:Event";
+const EVENT_END: &str = "</log4j:Event>";
+const MESSAGE_START: &str = "<log4j:Message>";
+const MESSAGE_END: &str = "</log4j:Message>";
+const THROWABLE_START: &str = "<log4j:Throwable>";
+const THROWABLE_END: &str = "</log4j:Throwable>";
+const CDATA_START: &str = "<![CDATA[";
+const CDATA_END: &str = "]]>";
+
+/// Check if the input looks like a Log4j XML log, i.e. it starts with an event, possibly after an
+/// XML declaration. Only the start is checked, so this works on the start of a stream too.
+pub fn is_log4j_xml(input: &str) -> bool {
+    let input = input.trim_start();
+    let input = match input.strip_prefix("<?xml") {
+        Some(rest) => rest
+            .split_once("?>")
+            .map_or("", |(_, rest)| rest.trim_start()),
+        None => input,
+    };
+    input.starts_with(EVENT_START)
+}
+
+/// Map the stacktrace of every event, re-embedding each mapped trace in the XML as CDATA.
+///
+/// Traces that fail to map are left as-is, since a log will often contain traces we don't
+/// understand.
+pub fn map_log4j_xml(
+    input: &str,
+    mut map_trace: impl FnMut(&str) -> Result<String, Report<SPError>>,
+) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some((before, content, after)) = split_element(rest, THROWABLE_START, THROWABLE_END) {
+        output.push_str(before);
+        match map_embedded_trace(&decode_text(content), &mut map_trace) {
+            Ok(mapped) => output.push_str(&cdata(&mapped)),
+            Err(e) => {
+                tracing::warn!("Failed to map stacktrace of a Log4j event: {:?}", e);
+                output.push_str(content);
+            }
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Convert the events to plain log lines like the launcher shows them, e.g.
+/// `[12:00:00] [Render thread/ERROR]: Reported exception`, each followed by its stacktrace. The
+/// times are in UTC.
+pub fn log4j_xml_to_text(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some((_, content, after)) = split_element(rest, EVENT_START, EVENT_END) {
+        rest = after;
+        let Some((attributes, body)) = content.split_once('>') else {
+            continue;
+        };
+        if let Some(time) = attribute(attributes, "timestamp").and_then(|t| t.parse().ok()) {
+            output.push_str(&format!("[{}] ", format_time(time)));
+        }
+        output.push_str(&format!(
+            "[{}/{}]: ",
+            attribute(attributes, "thread").unwrap_or_default(),
+            attribute(attributes, "level").unwrap_or_default()
+        ));
+        if let Some((_, message, _)) = split_element(body, MESSAGE_START, MESSAGE_END) {
+            output.push_str(decode_text(message).trim_end());
+        }
+        output.push('\n');
+        if let Some((_, throwable, _)) = split_element(body, THROWABLE_START, THROWABLE_END) {
+            let throwable = decode_text(throwable);
+            output.push_str(&throwable);
+            if !throwable.is_empty() && !throwable.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+    }
+    output
+}
+
+/// Split the `text` around the contents of the first element between `start` and `end`, giving
+/// the text up to and including `start`, the contents, and the rest from `end`.
+fn split_element<'a>(text: &'a str, start: &str, end: &str) -> Option<(&'a str, &'a str, &'a str)> {
+    let content_start = text.find(start)? + start.len();
+    let content_end = content_start + text[content_start..].find(end)?;
+    Some((
+        &text[..content_start],
+        &text[content_start..content_end],
+        &text[content_end..],
+    ))
+}
+
+/// The unescaped value of the attribute `name` in the `attributes` of a start tag.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let start = attributes.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = start + attributes[start..].find('"')?;
+    Some(unescape(&attributes[start..end]))
+}
+
+/// The text of the contents of an element, from its CDATA sections and its escaped text.
+fn decode_text(content: &str) -> String {
+    let mut text = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(CDATA_START) {
+        text.push_str(&unescape(&rest[..start]));
+        let cdata = &rest[start + CDATA_START.len()..];
+        let end = cdata.find(CDATA_END).unwrap_or(cdata.len());
+        text.push_str(&cdata[..end]);
+        rest = cdata.get(end + CDATA_END.len()..).unwrap_or_default();
+    }
+    text.push_str(&unescape(rest));
+    text
+}
+
+/// Wrap `text` in a CDATA section, splitting it where the text contains the end of one.
+fn cdata(text: &str) -> String {
+    format!(
+        "{}{}{}",
+        CDATA_START,
+        text.replace(CDATA_END, "]]]]><![CDATA[>"),
+        CDATA_END
+    )
+}
+
+/// Replace the predefined entities and character references of XML in `text`. Anything else is
+/// kept as-is.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        rest = &rest[amp + 1..];
+        let entity = rest.split_once(';').and_then(|(entity, after)| {
+            let c = match entity {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => entity
+                    .strip_prefix("#x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#')?.parse().ok())
+                    .and_then(char::from_u32)?,
+            };
+            Some((c, after))
+        });
+        match entity {
+            Some((c, after)) => {
+                unescaped.push(c);
+                rest = after;
+            }
+            None => unescaped.push('&'),
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Format a timestamp in milliseconds since the Unix epoch as the time of day in UTC.
+fn format_time(millis: u64) -> String {
+    let seconds = millis / 1000 % 86400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -1,67 +1,717 @@
+use std::borrow::Cow;
 use std::fmt::Debug;
-use std::io::Read;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use clap::Parser;
-use derive_more::Display;
-use error_stack::{Context, Report, ResultExt};
+use clap::error::ErrorKind;
+use clap::{value_parser, ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use error_stack::{Report, ResultExt};
+use stacked_portrayals::culprit::suspect_culprit;
+use stacked_portrayals::document::DocumentChunks;
+use stacked_portrayals::guess::guess_version;
+#[cfg(feature = "mojang")]
+use stacked_portrayals::guess::{check_version, VersionMismatch};
+use stacked_portrayals::mappings::hints::{DescriptorHint, WithHints};
+use stacked_portrayals::mappings::mod_mappings::{ModMappings, WithModMappings};
+use stacked_portrayals::mappings::{
+    bundle, cache, describe_graph, explain_path, generate_mapper, hash_mappings, preload, source,
+    ClassMapper, Descriptor, EitherMapper, MapSelf, MethodMapper,
+};
+use stacked_portrayals::names::{find_srg_name, NamesType};
+use stacked_portrayals::parsing::{normalize_line_endings, ParseErrors};
+use stacked_portrayals::progress::{PROGRESS_TARGET, TIMINGS_TARGET};
+use stacked_portrayals::refmap::RefmapSelectors;
+use stacked_portrayals::render::{
+    ClassNames, ClassPattern, FrameTemplate, LinkStyle, Links, RenderOptions, DEFAULT_FOLD,
+};
+use stacked_portrayals::rewrite::rewrite_stacktrace;
+use stacked_portrayals::stacktrace::{parse_frame, parse_stacktrace, Stacktrace};
+use stacked_portrayals::transport::{
+    set_transport, RecordingTransport, ReplayTransport, ReqwestTransport,
+};
+use stacked_portrayals::{
+    demo, document, free_text, json_lines, lenient, log4j_xml, sentry, SPError,
+};
+use tracing::Subscriber;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter, Targets};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
 
-use crate::mappings::{generate_mapper, MapSelf};
-use crate::names::NamesType;
-use crate::parsing::ParseErrors;
-use crate::stacktrace::parse_stacktrace;
+use crate::batch::DEFAULT_SUFFIX;
+use crate::config::{apply_rules, Config, Rule, Stage};
+use crate::last_run::LastRun;
+use crate::output::{Clipboard, FileSink, HttpCallback, Mclogs, OutputSink, Stdout};
+use crate::timings::Timings;
 
-mod http;
-mod mappings;
-mod mojang_api;
-mod names;
-mod parsing;
-mod stacktrace;
+mod archive;
+mod batch;
+mod config;
+mod last_run;
+mod mods;
+mod output;
+mod timings;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
 
 /// Reads a stacktrace from stdin and maps the names according plan.
 ///
+/// Files or glob patterns can be given to map many inputs with the same mapper. Use `--log` for
+/// inputs such as crash reports that contain more than just a stacktrace.
+///
+/// If the input is JSON lines (e.g. from a JSON logging layout), the stacktraces in their
+/// `stack_trace` or `throwable` fields are mapped instead. If it is a Sentry event, the frames of
+/// its exceptions are mapped. If it is a Log4j XML log, as exported by launchers, the stacktraces
+/// of its events are mapped, keeping the XML unless `--xml-to-text` is given.
+///
 /// Note that a stacktrace cannot uniquely identify a method, so the mapping
-/// may give multiple results. In this case, the methods are joined with a `/`.
-#[derive(Parser, Debug)]
-#[clap(version)]
+/// may give multiple results. In this case, the methods are joined with a `/`, or the
+/// `--candidate-separator`.
+#[derive(Parser, Debug, Clone)]
+#[clap(
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    group(ArgGroup::new("file_inputs").args(["files", "watch_dir"]).multiple(true))
+)]
 struct StackedPortrayals {
-    /// The version of Minecraft to use.
-    mc_version: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// The version of Minecraft to use. Leave this out with `--guess-version` or `--normalize`.
+    ///
+    /// Leave out the version and the names to reuse those of the last successful run, e.g. to map
+    /// another trace from stdin the same way.
+    mc_version: Option<String>,
     /// The names to start with.
     ///
     #[doc = include_str!("docs/name_types.md")]
-    from_names: NamesType,
+    from_names: Option<NamesType>,
     /// The names to end with.
     ///
     #[doc = include_str!("docs/name_types.md")]
-    to_names: NamesType,
+    to_names: Option<NamesType>,
+    /// Files or glob patterns to map instead of reading from stdin.
+    files: Vec<String>,
+    /// Map this text instead of reading from stdin, or read stdin if it is `-`.
+    ///
+    /// Escaped newlines and tabs (`\n`, `\t`) are expanded, and `\\` is a backslash, so a
+    /// whole trace fits in one shell argument.
+    #[clap(long, value_name = "TEXT", conflicts_with_all = ["files", "watch_dir"])]
+    trace: Option<String>,
+    /// The largest input to read from stdin, in megabytes, or 0 for no limit.
+    ///
+    /// Larger input fails instead of filling the memory, unless it's a log mapped as it's read,
+    /// see `--log`.
+    #[clap(long, value_name = "MB", default_value_t = 64)]
+    max_input_mb: u64,
+    /// Write the mapped output for each file next to it, with this suffix replacing the extension.
+    ///
+    /// Without this, the output for each file is written to stdout. Only files and
+    /// `--watch-dir` have a place to write next to.
+    #[clap(
+        long,
+        value_name = "SUFFIX",
+        require_equals = true,
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_SUFFIX,
+        requires = "file_inputs"
+    )]
+    suffix: Option<String>,
+    /// Watch a directory, such as `crash-reports`, and write a mapped copy of every new file next
+    /// to it.
+    ///
+    /// The files are mapped in log mode, and the copies use the `--suffix`.
+    #[clap(long, value_name = "DIR", conflicts_with = "files")]
+    watch_dir: Option<PathBuf>,
+    /// Treat the input as a log or crash report, mapping every stacktrace in it and leaving the
+    /// rest of the text as-is.
+    ///
+    /// A log from stdin is mapped and printed as it's read, so it can be of any size, unless the
+    /// output goes elsewhere or needs the whole log, e.g. with `--header` or `--lenient`.
+    #[clap(long)]
+    log: bool,
+    /// Convert a Log4j XML log to plain log lines, e.g. `[12:00:00] [main/ERROR]: message`, with
+    /// the stacktraces mapped, instead of keeping the XML. The times are in UTC.
+    #[clap(long, conflicts_with_all = ["free_text", "normalize"])]
+    xml_to_text: bool,
+    /// Fold runs of frames from these classes into a single line.
+    ///
+    /// Patterns are comma-separated class names, where a trailing `*` matches any suffix.
+    /// If no patterns are given, JDK and common library packages are folded.
+    #[clap(
+        long,
+        value_name = "PATTERNS",
+        require_equals = true,
+        num_args = 0..=1,
+        value_delimiter = ',',
+        default_missing_values = DEFAULT_FOLD
+    )]
+    fold: Option<Vec<ClassPattern>>,
+    /// Collapse runs of frames in a cause that were already printed further up into
+    /// `... n common frames omitted ...`, e.g. for long cause chains that repeat the same frames.
+    ///
+    /// Frames are compared after mapping, so they must render the same, including their lines.
+    #[clap(long)]
+    collapse_common: bool,
+    /// Render each frame with this template instead of the standard `at ...` form.
+    ///
+    /// The placeholders are `{module}`, `{class}`, `{method}`, `{file}`, `{line}`,
+    /// `{candidates}`, the number of candidate mappings for the method, `{confidence}`, see
+    /// `--confidence`, and `{original}`, the class and method before mapping. Missing values are
+    /// empty, and braces can be escaped by doubling them.
+    /// For example, `{class}#{method} ({file}:{line}) [{candidates}]`.
+    #[clap(long, value_name = "TEMPLATE")]
+    format: Option<FrameTemplate>,
+    /// Print the mapped stacktrace as JSON, including every frame and its candidate mappings.
+    ///
+    /// Each candidate has its name, descriptor, class, confidence and the mappings it came from.
+    /// Frames are never folded, and `--format` is ignored.
+    #[cfg(feature = "serde")]
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "log", "exact", "links", "free_text", "normalize", "fingerprint", "dedupe", "culprit"
+        ]
+    )]
+    json: bool,
+    /// Rewrite only the mapped names in the input, keeping everything else byte-for-byte, instead
+    /// of rendering the mapped stacktrace.
+    #[clap(long, conflicts_with_all = ["fold", "format", "collapse_common"])]
+    exact: bool,
+    /// Link mapped methods to their details on Linkie, for the same version and names.
+    ///
+    /// By default the names become terminal hyperlinks, which are clickable in most terminals.
+    /// Use `--links=url` to append the URLs instead, e.g. when the output is pasted elsewhere.
+    /// Frames in logs or rendered with `--format` are not linked.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        require_equals = true,
+        num_args = 0..=1,
+        default_missing_value = "hyperlink",
+        conflicts_with = "exact"
+    )]
+    links: Option<LinkStyleArg>,
+    /// Mark each mapped frame with how confident its mapping is, e.g. `[medium]`.
+    ///
+    /// A method found in its class is `medium`, as frames have no descriptors to match exactly.
+    /// A method that wasn't in its class, but is the only one with its name, is `low`. The JSON
+    /// output always includes the confidence.
+    #[clap(long, conflicts_with = "exact")]
+    confidence: bool,
+    /// Show the descriptor of each candidate mapping of ambiguous methods, e.g.
+    /// `tick()V/tick(Z)V`, instead of each distinct name once.
+    #[clap(long, conflicts_with_all = ["exact", "format"])]
+    show_descriptors: bool,
+    /// Separate the candidate mappings of ambiguous methods with this instead of `/`, e.g. `|`
+    /// for tools that parse the output.
+    #[clap(long, value_name = "SEP", conflicts_with_all = ["exact", "format"])]
+    candidate_separator: Option<String>,
+    /// Show at most this many candidate mappings of ambiguous methods, followed by how many more
+    /// there are, e.g. `tick/tickEntities (+3 more)`. The JSON output always has every candidate.
+    #[clap(long, value_name = "N", conflicts_with_all = ["exact", "format"])]
+    max_candidates: Option<NonZeroUsize>,
+    /// How to show the classes of frames and the types of exceptions, e.g. shorter for chat
+    /// messages. The JSON output always has fully qualified names.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        default_value = "qualified",
+        conflicts_with = "exact"
+    )]
+    class_names: ClassNamesArg,
+    /// Keep the original names of each mapped frame after it, e.g.
+    /// `net.minecraft.client.Minecraft.run(Minecraft.java:123) [was: dyr.e]`, to cross-reference
+    /// the mapped trace with the original report.
+    #[clap(long, conflicts_with_all = ["exact", "format"])]
+    annotate_original: bool,
+    /// Render frames in the plain form IntelliJ's "Analyze Stacktrace" links to its sources, so
+    /// the output can be pasted straight into the IDE.
+    ///
+    /// Ambiguous methods get one candidate, preferring one in the frame's class, then the most
+    /// confident, then the first by name. Obfuscated `SourceFile` locations are named after the
+    /// class, e.g. `Minecraft.java`.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "exact", "fold", "format", "links", "confidence", "show_descriptors",
+            "annotate_original", "candidate_separator", "max_candidates", "class_names"
+        ]
+    )]
+    ide: bool,
+    /// Write the mapped output to this file instead of printing it.
+    ///
+    /// This can be combined with the other destinations, e.g. `--clipboard`. The output is only
+    /// printed if none is given.
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["files", "watch_dir"])]
+    output: Option<PathBuf>,
+    /// Copy the mapped output to the clipboard instead of printing it, with `pbcopy`, `clip`,
+    /// `wl-copy`, `xclip` or `xsel`, whichever is available.
+    #[clap(long, conflicts_with_all = ["files", "watch_dir"])]
+    clipboard: bool,
+    /// Upload the mapped output to mclo.gs and print the link to it instead of the output, e.g.
+    /// to share it in a support channel.
+    #[clap(long, conflicts_with_all = ["files", "watch_dir"])]
+    upload: bool,
+    /// Post the mapped output as plain text to this URL instead of printing it, e.g. to hand it
+    /// back to a bot.
+    #[clap(long, value_name = "URL", conflicts_with_all = ["files", "watch_dir"])]
+    post_to: Option<String>,
+    /// Start the output with a comment saying what it was mapped with, e.g.
+    /// `# mapped with stacked-portrayals v0.1.0, 1.20.1 obf->mojang, mojang maps sha1=..., on
+    /// 2024-05-01T12:00:00Z`, so archived reports can be traced back to the exact mappings.
+    ///
+    /// JSON output can't have comments, so it never gets a header.
+    #[clap(long)]
+    header: bool,
+    /// Guess the version instead of giving it, picking whichever of these comma-separated
+    /// versions has mappings for the most frames of the input, e.g.
+    /// `--guess-version=1.20,1.20.1,1.20.4`.
+    ///
+    /// Without versions, the 10 most recent releases are tried. The score of each version is
+    /// logged, so check it when the best ones are close.
+    #[clap(
+        long,
+        value_name = "VERSIONS",
+        require_equals = true,
+        num_args = 0..=1,
+        value_delimiter = ',',
+        conflicts_with_all = ["files", "watch_dir"]
+    )]
+    guess_version: Option<Vec<String>>,
+    /// If most of the Minecraft frames of the input aren't mapped, compare the versions next to
+    /// the given one, and name the one that maps the most, e.g. `This trace looks like 1.20.2`.
+    ///
+    /// This downloads the mappings of up to four more versions, so without it, only the number of
+    /// mapped frames is pointed out.
+    #[cfg(feature = "mojang")]
+    #[clap(long, conflicts_with_all = ["guess_version", "demo"])]
+    check_version: bool,
+    /// Treat the input as free text, e.g. a forum post, a mixin config, or decompiled code, and
+    /// map every known class, method and field name in it instead of looking for stacktraces.
+    ///
+    /// Classes must be qualified. Methods stand alone only if their name is unique and can't be
+    /// an ordinary word, e.g. `method_1234`, otherwise they must be qualified by their class.
+    /// Fields must be qualified, e.g. `abc.b`.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "log", "exact", "fold", "collapse_common", "format", "links", "guess_version"
+        ]
+    )]
+    free_text: bool,
+    /// Reformat the stacktraces in the input canonically instead of mapping them, e.g. to diff
+    /// traces from different sources. No version or names are needed, so every positional argument
+    /// is a file.
+    ///
+    /// Frames are indented with a tab, each cause gets its own `Caused by: ` line, and logger
+    /// prefixes are stripped from the lines of traces. The rest of the input is left as-is.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "watch_dir", "log", "exact", "fold", "collapse_common", "format", "links",
+            "free_text", "guess_version", "header"
+        ]
+    )]
+    normalize: bool,
+    /// Map a bundled example trace from obfuscated names to these names, `mojang` by default,
+    /// with bundled sample mappings instead of a version's. Nothing is downloaded, so this checks
+    /// that the installation works, even without network access.
+    #[clap(
+        long,
+        value_name = "NAMES",
+        require_equals = true,
+        num_args = 0..=1,
+        default_missing_value = "mojang",
+        conflicts_with_all = [
+            "mc_version", "files", "trace", "watch_dir", "guess_version", "normalize"
+        ]
+    )]
+    demo: Option<NamesType>,
+    /// Print a fingerprint of the crash instead of the mapped output, e.g. to tell whether two
+    /// reports are of the same crash. It is a hash of the mapped type and top frames of the root
+    /// cause of the first stacktrace in the input.
+    #[clap(
+        long,
+        conflicts_with_all = ["watch_dir", "exact", "free_text", "normalize", "header"]
+    )]
+    fingerprint: bool,
+    /// How many of the top frames of the root cause go into a fingerprint.
+    #[clap(long, value_name = "N", default_value_t = 5)]
+    fingerprint_frames: usize,
+    /// Group the files by the fingerprint of their crash instead of mapping them, printing each
+    /// group with its files, e.g. to find the duplicates among hundreds of crash reports. See
+    /// `--fingerprint`.
+    #[clap(
+        long,
+        requires = "files",
+        conflicts_with_all = ["suffix", "exact", "free_text", "normalize", "header", "fingerprint"]
+    )]
+    dedupe: bool,
+    /// After the mapped output, print a line naming the mod or class most plausibly responsible
+    /// for the crash, e.g. `Likely culprit: mod examplemod (...)`.
+    ///
+    /// It is guessed from the mixin handlers in the first stacktrace, its topmost frame outside
+    /// the JDK, libraries and the game, and the JARs Log4j printed for its frames, so treat it as
+    /// a hint.
+    #[clap(long, conflicts_with_all = ["free_text", "normalize", "fingerprint", "dedupe"])]
+    culprit: bool,
+    /// Open an interactive terminal UI to paste stacktraces into, showing the mapped trace and the
+    /// candidates for each frame, instead of reading stdin.
+    ///
+    /// The names can be switched in the UI, and mappers stay loaded while it runs.
+    #[cfg(feature = "tui")]
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "files", "watch_dir", "trace", "log", "free_text", "guess_version", "normalize"
+        ]
+    )]
+    tui: bool,
+    /// Resolve the mixin selectors in the input, e.g. in mixin errors, to the names the mod
+    /// developers wrote, using the refmaps of the mod jars in this folder.
+    #[clap(long, value_name = "DIR")]
+    mods: Option<PathBuf>,
+    /// Also map the classes of a mod that obfuscates them itself, with the ProGuard mappings it
+    /// ships, e.g. `--extra-mapping examplemod=jar:mods/examplemod.jar!/META-INF/mappings.txt`.
+    /// Can be repeated.
+    ///
+    /// The mappings can also be a plain file. The classes in them are mapped with them even if
+    /// the Minecraft mappings have a class of the same name.
+    #[clap(long, value_name = "MODID=LOCATION", value_parser = parse_extra_mapping)]
+    extra_mapping: Vec<(String, String)>,
+    /// Narrow the candidates of an ambiguous method to the overload with these parameters, e.g.
+    /// `--hint "abc.d.e=(int,java.lang.String)"` for the method `e` of the class `abc.d`. Can be
+    /// repeated, and hints can be given in the `hints` of the config too.
+    ///
+    /// The method is named as in the input, and the parameter types as in the output, as in
+    /// decompiled sources. Classes may be given by their simple name.
+    #[clap(long = "hint", value_name = "HINT")]
+    hints: Vec<DescriptorHint>,
+    /// Normalize mangled input before parsing.
+    ///
+    /// This strips quote markers and code fences, fixes frame indentation, and rejoins frames
+    /// that were hard-wrapped across lines, e.g. when pasted from Discord or a narrow terminal.
+    #[clap(long)]
+    lenient: bool,
+    /// Use the mappings at a path or URL instead of those of a source, e.g.
+    /// `--override-source mojang=client-patched.txt`, for every version. Can be repeated.
+    ///
+    /// The sources are listed by `resolve-path`. The mappings are not verified, so this needs
+    /// `--insecure-override`.
+    #[clap(
+        long,
+        value_name = "SOURCE=LOCATION",
+        value_parser = parse_source_override,
+        requires = "insecure_override",
+        global = true
+    )]
+    override_source: Vec<(String, String)>,
+    /// Acknowledge that the mappings of `--override-source` are used without checking their
+    /// hashes.
+    #[clap(long, global = true)]
+    insecure_override: bool,
+    /// Ignore the cached mappings, downloading and verifying them again and replacing the cache,
+    /// e.g. when an artifact was republished under the same version.
+    ///
+    /// The Gradle cache of Fabric Loom is ignored too. Version manifests are never cached.
+    #[clap(long, global = true)]
+    refresh: bool,
+    /// Download mappings that have no checksum to verify them with, only checking their size if
+    /// it is known, e.g. old intermediary artifacts. A warning is logged for each.
+    #[clap(long, global = true)]
+    allow_unverified: bool,
+    /// Save every HTTP response to this directory, e.g. to attach to a bug report so the mapping
+    /// can be reproduced with `--replay`. Implies `--refresh`, so the mappings are recorded too.
+    #[clap(long, value_name = "DIR", conflicts_with = "replay", global = true)]
+    record: Option<PathBuf>,
+    /// Answer HTTP requests with the responses saved by `--record` to this directory, without
+    /// any network access.
+    #[clap(long, value_name = "DIR", global = true)]
+    replay: Option<PathBuf>,
+    /// Report progress on stderr in this format, alongside the usual logging.
+    #[clap(long, value_enum)]
+    progress: Option<ProgressFormat>,
+    /// The format of the logs, e.g. `json` when they are collected by a bot or service.
+    #[clap(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+    /// Also write the logs to this file, at the verbosity given by `-v`, while the terminal only
+    /// gets the usual logging, e.g. to attach a debug log to a bug report.
+    #[clap(long, global = true)]
+    log_file: Option<PathBuf>,
+    /// Report on stderr how long was spent fetching manifests, downloading, parsing and indexing
+    /// mappings, and mapping frames, e.g. to tell whether slowness comes from the network.
+    #[clap(long, global = true)]
+    timings: bool,
+    /// Read the config from this file instead of `config.json` in the config directory of the
+    /// user, e.g. `~/.config/stacked-portrayals/config.json` on Linux.
+    ///
+    /// The config is JSON. Its `rules` rewrite or drop lines of the input before it is parsed, or
+    /// of the output after it is mapped, e.g. to strip the log prefixes of a hosting panel or to
+    /// drop the frames of a noisy agent:
+    /// `{"rules": [{"stage": "input", "pattern": "^\\[Panel\\] ", "replace": ""},
+    /// {"stage": "output", "pattern": "at com\\.example\\.agent\\.", "drop": true}]}`.
+    /// Patterns are regexes matched against each line, and `replace` can refer to their groups,
+    /// e.g. `$1`. Rules are applied in order. Its `hints` are added to those of `--hint`, e.g.
+    /// `{"hints": ["abc.d.e=(int,java.lang.String)"]}`.
+    #[clap(long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+    /// The rules of the config, loaded after parsing the arguments.
+    #[clap(skip)]
+    rules: Vec<Rule>,
     /// Verbosity level, repeat to increase.
     #[clap(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 }
 
-#[derive(Debug, Display)]
-pub struct SPError;
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per event, with its fields and spans.
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ProgressFormat {
+    /// One JSON object per event, with the kind of event in its `event` field.
+    Json,
+}
+
+fn parse_extra_mapping(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((mod_id, location)) if !mod_id.is_empty() && !location.is_empty() => {
+            Ok((mod_id.to_string(), location.to_string()))
+        }
+        _ => Err("expected MODID=LOCATION, e.g. examplemod=jar:mod.jar!/mappings.txt".to_string()),
+    }
+}
+
+fn parse_source_override(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((source, location)) if !source.is_empty() && !location.is_empty() => {
+            Ok((source.to_string(), location.to_string()))
+        }
+        _ => Err("expected SOURCE=LOCATION, e.g. mojang=client.txt".to_string()),
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ClassNamesArg {
+    /// Fully qualified, e.g. `net.minecraft.client.Minecraft`.
+    Qualified,
+    /// Without the package, e.g. `Minecraft`.
+    Simple,
+    /// With every package but the innermost abbreviated, e.g. `n.m.client.Minecraft`.
+    Abbreviated,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum LinkStyleArg {
+    /// Terminal hyperlinks on the mapped names.
+    Hyperlink,
+    /// The URL after each mapped frame.
+    Url,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Download and parse the mappings for the given versions ahead of time, e.g. before going
+    /// offline.
+    Preload {
+        /// The versions of Minecraft to preload. Ranges such as `1.20..1.20.4` and patterns
+        /// such as `1.19.*` are expanded with Mojang's manifest.
+        #[clap(required = true, value_delimiter = ',')]
+        versions: Vec<String>,
+        /// The names to preload the mappings for, comma-separated.
+        #[clap(long, value_delimiter = ',', default_value = "mojang,fabric")]
+        namespaces: Vec<NamesType>,
+    },
+    /// Print the mappings graph: the names that can be mapped between, and the sources that map
+    /// between them, including overridden ones. Nothing is downloaded.
+    Graph {
+        /// Also say where each source would get the mappings for this version.
+        version: Option<String>,
+        /// Print the graph in Graphviz's DOT language instead, e.g. for `dot -Tsvg`.
+        #[clap(long)]
+        dot: bool,
+    },
+    /// Explain how names would be mapped for a version: the steps between names, and the sources
+    /// that could provide each step. Nothing is downloaded.
+    ResolvePath {
+        /// The version of Minecraft to use.
+        version: String,
+        /// The names to start with.
+        from_names: NamesType,
+        /// The names to end with.
+        to_names: NamesType,
+    },
+    /// Load the mapper for a version and print what it maps, e.g. to sanity-check a new mapping
+    /// source.
+    Stats {
+        /// The version of Minecraft to use.
+        version: String,
+        /// The names to start with.
+        from_names: NamesType,
+        /// The names to end with.
+        to_names: NamesType,
+    },
+    /// Explain how a single frame is mapped: every candidate for its method, the step that
+    /// produced it, and the heuristics that found or rejected it.
+    Explain {
+        /// The version of Minecraft to use.
+        version: String,
+        /// The names to start with.
+        from_names: NamesType,
+        /// The names to end with.
+        to_names: NamesType,
+        /// The frame to explain, e.g. `at abc.d(SourceFile:10)`.
+        #[clap(long)]
+        frame: String,
+    },
+    /// Map a stacktrace from stdin with several versions and compare the frames, marking the ones
+    /// whose mapped names differ, e.g. to check whether a crash is version-specific.
+    Compare {
+        /// The names to start with.
+        from_names: NamesType,
+        /// The names to end with.
+        to_names: NamesType,
+        /// The versions of Minecraft to compare. Ranges such as `1.20..1.20.4` and patterns such
+        /// as `1.19.*` are expanded with Mojang's manifest.
+        #[clap(required = true, value_delimiter = ',')]
+        versions: Vec<String>,
+    },
+    /// Move mappings between machines, e.g. to one without network access.
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommand,
+    },
+}
 
-impl Context for SPError {}
+#[derive(Subcommand, Debug, Clone)]
+enum CacheCommand {
+    /// Fetch the mappings for the given versions and write them to a bundle, e.g.
+    /// `mappings.tar.zst`.
+    Export {
+        /// The bundle to write.
+        file: PathBuf,
+        /// The versions of Minecraft to bundle. Ranges such as `1.20..1.20.4` and patterns such
+        /// as `1.19.*` are expanded with Mojang's manifest.
+        #[clap(required = true, value_delimiter = ',')]
+        versions: Vec<String>,
+        /// The names to bundle the mappings for, comma-separated.
+        #[clap(long, value_delimiter = ',', default_value = "mojang,fabric")]
+        namespaces: Vec<NamesType>,
+    },
+    /// Import a bundle written by `cache export`, so its mappings are used instead of fetching
+    /// them.
+    Import {
+        /// The bundle to read.
+        file: PathBuf,
+    },
+    /// Re-check every cached mappings file against its hash, e.g. after disk issues or
+    /// interrupted runs.
+    Verify {
+        /// Delete the corrupt files, so they are downloaded again when needed.
+        #[clap(long)]
+        delete: bool,
+    },
+}
 
 fn main() -> Result<(), Report<SPError>> {
-    let args = StackedPortrayals::parse();
-    let env_filt = tracing_subscriber::filter::EnvFilter::builder()
-        .with_default_directive(
-            match args.verbose {
-                0 => tracing_subscriber::filter::LevelFilter::INFO,
-                1 => tracing_subscriber::filter::LevelFilter::DEBUG,
-                _ => tracing_subscriber::filter::LevelFilter::TRACE,
-            }
-            .into(),
+    let mut args = parse_args();
+    if args.guess_version.is_some() && args.command.is_none() {
+        shift_guessed_version(&mut args);
+    }
+    let level = match args.verbose {
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    // With a log file, the verbose logs only go there
+    let terminal_level = if args.log_file.is_some() {
+        LevelFilter::INFO
+    } else {
+        level
+    };
+    let file_layer = match &args.log_file {
+        Some(path) => {
+            let file = File::create(path)
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to create {}", path.display()))?;
+            Some(log_layer(
+                args.log_format,
+                std::sync::Mutex::new(file),
+                false,
+                env_filter(level),
+            ))
+        }
+        None => None,
+    };
+    let progress_layer = args.progress.map(|ProgressFormat::Json| {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_target(false)
+            .with_level(false)
+            .with_writer(std::io::stderr)
+            .with_filter(Targets::new().with_target(PROGRESS_TARGET, LevelFilter::INFO))
+    });
+    // The terminal UI owns the terminal, and shows failures itself
+    #[cfg(feature = "tui")]
+    let logging = !args.tui;
+    #[cfg(not(feature = "tui"))]
+    let logging = true;
+    // Windows consoles only show colors once asked to
+    let ansi = anstyle_query::windows::enable_ansi_colors().unwrap_or(true);
+    let terminal_layer = logging.then(|| {
+        log_layer(
+            args.log_format,
+            std::io::stderr,
+            ansi,
+            env_filter(terminal_level),
         )
-        .from_env_lossy()
-        // Set some loud things to warn
-        .add_directive("reqwest=warn".parse().unwrap())
-        .add_directive("hyper=warn".parse().unwrap());
-    tracing_subscriber::fmt().with_env_filter(env_filt).init();
+    });
+    let timings = args.timings.then(Timings::default);
+    let timings_layer = timings.as_ref().map(|timings| {
+        timings
+            .layer()
+            .with_filter(Targets::new().with_target(TIMINGS_TARGET, LevelFilter::INFO))
+    });
+    tracing_subscriber::registry()
+        .with(terminal_layer)
+        .with(file_layer)
+        .with(progress_layer)
+        .with(timings_layer)
+        .init();
 
-    if let Err(e) = main_for_result(args) {
+    if needs_mapping_args(&args) {
+        reuse_last_run(&mut args);
+    }
+    let last_run = match (&args.mc_version, args.from_names, args.to_names) {
+        (Some(version), Some(from_names), Some(to_names))
+            if needs_mapping_args(&args) && args.demo.is_none() =>
+        {
+            Some(LastRun::new(version, from_names, to_names))
+        }
+        _ => None,
+    };
+    let result = main_for_result(args);
+    if let (Ok(()), Some(last_run)) = (&result, last_run) {
+        last_run.save();
+    }
+    if let Some(timings) = &timings {
+        timings.print();
+    }
+    if let Err(e) = result {
         if let Some(parse) = e.downcast_ref::<ParseErrors>() {
             parse.eprint();
         }
@@ -71,30 +721,1207 @@ fn main() -> Result<(), Report<SPError>> {
     Ok(())
 }
 
-fn main_for_result(args: StackedPortrayals) -> Result<(), Report<SPError>> {
-    let stacktrace = {
-        let mut buf = String::new();
-        tracing::info!("Enter stacktrace (Ctrl+D to finish):");
-        std::io::stdin()
-            .read_to_string(&mut buf)
-            .change_context(SPError)
-            .attach_printable("Failed to read stacktrace from stdin")?;
-        buf
+fn env_filter(level: LevelFilter) -> EnvFilter {
+    EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy()
+        // Set some loud things to warn
+        .add_directive("reqwest=warn".parse().unwrap())
+        .add_directive("hyper=warn".parse().unwrap())
+        // Progress events are only for the progress layer, and timing spans for the timings
+        .add_directive(format!("{}=off", PROGRESS_TARGET).parse().unwrap())
+        .add_directive(format!("{}=off", TIMINGS_TARGET).parse().unwrap())
+}
+
+/// A layer writing the logs that pass the `filter` in the `format`, with colors if `ansi`.
+fn log_layer<S, W>(
+    format: LogFormat,
+    writer: W,
+    ansi: bool,
+    filter: EnvFilter,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.with_filter(filter).boxed(),
+        LogFormat::Json => layer.json().with_filter(filter).boxed(),
+    }
+}
+
+/// Check if the arguments need a version and names, i.e. there's no subcommand and they aren't
+/// guessed, normalized away or given by `--demo`.
+fn needs_mapping_args(args: &StackedPortrayals) -> bool {
+    args.command.is_none() && args.guess_version.is_none() && !args.normalize && args.demo.is_none()
+}
+
+/// The version and names to map with.
+///
+/// # Panics
+/// If they are missing, which they only are with a subcommand or `--normalize`, as they are
+/// otherwise given, guessed, set by `--demo` or reused by [`reuse_last_run`] before mapping.
+fn mapping_args(args: &StackedPortrayals) -> (&str, NamesType, NamesType) {
+    let (Some(version), Some(from_names), Some(to_names)) =
+        (&args.mc_version, args.from_names, args.to_names)
+    else {
+        unreachable!("the version and names are filled in before mapping");
+    };
+    (version, from_names, to_names)
+}
+
+/// Fill in the version and names of the last successful run if none are given, or fail like clap
+/// would if only some are.
+fn reuse_last_run(args: &mut StackedPortrayals) {
+    match (&args.mc_version, args.from_names, args.to_names) {
+        (Some(_), Some(_), Some(_)) => return,
+        (None, None, None) => {}
+        _ => StackedPortrayals::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the version and the names to start and end with are required",
+            )
+            .exit(),
+    }
+    let Some((last_run, (from_names, to_names))) =
+        LastRun::load().and_then(|r| r.names().map(|names| (r, names)))
+    else {
+        StackedPortrayals::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the version and the names to start and end with are required, \
+                 as there is no previous run to reuse",
+            )
+            .exit();
+    };
+    tracing::info!(
+        "Reusing the last run: {} {} {}",
+        last_run.version,
+        from_names,
+        to_names
+    );
+    args.mc_version = Some(last_run.version);
+    args.from_names = Some(from_names);
+    args.to_names = Some(to_names);
+}
+
+/// Parse the arguments. With `--normalize`, clap puts the first files where the version and names
+/// go, so they are moved to the files.
+fn parse_args() -> StackedPortrayals {
+    // The names are taken as plain text at first, as they may be files
+    let mut matches = StackedPortrayals::command()
+        .mut_args(|arg| match arg.get_id().as_str() {
+            "from_names" | "to_names" => arg.value_parser(value_parser!(String)),
+            _ => arg,
+        })
+        .get_matches();
+    if !matches.get_flag("normalize") {
+        return StackedPortrayals::parse();
+    }
+    let files = ["mc_version", "from_names", "to_names"]
+        .into_iter()
+        .filter_map(|id| matches.remove_one::<String>(id))
+        .collect::<Vec<_>>();
+    let mut args =
+        StackedPortrayals::from_arg_matches_mut(&mut matches).unwrap_or_else(|e| e.exit());
+    args.files.splice(0..0, files);
+    args
+}
+
+/// Without a version, clap puts the names in the wrong positions, so move them to where they
+/// belong.
+fn shift_guessed_version(args: &mut StackedPortrayals) {
+    if args.to_names.is_some() {
+        StackedPortrayals::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the version can't be given with '--guess-version'",
+            )
+            .exit();
+    }
+    let from_names = match args.mc_version.take().map(|n| n.parse::<NamesType>()) {
+        Some(Ok(from_names)) => from_names,
+        Some(Err(_)) | None => StackedPortrayals::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "'--guess-version' requires the names to start and end with",
+            )
+            .exit(),
+    };
+    args.to_names = args.from_names.replace(from_names);
+    if args.to_names.is_none() {
+        StackedPortrayals::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "'--guess-version' requires the names to start and end with",
+            )
+            .exit();
+    }
+}
+
+fn main_for_result(mut args: StackedPortrayals) -> Result<(), Report<SPError>> {
+    if let Some(dir) = &args.record {
+        set_transport(RecordingTransport::new(ReqwestTransport::default(), dir)?);
+        cache::refresh_cache();
+    }
+    if let Some(dir) = &args.replay {
+        set_transport(ReplayTransport::new(dir)?);
+    }
+    if args.refresh {
+        cache::refresh_cache();
+    }
+    if args.allow_unverified {
+        cache::allow_unverified();
+    }
+    if args.header {
+        hash_mappings();
+    }
+    for (source, location) in &args.override_source {
+        source::override_source(source, location.as_str())?;
+    }
+    if let Some(to_names) = args.demo {
+        demo::register_demo_sources();
+        args.mc_version = Some(demo::VERSION.to_string());
+        args.from_names = Some(NamesType::Obfuscated);
+        args.to_names = Some(to_names);
+    }
+    match &args.command {
+        Some(Command::Preload {
+            versions,
+            namespaces,
+        }) => return preload_all(&expand_versions(versions)?, namespaces),
+        Some(Command::Graph { version, dot }) => return print_graph(version.as_deref(), *dot),
+        Some(Command::ResolvePath {
+            version,
+            from_names,
+            to_names,
+        }) => return resolve_path(version, *from_names, *to_names),
+        Some(Command::Stats {
+            version,
+            from_names,
+            to_names,
+        }) => return print_stats(version, *from_names, *to_names),
+        Some(Command::Explain {
+            version,
+            from_names,
+            to_names,
+            frame,
+        }) => return explain_frame(version, *from_names, *to_names, frame),
+        Some(Command::Compare {
+            from_names,
+            to_names,
+            versions,
+        }) => {
+            return compare_versions(
+                &expand_versions(versions)?,
+                *from_names,
+                *to_names,
+                args.max_input_mb,
+            )
+        }
+        Some(Command::Cache { command }) => return run_cache_command(command),
+        None => {}
+    }
+
+    #[cfg(feature = "tui")]
+    if args.tui {
+        let (mc_version, from_names, to_names) = mapping_args(&args);
+        return tui::run(mc_version.to_string(), from_names, to_names);
+    }
+
+    let config = Config::load(args.config.as_deref())?;
+    args.rules = config.rules;
+    args.hints.extend(config.hints);
+    let refmaps = match &args.mods {
+        Some(dir) => mods::load_refmaps(dir)?,
+        None => RefmapSelectors::new(),
+    };
+
+    if let Some(dir) = &args.watch_dir {
+        let mapper = build_mapper(&args)?;
+        let render_options = render_options(&args);
+        let suffix = args.suffix.as_deref().unwrap_or(DEFAULT_SUFFIX);
+        // Crash reports are never just a stacktrace
+        let args = StackedPortrayals {
+            log: true,
+            ..args.clone()
+        };
+        return watch::watch_dir(dir, suffix, |input| {
+            let input = apply_rules(&args.rules, Stage::Input, input);
+            map_input(&refmaps.resolve(&input), &args, &mapper, &render_options)
+        });
+    }
+
+    if !args.files.is_empty() {
+        let paths = batch::expand_paths(&args.files)?;
+        if args.normalize {
+            return batch::map_files(&paths, args.suffix.as_deref(), |input| {
+                let input = apply_rules(&args.rules, Stage::Input, input);
+                let output = document::normalize_document(&prepare_input(&input, args.lenient));
+                Ok(apply_rules(&args.rules, Stage::Output, &output).into_owned())
+            });
+        }
+        let mapper = build_mapper(&args)?;
+        if args.dedupe {
+            return batch::dedupe_files(&paths, |input| {
+                let input = apply_rules(&args.rules, Stage::Input, input);
+                let stacktrace = crash_stacktrace(&refmaps.resolve(&input), &args, &mapper)?;
+                let root = stacktrace.root_cause();
+                let description = match root.frames.first() {
+                    Some(frame) => format!("{} at {}.{}", root.ty, frame.class, frame.method),
+                    None => root.ty.to_string(),
+                };
+                Ok((stacktrace.fingerprint(args.fingerprint_frames), description))
+            });
+        }
+        let render_options = render_options(&args);
+        return batch::map_files(&paths, args.suffix.as_deref(), |input| {
+            let input = apply_rules(&args.rules, Stage::Input, input);
+            map_input(&refmaps.resolve(&input), &args, &mapper, &render_options)
+        });
+    }
+
+    let input = match args.trace.as_deref() {
+        _ if args.demo.is_some() => demo::TRACE.to_string(),
+        Some(trace) if trace != "-" => unescape_trace(trace),
+        _ => {
+            tracing::info!("Enter stacktrace (Ctrl+D to finish):");
+            let mut stdin = std::io::stdin().lock();
+            let head = stdin
+                .fill_buf()
+                .change_context(SPError::IoError)
+                .attach_printable("Failed to read stacktrace from stdin")?;
+            if can_stream_log(&args)
+                && batch::is_plain_text(head)
+                && !is_structured_head(&String::from_utf8_lossy(head))
+            {
+                return stream_log(stdin, &args, &refmaps);
+            }
+            read_input(stdin, args.max_input_mb)?
+        }
     };
-    let stacktrace = parse_stacktrace(&stacktrace)?;
+    let input = apply_rules(&args.rules, Stage::Input, &input).into_owned();
+    if args.normalize {
+        tracing::info!("Normalizing stacktraces...");
+        let output = document::normalize_document(&prepare_input(&input, args.lenient));
+        return write_output(&args, &apply_rules(&args.rules, Stage::Output, &output));
+    }
+    let input = refmaps.resolve(&input);
+    if let Some(versions) = &args.guess_version {
+        let version = guess_mc_version(&prepare_input(&input, args.lenient), versions, &args)?;
+        args.mc_version = Some(version);
+    }
+    let render_options = render_options(&args);
+
+    if args.fingerprint {
+        let mapper = build_mapper(&args)?;
+        let stacktrace = crash_stacktrace(&input, &args, &mapper)?;
+        let output = format!("{}\n", stacktrace.fingerprint(args.fingerprint_frames));
+        return write_output(&args, &output);
+    }
+
+    if args.log
+        || args.free_text
+        || sentry::is_sentry_event(&input)
+        || json_lines::is_json_lines(&input)
+        || log4j_xml::is_log4j_xml(&input)
+    {
+        let mapper = build_mapper(&args)?;
+        warn_version_mismatch(&input, &args, &mapper);
+        let output = map_input(&input, &args, &mapper, &render_options)?;
+        return write_output(&args, &output);
+    }
+
+    // Parse before generating the mapper, so bad input fails fast
+    let input = prepare_input(&input, args.lenient);
+    tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "stacktrace");
+    let stacktrace = parse_stacktrace(&input)?;
+
+    let mapper = build_mapper(&args)?;
+    warn_version_mismatch(&input, &args, &mapper);
+    warn_srg_names(&input);
+
+    tracing::info!("Mapping stacktrace...");
+    let mapped_stacktrace = map_stacktrace(stacktrace, &mapper, &args);
+
+    let mut output = format_stacktrace(&input, &mapped_stacktrace, &args, &render_options)?;
+    // With `--exact`, keep the original line endings exactly
+    if !args.exact {
+        output.push('\n');
+    }
+    let output = apply_rules(&args.rules, Stage::Output, &output).into_owned();
+    let output = add_culprit(output, &input, &args, &mapper);
+    write_output(&args, &add_header(output, &input, &args, &mapper))
+}
+
+/// Warn if the given version leaves the input mostly unmapped, naming a neighboring version that
+/// fits it better with `--check-version`. A guessed version was already compared with others, so
+/// it isn't checked.
+#[cfg(feature = "mojang")]
+fn warn_version_mismatch(input: &str, args: &StackedPortrayals, mapper: &impl MethodMapper) {
+    let (version, from_names, to_names) = mapping_args(args);
+    // The sample mappings of `--demo` have no neighboring versions
+    if args.guess_version.is_some() || args.demo.is_some() {
+        return;
+    }
+    let result = check_version(
+        input,
+        version,
+        mapper,
+        from_names,
+        to_names,
+        args.check_version,
+    );
+    match result {
+        Ok(Some(VersionMismatch {
+            given,
+            better: Some(better),
+        })) => tracing::warn!(
+            "This trace looks like {}, not {}: it maps {}/{} of its Minecraft frames, {} maps {}",
+            better.version,
+            version,
+            given.mapped,
+            given.frames,
+            better.version,
+            better.mapped
+        ),
+        Ok(Some(VersionMismatch {
+            given,
+            better: None,
+        })) => tracing::warn!(
+            "Only {}/{} of the Minecraft frames of this trace were mapped, it may not be from {}; \
+             use --check-version to compare it with the neighboring versions",
+            given.mapped,
+            given.frames,
+            version
+        ),
+        Ok(None) => {}
+        Err(e) => tracing::debug!("Failed to check the version of the trace: {:?}", e),
+    }
+}
+
+#[cfg(not(feature = "mojang"))]
+fn warn_version_mismatch(_input: &str, _args: &StackedPortrayals, _mapper: &impl MethodMapper) {}
+
+/// Warn if the input has SRG names, which none of the names can map from. Returns whether it
+/// warned.
+fn warn_srg_names(input: &str) -> bool {
+    let name = find_srg_name(input);
+    if let Some(name) = name {
+        tracing::warn!(
+            "The input has SRG names from Forge before 1.17, e.g. {}, which can't be mapped",
+            name
+        );
+    }
+    name.is_some()
+}
 
+/// Where to deliver the mapped output, according to the arguments.
+fn output_sinks(args: &StackedPortrayals) -> Vec<Box<dyn OutputSink>> {
+    let mut sinks: Vec<Box<dyn OutputSink>> = Vec::new();
+    if let Some(path) = &args.output {
+        sinks.push(Box::new(FileSink(path.clone())));
+    }
+    if args.clipboard {
+        sinks.push(Box::new(Clipboard));
+    }
+    if args.upload {
+        sinks.push(Box::new(Mclogs));
+    }
+    if let Some(url) = &args.post_to {
+        sinks.push(Box::new(HttpCallback(url.clone())));
+    }
+    if sinks.is_empty() {
+        sinks.push(Box::new(Stdout));
+    }
+    sinks
+}
+
+/// Deliver the mapped output to every destination of the arguments.
+fn write_output(args: &StackedPortrayals, output: &str) -> Result<(), Report<SPError>> {
+    for mut sink in output_sinks(args) {
+        sink.write(output)?;
+    }
+    Ok(())
+}
+
+/// How to render mapped stacktraces, according to the arguments.
+fn render_options(args: &StackedPortrayals) -> RenderOptions {
+    RenderOptions {
+        fold: args.fold.clone(),
+        collapse_common: args.collapse_common,
+        frame_template: args.format.clone(),
+        exact: args.exact,
+        confidence: args.confidence,
+        show_descriptors: args.show_descriptors,
+        candidate_separator: args.candidate_separator.clone(),
+        max_candidates: args.max_candidates.map(NonZeroUsize::get),
+        class_names: match args.class_names {
+            ClassNamesArg::Qualified => ClassNames::Qualified,
+            ClassNamesArg::Simple => ClassNames::Simple,
+            ClassNamesArg::Abbreviated => ClassNames::Abbreviated,
+        },
+        annotate_original: args.annotate_original,
+        ide: args.ide,
+        links: args.links.map(|style| {
+            let style = match style {
+                LinkStyleArg::Hyperlink => LinkStyle::Hyperlink,
+                LinkStyleArg::Url => LinkStyle::Url,
+            };
+            let (version, _, to_names) = mapping_args(args);
+            Links::new(style, to_names, version)
+        }),
+    }
+}
+
+/// Pick the version from the `versions` that best covers the frames in the `input`, or from the
+/// recent releases if none are given.
+fn guess_mc_version(
+    input: &str,
+    versions: &[String],
+    args: &StackedPortrayals,
+) -> Result<String, Report<SPError>> {
+    let (Some(from_names), Some(to_names)) = (args.from_names, args.to_names) else {
+        unreachable!("shift_guessed_version requires the names with --guess-version");
+    };
+    let recent;
+    let versions = if versions.is_empty() {
+        recent = recent_releases()?;
+        &recent
+    } else {
+        versions
+    };
+    tracing::info!("Guessing the version from {}...", versions.join(", "));
+    let scores = guess_version(input, versions, from_names, to_names)?;
+    for score in &scores {
+        tracing::info!(
+            "{}: {}/{} frames mapped ({:.0}%)",
+            score.version,
+            score.mapped,
+            score.frames,
+            score.coverage() * 100.0
+        );
+    }
+    let best = &scores[0];
+    if best.mapped == 0 {
+        return Err(Report::new(SPError::UnknownVersion)
+            .attach_printable("None of the versions have mappings for the frames"));
+    }
+    if scores.get(1).is_some_and(|s| s.mapped == best.mapped) {
+        tracing::warn!("Other versions map as many frames as {}", best.version);
+    }
+    tracing::info!("Guessed version {}", best.version);
+    Ok(best.version.clone())
+}
+
+#[cfg(feature = "mojang")]
+fn recent_releases() -> Result<Vec<String>, Report<SPError>> {
+    stacked_portrayals::mappings::recent_releases(10)
+}
+
+#[cfg(not(feature = "mojang"))]
+fn recent_releases() -> Result<Vec<String>, Report<SPError>> {
+    Err(Report::new(SPError::UnknownVersion).attach_printable(
+        "Give the versions to guess from, the recent releases need Mojang's manifest",
+    ))
+}
+
+#[cfg(feature = "mojang")]
+fn expand_versions(versions: &[String]) -> Result<Vec<String>, Report<SPError>> {
+    stacked_portrayals::mappings::expand_versions(versions)
+}
+
+#[cfg(not(feature = "mojang"))]
+fn expand_versions(versions: &[String]) -> Result<Vec<String>, Report<SPError>> {
+    if let Some(pattern) = versions
+        .iter()
+        .find(|v| v.contains("..") || v.contains(['*', '?', '[']))
+    {
+        return Err(
+            Report::new(SPError::UnknownVersion).attach_printable(format!(
+                "Can't expand {}, version ranges and patterns need Mojang's manifest",
+                pattern
+            )),
+        );
+    }
+    Ok(versions.to_vec())
+}
+
+/// Map a whole input according to its format and the arguments, with a header if asked for.
+fn map_input(
+    input: &str,
+    args: &StackedPortrayals,
+    mapper: &Mapper,
+    render_options: &RenderOptions,
+) -> Result<String, Report<SPError>> {
+    if args.fingerprint {
+        let stacktrace = crash_stacktrace(input, args, mapper)?;
+        return Ok(stacktrace.fingerprint(args.fingerprint_frames));
+    }
+    warn_srg_names(input);
+    let output = map_input_by_format(input, args, mapper, render_options)?;
+    let output = apply_rules(&args.rules, Stage::Output, &output).into_owned();
+    let output = add_culprit(output, input, args, mapper);
+    Ok(add_header(output, input, args, mapper))
+}
+
+/// Start the `output` with a comment saying what it was mapped with, if `--header` is given and
+/// the output of the `input` isn't JSON.
+fn add_header(output: String, input: &str, args: &StackedPortrayals, mapper: &Mapper) -> String {
+    if !args.header || is_structured_output(input, args) {
+        return output;
+    }
+    let (version, from_names, to_names) = mapping_args(args);
+    let mut header = format!(
+        "# mapped with stacked-portrayals v{}, {} {}->{}",
+        env!("CARGO_PKG_VERSION"),
+        version,
+        from_names,
+        to_names
+    );
+    for origin in mapper.base().base().origins() {
+        if let Some(sha1) = &origin.sha1 {
+            header.push_str(&format!(", {} maps sha1={}", origin.source, sha1));
+        }
+    }
+    header.push_str(&format!(", on {}\n", utc_timestamp(SystemTime::now())));
+    header + &output
+}
+
+/// End the `output` with the likely culprit of the crash, if `--culprit` is given and the output
+/// of the `input` isn't JSON or XML.
+fn add_culprit(
+    mut output: String,
+    input: &str,
+    args: &StackedPortrayals,
+    mapper: &Mapper,
+) -> String {
+    if !args.culprit || is_structured_output(input, args) {
+        return output;
+    }
+    let culprit = crash_stacktrace(input, args, mapper)
+        .ok()
+        .and_then(|stacktrace| suspect_culprit(&stacktrace));
+    match culprit {
+        Some(culprit) => {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(&format!("Likely culprit: {}\n", culprit));
+        }
+        None => tracing::info!("No likely culprit found"),
+    }
+    output
+}
+
+/// Check if the output for the `input` is JSON or XML, which can't have anything added to it.
+fn is_structured_output(input: &str, args: &StackedPortrayals) -> bool {
+    wants_json(args)
+        || sentry::is_sentry_event(input)
+        || json_lines::is_json_lines(input)
+        || (log4j_xml::is_log4j_xml(input) && !args.xml_to_text)
+}
+
+#[cfg(feature = "serde")]
+fn wants_json(args: &StackedPortrayals) -> bool {
+    args.json
+}
+
+#[cfg(not(feature = "serde"))]
+fn wants_json(_args: &StackedPortrayals) -> bool {
+    false
+}
+
+/// Format a time as an RFC 3339 timestamp in UTC, e.g. `2024-05-01T12:00:00Z`.
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Days to a civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Map a whole input according to its format and the arguments.
+fn map_input_by_format(
+    input: &str,
+    args: &StackedPortrayals,
+    mapper: &Mapper,
+    render_options: &RenderOptions,
+) -> Result<String, Report<SPError>> {
+    if args.free_text {
+        tracing::info!("Mapping names in text...");
+        tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "free_text");
+        let output = free_text::map_free_text(input, mapper);
+        tracing::info!(target: PROGRESS_TARGET, event = "mapping_done");
+        return Ok(output);
+    }
+    if sentry::is_sentry_event(input) {
+        tracing::info!("Mapping Sentry event...");
+        tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "sentry");
+        let output = sentry::map_sentry_event(input, mapper)?;
+        tracing::info!(target: PROGRESS_TARGET, event = "mapping_done");
+        return Ok(output);
+    }
+    if json_lines::is_json_lines(input) {
+        tracing::info!("Mapping JSON log lines...");
+        tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "json_lines");
+        let output = json_lines::map_json_lines(input, |trace| {
+            map_embedded_trace(trace, args, mapper, render_options)
+        })?;
+        tracing::info!(target: PROGRESS_TARGET, event = "mapping_done");
+        return Ok(output);
+    }
+    if log4j_xml::is_log4j_xml(input) {
+        tracing::info!("Mapping Log4j XML log...");
+        tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "log4j_xml");
+        let output = if args.xml_to_text {
+            let text = log4j_xml::log4j_xml_to_text(input);
+            document::map_document(&prepare_input(&text, args.lenient), mapper, render_options)
+        } else {
+            log4j_xml::map_log4j_xml(input, |trace| {
+                map_embedded_trace(trace, args, mapper, render_options)
+            })
+        };
+        tracing::info!(target: PROGRESS_TARGET, event = "mapping_done");
+        return Ok(output);
+    }
+    let input = prepare_input(input, args.lenient);
+    if args.log {
+        tracing::info!("Mapping stacktraces in log...");
+        tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "log");
+        let output = document::map_document(&input, mapper, render_options);
+        tracing::info!(target: PROGRESS_TARGET, event = "mapping_done");
+        return Ok(output);
+    }
+    tracing::info!("Mapping stacktrace...");
+    tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "stacktrace");
+    format_stacktrace(
+        &input,
+        &map_stacktrace(parse_stacktrace(&input)?, mapper, args),
+        args,
+        render_options,
+    )
+}
+
+/// Find the first stacktrace of an input, e.g. the crash of a crash report, and map it.
+fn crash_stacktrace(
+    input: &str,
+    args: &StackedPortrayals,
+    mapper: &Mapper,
+) -> Result<Stacktrace, Report<SPError>> {
+    let input = if log4j_xml::is_log4j_xml(input) {
+        log4j_xml::log4j_xml_to_text(input).into()
+    } else {
+        Cow::Borrowed(input)
+    };
+    let stacktrace = document::find_stacktraces(&prepare_input(&input, args.lenient))
+        .into_iter()
+        .next()
+        .ok_or_else(|| Report::new(SPError::ParseError).attach_printable("No stacktrace found"))?;
+    Ok(map_stacktrace(stacktrace, mapper, args))
+}
+
+/// Map a stacktrace embedded in a structured log, such as JSON lines.
+fn map_embedded_trace(
+    trace: &str,
+    args: &StackedPortrayals,
+    mapper: &Mapper,
+    render_options: &RenderOptions,
+) -> Result<String, Report<SPError>> {
+    let trace = prepare_input(trace, args.lenient);
+    let stacktrace = map_stacktrace(parse_stacktrace(&trace)?, mapper, args);
+    Ok(if args.exact {
+        rewrite_stacktrace(&trace, &stacktrace)
+    } else {
+        stacktrace.render(render_options).to_string()
+    })
+}
+
+/// Map a stacktrace, unless it already uses the names to map to.
+fn map_stacktrace(stacktrace: Stacktrace, mapper: &Mapper, args: &StackedPortrayals) -> Stacktrace {
+    if stacktrace.uses_target_names(mapper.base().base()) {
+        tracing::warn!(
+            "The stacktrace already uses {} names, e.g. from a Paper server, so it is left as-is",
+            mapping_args(args).2
+        );
+        return stacktrace;
+    }
+    stacktrace.map_self(mapper)
+}
+
+/// Format a mapped stacktrace for output, as text or JSON. The `input` is the text it was parsed
+/// from.
+fn format_stacktrace(
+    input: &str,
+    stacktrace: &Stacktrace,
+    args: &StackedPortrayals,
+    render_options: &RenderOptions,
+) -> Result<String, Report<SPError>> {
+    let stats = stacktrace.stats();
+    tracing::info!(
+        target: PROGRESS_TARGET,
+        event = "mapping_done",
+        frames = stats.frames,
+        mapped = stats.mapped,
+        ambiguous = stats.ambiguous
+    );
+    if args.exact {
+        return Ok(rewrite_stacktrace(input, stacktrace));
+    }
+    #[cfg(feature = "serde")]
+    if args.json {
+        return serde_json::to_string_pretty(stacktrace)
+            .change_context(SPError::ParseError)
+            .attach_printable("Failed to write stacktrace as JSON");
+    }
+    Ok(stacktrace.render(render_options).to_string())
+}
+
+fn read_stdin(max_input_mb: u64) -> Result<String, Report<SPError>> {
+    tracing::info!("Enter stacktrace (Ctrl+D to finish):");
+    read_input(std::io::stdin().lock(), max_input_mb)
+}
+
+/// Read the whole `stdin`, failing if it's larger than `max_input_mb` megabytes, unless that is 0.
+fn read_input(stdin: impl Read, max_input_mb: u64) -> Result<String, Report<SPError>> {
+    let limit = match max_input_mb {
+        0 => u64::MAX,
+        mb => mb.saturating_mul(1024 * 1024),
+    };
+    let mut buf = Vec::new();
+    stdin
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut buf)
+        .change_context(SPError::IoError)
+        .attach_printable("Failed to read stacktrace from stdin")?;
+    if buf.len() as u64 > limit {
+        return Err(Report::new(SPError::IoError)
+            .attach_printable(format!(
+                "The input is larger than the limit of {} MB",
+                max_input_mb
+            ))
+            .attach_printable(
+                "Raise the limit with --max-input-mb, or map a log as it's read with --log",
+            ));
+    }
+    batch::decode_input(buf).attach_printable("Failed to read stacktrace from stdin")
+}
+
+/// Whether a log from stdin can be mapped as it's read: its output must go to stdout, and nothing
+/// may need the whole log.
+fn can_stream_log(args: &StackedPortrayals) -> bool {
+    args.log
+        && !args.lenient
+        && !args.header
+        && !args.culprit
+        && !args.fingerprint
+        && args.guess_version.is_none()
+        && args.output.is_none()
+        && !args.clipboard
+        && !args.upload
+        && args.post_to.is_none()
+}
+
+/// Check if the start of a log from stdin looks like one of the formats [`map_input_by_format`]
+/// needs whole, so the log can't be mapped as it's read.
+///
+/// JSON lines and Sentry events are only recognized by their first object, as the `head` may end
+/// in the middle of a line.
+fn is_structured_head(head: &str) -> bool {
+    log4j_xml::is_log4j_xml(head) || head.trim_start().starts_with('{')
+}
+
+/// Map the log in `stdin` chunk by chunk, printing each as soon as it's mapped.
+///
+/// The input is only warned about once: SRG names at the first chunk that has them, and the
+/// version at the first stacktrace.
+fn stream_log(
+    stdin: impl BufRead,
+    args: &StackedPortrayals,
+    refmaps: &RefmapSelectors,
+) -> Result<(), Report<SPError>> {
+    let mapper = build_mapper(args)?;
+    let render_options = render_options(args);
+    tracing::info!("Mapping stacktraces in log...");
+    tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "log");
+    let mut stdout = std::io::stdout().lock();
+    let mut warned_srg_names = false;
+    let mut checked_version = false;
+    for chunk in DocumentChunks::new(stdin) {
+        let chunk = chunk
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to read log from stdin")?;
+        let chunk = normalize_line_endings(&chunk);
+        let chunk = apply_rules(&args.rules, Stage::Input, &chunk);
+        if !warned_srg_names {
+            warned_srg_names = warn_srg_names(&chunk);
+        }
+        // A chunk of more than one line holds a stacktrace
+        if !checked_version && chunk.lines().nth(1).is_some() {
+            warn_version_mismatch(&chunk, args, &mapper);
+            checked_version = true;
+        }
+        let output = document::map_document(&refmaps.resolve(&chunk), &mapper, &render_options);
+        stdout
+            .write_all(apply_rules(&args.rules, Stage::Output, &output).as_bytes())
+            .and_then(|_| stdout.flush())
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to write to stdout")?;
+    }
+    tracing::info!(target: PROGRESS_TARGET, event = "mapping_done");
+    Ok(())
+}
+
+/// Expand the escapes in a trace given as an argument, and terminate its last line.
+fn unescape_trace(trace: &str) -> String {
+    let mut output = String::with_capacity(trace.len() + 1);
+    let mut chars = trace.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('\\') => output.push('\\'),
+            // Unknown escapes are kept as-is
+            Some(other) => {
+                output.push('\\');
+                output.push(other);
+            }
+            None => output.push('\\'),
+        }
+    }
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
+
+fn print_graph(version: Option<&str>, dot: bool) -> Result<(), Report<SPError>> {
+    let graph = describe_graph(version);
+    if dot {
+        print!("{}", graph.to_dot());
+        return Ok(());
+    }
+    let names: Vec<String> = graph.nodes.iter().map(ToString::to_string).collect();
+    println!("Names: {}", names.join(", "));
+    println!();
+    println!("Sources, in the order they are tried:");
+    for edge in &graph.edges {
+        print!(
+            "  {}{}: {} <-> {}",
+            edge.source,
+            if edge.cached { " (cached)" } else { "" },
+            edge.primary,
+            edge.secondary
+        );
+        match &edge.location {
+            Some(location) => println!(", {}", location),
+            None => println!(),
+        }
+    }
+    Ok(())
+}
+
+fn resolve_path(version: &str, from: NamesType, to: NamesType) -> Result<(), Report<SPError>> {
+    let steps = explain_path(version, from, to)?;
+    let path = steps
+        .iter()
+        .map(|s| s.to)
+        .fold(from.to_string(), |path, names| {
+            format!("{} -> {}", path, names)
+        });
+    println!("{} for {}", path, version);
+    for step in &steps {
+        println!();
+        println!("{} -> {}, trying in order:", step.from, step.to);
+        for source in &step.sources {
+            println!(
+                "  {}{}: {}",
+                source.name,
+                if source.cached { " (cached)" } else { "" },
+                source
+                    .location
+                    .as_deref()
+                    .unwrap_or("location unknown until fetched")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_stats(version: &str, from: NamesType, to: NamesType) -> Result<(), Report<SPError>> {
     tracing::info!("Generating mapper...");
-    let mapper = generate_mapper(args.mc_version, args.from_names, args.to_names)
+    let mapper = generate_mapper(version.to_string(), from, to)?;
+    let stats = mapper.stats();
+    let percent = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total as f64
+        }
+    };
+    println!("{}", mapper);
+    println!(
+        "Classes: {}, {} mapped ({:.1}%), {} with identical names",
+        stats.classes,
+        stats.mapped_classes,
+        percent(stats.mapped_classes, stats.classes),
+        stats.identical_classes
+    );
+    println!(
+        "Methods: {}, {} mapped ({:.1}%), {} with identical names",
+        stats.methods,
+        stats.mapped_methods,
+        percent(stats.mapped_methods, stats.methods),
+        stats.identical_methods
+    );
+    Ok(())
+}
+
+fn compare_versions(
+    versions: &[String],
+    from: NamesType,
+    to: NamesType,
+    max_input_mb: u64,
+) -> Result<(), Report<SPError>> {
+    let input = read_stdin(max_input_mb)?;
+    let stacktrace = parse_stacktrace(&input)?;
+    let mut mapped = Vec::with_capacity(versions.len());
+    for version in versions {
+        tracing::info!("Generating mapper for {}...", version);
+        let mapper = generate_mapper(version.clone(), from, to)
+            .attach_printable_lazy(|| format!("Failed to generate mapper for {}", version))?;
+        // Mapping consumes the trace, so parse it again for each version
+        mapped.push(parse_stacktrace(&input)?.map_self(&mapper));
+    }
+
+    let width = versions.iter().map(String::len).max().unwrap_or(0);
+    let mut frames: Vec<_> = mapped.iter().map(|t| t.all_frames()).collect();
+    let mut differing = 0;
+    for original in stacktrace.all_frames() {
+        let names: Vec<String> = frames
+            .iter_mut()
+            .map(|f| {
+                let frame = f.next().expect("mapping keeps the frames");
+                format!("{}.{}", frame.class, frame.method)
+            })
+            .collect();
+        if names.iter().all(|n| n == &names[0]) {
+            println!("  at {}", names[0]);
+            continue;
+        }
+        differing += 1;
+        println!("! at {}.{}", original.class, original.method);
+        for (version, name) in versions.iter().zip(&names) {
+            println!("    {:width$}  {}", version, name, width = width);
+        }
+    }
+    println!();
+    println!(
+        "{} of {} frames differ between versions",
+        differing,
+        stacktrace.all_frames().count()
+    );
+    Ok(())
+}
+
+fn explain_frame(
+    version: &str,
+    from: NamesType,
+    to: NamesType,
+    line: &str,
+) -> Result<(), Report<SPError>> {
+    let frame = parse_frame(&format!("\t{}", line.trim())).ok_or_else(|| {
+        Report::new(SPError::ParseError).attach_printable(format!("Invalid frame: {}", line))
+    })?;
+    if frame.is_generated() {
+        println!(
+            "{} is generated at runtime, so it is never mapped",
+            frame.class
+        );
+        return Ok(());
+    }
+    tracing::info!("Generating mapper...");
+    let mapper = generate_mapper(version.to_string(), from, to)?;
+
+    println!(
+        "Class: {} -> {}",
+        frame.class,
+        mapper.map_class(&frame.class).unwrap_or("(not mapped)")
+    );
+    for (i, step) in mapper
+        .explain_method(&frame.class, &frame.method, None)
+        .iter()
+        .enumerate()
+    {
+        println!();
+        println!("Step {}: {}", i + 1, step.mapper);
+        if step.lookups.is_empty() {
+            println!("  Nothing to look up");
+        }
+        for lookup in &step.lookups {
+            let descriptor = lookup
+                .descriptor
+                .as_ref()
+                .map(Descriptor::internal_descriptor)
+                .unwrap_or_default();
+            println!(
+                "  {}.{}{}: {}",
+                lookup.class, lookup.name, descriptor, lookup.heuristic
+            );
+            for (class, id) in &lookup.found {
+                println!(
+                    "    {}.{}{}",
+                    class,
+                    id.name,
+                    id.descriptor.internal_descriptor()
+                );
+            }
+        }
+    }
+
+    let candidates = mapper.map_method(&frame.class, &frame.method, None);
+    println!();
+    if candidates.is_empty() {
+        println!("No candidates, the method is left as-is");
+    } else {
+        println!("Candidates ({}):", candidates.len());
+        for (class, id) in candidates {
+            println!(
+                "  {}.{}{}",
+                class,
+                id.name,
+                id.descriptor.internal_descriptor()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_cache_command(command: &CacheCommand) -> Result<(), Report<SPError>> {
+    match command {
+        CacheCommand::Export {
+            file,
+            versions,
+            namespaces,
+        } => {
+            let output = File::create(file)
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to create {}", file.display()))?;
+            let versions = expand_versions(versions)?;
+            let count = bundle::export_bundle(&versions, namespaces, BufWriter::new(output))
+                .attach_printable_lazy(|| format!("Failed to export {}", file.display()))?;
+            tracing::info!("Exported {} mappings to {}", count, file.display());
+        }
+        CacheCommand::Import { file } => {
+            let input = File::open(file)
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to open {}", file.display()))?;
+            let count = bundle::import_bundle(BufReader::new(input))
+                .attach_printable_lazy(|| format!("Failed to import {}", file.display()))?;
+            tracing::info!("Imported {} mappings from {}", count, file.display());
+        }
+        CacheCommand::Verify { delete } => {
+            let verification = cache::verify_cache(*delete)?;
+            tracing::info!(
+                "Checked {} cached mappings, {} corrupt",
+                verification.checked,
+                verification.corrupt.len()
+            );
+            if !verification.corrupt.is_empty() && !delete {
+                return Err(Report::new(SPError::CacheError).attach_printable(format!(
+                    "{} cached mappings are corrupt, use --delete to remove them",
+                    verification.corrupt.len()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn prepare_input(input: &str, lenient: bool) -> Cow<'_, str> {
+    if lenient {
+        lenient::normalize(input).into()
+    } else {
+        input.into()
+    }
+}
+
+/// The mapper of the Minecraft mappings, with those of the mods and the descriptor hints.
+type Mapper = WithHints<WithModMappings<EitherMapper>>;
+
+fn build_mapper(args: &StackedPortrayals) -> Result<Mapper, Report<SPError>> {
+    let (mc_version, from_names, to_names) = mapping_args(args);
+    tracing::info!("Generating mapper...");
+    let mapper = generate_mapper(mc_version.to_string(), from_names, to_names)
         .attach_printable_lazy(|| {
             format!(
                 "Failed to generate mapper from {} to {}",
-                args.from_names, args.to_names
+                from_names, to_names
             )
         })?;
+    let mods = args
+        .extra_mapping
+        .iter()
+        .map(|(mod_id, location)| ModMappings::load(mod_id, location))
+        .collect::<Result<_, _>>()?;
+    Ok(WithHints::new(
+        WithModMappings::new(mapper, mods),
+        args.hints.clone(),
+    ))
+}
 
-    tracing::info!("Mapping stacktrace...");
-    let mapped_stacktrace = stacktrace.map_self(&mapper);
-
-    println!("{}", mapped_stacktrace);
+/// Preload every combination of version and names in parallel, reporting all failures.
+fn preload_all(versions: &[String], namespaces: &[NamesType]) -> Result<(), Report<SPError>> {
+    let failures = std::thread::scope(|s| {
+        let handles = versions
+            .iter()
+            .flat_map(|version| namespaces.iter().map(move |&names| (version, names)))
+            .map(|(version, names)| {
+                s.spawn(move || {
+                    tracing::info!("Preloading {} names for {}...", names, version);
+                    preload(version, names).err().map(|e| {
+                        tracing::error!(
+                            "Failed to preload {} names for {}: {:?}",
+                            names,
+                            version,
+                            e
+                        );
+                        *e.current_context()
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .filter_map(|h| h.join().expect("preload thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    batch::report_failures(&failures, |count| {
+        format!("Failed to preload {} mapping(s)", count)
+    })?;
+    tracing::info!("Preloaded all mappings");
     Ok(())
 }
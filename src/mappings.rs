@@ -1,69 +1,201 @@
 use std::collections::HashMap;
 use std::fmt::{Debug, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+use chumsky::primitive::end;
+use chumsky::Parser;
 use derive_more::Display;
 use error_stack::{Report, ResultExt};
-use once_cell::sync::Lazy;
+use itertools::Itertools;
 use petgraph::algo::astar;
 use petgraph::graphmap::DiGraphMap;
+use sha1::{Digest, Sha1};
 
 use crate::names::NamesType;
+use crate::parsing::{descriptor, descriptor_type, handle_errors, parse_recovery_debuggable};
+use crate::progress::TIMINGS_TARGET;
 use crate::SPError;
 
+pub mod builder;
+pub mod bundle;
 pub mod cache;
 mod db;
+pub mod explain;
+#[cfg(feature = "fabric")]
 mod fabric_intermediary;
+pub mod hints;
+#[cfg(any(feature = "mojang", feature = "fabric"))]
+mod loom;
+#[cfg(any(feature = "mojang", feature = "fabric"))]
+mod meta;
+pub mod mod_mappings;
+#[cfg(feature = "mojang")]
 mod mojang;
-mod proguard;
+pub mod proguard;
 mod raw;
-mod tiny;
+mod registry;
+pub mod source;
+pub mod tiny;
+pub mod tiny_file;
 
-type MappingsGraph = DiGraphMap<NamesType, MappingType>;
+pub use builder::MapperBuilder;
+use explain::LookupHeuristic;
+#[cfg(feature = "mojang")]
+pub use mojang::{expand_versions, neighboring_versions, recent_releases};
+pub use registry::MapperRegistry;
+use source::{Content, MappingSource};
 
-/// Graph with nodes of [`NamesType`]s and edges of [`MappingLoader`]s.
-static MAPPINGS_GRAPH: Lazy<MappingsGraph> = Lazy::new(|| {
-    DiGraphMap::from_edges(&[
-        (
-            NamesType::Obfuscated,
-            NamesType::Mojang,
-            MappingType::ObfToMojang,
-        ),
-        (
-            NamesType::Mojang,
-            NamesType::Obfuscated,
-            MappingType::MojangToObf,
-        ),
-        (
-            NamesType::Obfuscated,
-            NamesType::FabricIntermediary,
-            MappingType::ObfToFabricIntermediary,
-        ),
-        (
-            NamesType::FabricIntermediary,
-            NamesType::Obfuscated,
-            MappingType::FabricIntermediaryToObf,
-        ),
-    ])
-});
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum MappingType {
-    ObfToMojang,
-    MojangToObf,
-    ObfToFabricIntermediary,
-    FabricIntermediaryToObf,
-}
-
-impl MappingType {
-    fn load(self, version: String) -> Result<BaseMapper, Report<SPError>> {
-        // TODO: Find some better way to encode this than a fucking bool, this is awful...
-        match self {
-            Self::ObfToMojang => mojang::load(version, false),
-            Self::MojangToObf => mojang::load(version, true),
-            Self::ObfToFabricIntermediary => fabric_intermediary::load(version, true),
-            Self::FabricIntermediaryToObf => fabric_intermediary::load(version, false),
+// Mappers are shared between threads, so don't let them lose `Send + Sync` by accident.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BaseMapper>();
+    assert_send_sync::<MultiMapper>();
+    assert_send_sync::<EitherMapper>();
+};
+
+type MappingsGraph = DiGraphMap<NamesType, ()>;
+
+static HASH_MAPPINGS: AtomicBool = AtomicBool::new(false);
+
+/// Hash the mappings of the mappers loaded from now on, so their [`MappingOrigin`]s have the SHA-1
+/// of the mappings, e.g. to name exactly which mappings output was mapped with. Hashing takes a
+/// while for large mappings, so it is only done when asked for.
+pub fn hash_mappings() {
+    HASH_MAPPINGS.store(true, Ordering::Relaxed);
+}
+
+/// Graph with nodes of [`NamesType`]s and edges in both directions for each registered
+/// [`MappingSource`].
+fn mappings_graph() -> MappingsGraph {
+    let mut graph = DiGraphMap::new();
+    for source in source::sources() {
+        graph.add_edge(source.primary_names(), source.secondary_names(), ());
+        graph.add_edge(source.secondary_names(), source.primary_names(), ());
+    }
+    graph
+}
+
+/// The mappings fetched for a single step of a path.
+struct FetchedStep {
+    source: Arc<dyn MappingSource>,
+    content: Content,
+    should_flip: bool,
+}
+
+/// Fetch the mappings for a single step of a path, from the most recently registered source that
+/// has mappings for the `version`. Imported bundles are used instead of fetching.
+fn fetch_step(
+    version: &str,
+    from: NamesType,
+    to: NamesType,
+) -> Result<FetchedStep, Report<SPError>> {
+    let mut unknown: Option<Report<SPError>> = None;
+    for source in source::sources() {
+        let should_flip = if (source.primary_names(), source.secondary_names()) == (from, to) {
+            false
+        } else if (source.secondary_names(), source.primary_names()) == (from, to) {
+            true
+        } else {
+            continue;
+        };
+        let content = match bundle::read_bundled(source.name(), version)? {
+            Some(content) => Ok(content.into()),
+            None => source.fetch(version),
+        };
+        match content {
+            // Another source may know the version
+            Err(e) if *e.current_context() == SPError::UnknownVersion => match &mut unknown {
+                Some(unknown) => unknown.extend_one(e),
+                None => unknown = Some(e),
+            },
+            result => {
+                return result.map(|content| FetchedStep {
+                    source,
+                    content,
+                    should_flip,
+                })
+            }
         }
     }
+    Err(unknown.expect("path step has no source"))
+}
+
+/// Check if any source may have mappings between `from` and `to` names for the `version`, i.e.
+/// they are imported or not known to be missing.
+fn is_step_available(version: &str, from: NamesType, to: NamesType) -> bool {
+    source::sources().iter().any(|source| {
+        let names = (source.primary_names(), source.secondary_names());
+        (names == (from, to) || names == (to, from))
+            && (bundle::is_bundled(source.name(), version)
+                || source.has_version(version) != Some(false))
+    })
+}
+
+/// Check that every step of the `path` is available for the `version` before fetching any of
+/// them, failing with the names that the version does have mappings between if one isn't.
+fn check_path_available(version: &str, path: &[NamesType]) -> Result<(), Report<SPError>> {
+    let Some(missing) = path
+        .windows(2)
+        .find(|step| !is_step_available(version, step[0], step[1]))
+    else {
+        return Ok(());
+    };
+    let available = mappings_graph()
+        .all_edges()
+        .filter(|&(from, to, _)| from < to && is_step_available(version, from, to))
+        .map(|(from, to, _)| format!("{} <-> {}", from, to))
+        .collect::<Vec<_>>();
+    let report = Report::new(SPError::UnknownVersion).attach_printable(format!(
+        "No mappings between {} and {} names for {}",
+        missing[0], missing[1], version
+    ));
+    Err(if available.is_empty() {
+        report.attach_printable(format!(
+            "No names can be mapped for {}, check that the version exists",
+            version
+        ))
+    } else {
+        report.attach_printable(format!(
+            "Mappings available for {}: {}",
+            version,
+            available.join(", ")
+        ))
+    })
+}
+
+/// Load a mapper for a single step of a path, see [`fetch_step`].
+fn load_step(version: &str, from: NamesType, to: NamesType) -> Result<BaseMapper, Report<SPError>> {
+    let step = fetch_step(version, from, to)?;
+    let mappings = {
+        let _span = tracing::info_span!(target: TIMINGS_TARGET, "parse_mappings").entered();
+        step.source.parse(version, &step.content)?
+    };
+    let _span = tracing::info_span!(target: TIMINGS_TARGET, "build_mapper").entered();
+    let mut mapper = raw::convert_mappings(
+        step.source.primary_names(),
+        step.source.secondary_names(),
+        version.to_string(),
+        mappings,
+        step.should_flip,
+    );
+    mapper.origin = Some(MappingOrigin {
+        source: step.source.name().to_string(),
+        sha1: HASH_MAPPINGS
+            .load(Ordering::Relaxed)
+            .then(|| format!("{:x}", Sha1::digest(step.content.as_bytes()))),
+    });
+    Ok(mapper)
+}
+
+/// Where the mappings of a step came from, so mapped output can be traced back to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingOrigin {
+    /// The name of the [`MappingSource`](source::MappingSource), e.g. `mojang`.
+    pub source: String,
+    /// The SHA-1 of the mappings as fetched, before parsing, if they were loaded after
+    /// [`hash_mappings`].
+    pub sha1: Option<String>,
 }
 
 #[derive(Debug)]
@@ -77,6 +209,9 @@ pub struct ClassMapping {
     to_name: String,
     /// Indexed by the `from` ID, to the `to` ID.
     methods: HashMap<MethodId, MethodId>,
+    /// The `from` and `to` names of the fields. Obfuscated names are overloaded by type, so one
+    /// `from` name may have several entries.
+    fields: Vec<(String, String)>,
 }
 
 pub trait ClassMapper: Debug + Display {
@@ -97,6 +232,56 @@ pub trait MethodMapper: ClassMapper {
         name: &str,
         descriptor: Option<&Descriptor>,
     ) -> Vec<(&str, &MethodId)>;
+
+    /// Like [`map_method`](Self::map_method), also giving how confident the mapping is. Without
+    /// a more specific implementation, every mapping is [`Confidence::Medium`].
+    fn map_method_with_confidence(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (Vec<(&str, &MethodId)>, Confidence) {
+        (
+            self.map_method(from_class_name, name, descriptor),
+            Confidence::Medium,
+        )
+    }
+
+    /// Map a field name of the class named `from_class_name`, or of any class if the class isn't
+    /// known. Fields are looked up by name alone, so there may be several mappings, e.g. for
+    /// obfuscated fields of different types.
+    ///
+    /// # Returns
+    /// A list of all possible mappings for the field, and their corresponding class names. This
+    /// is empty without field mappings.
+    fn map_field(&self, _from_class_name: Option<&str>, _name: &str) -> Vec<(&str, &str)> {
+        Vec::new()
+    }
+
+    /// Describe the mappings that methods of the class named `from_class_name` are mapped with,
+    /// as the provenance of their candidates, e.g. `mojang`. Without a more specific
+    /// implementation, this is the mapper itself.
+    fn provenance(&self, _from_class_name: &str) -> String {
+        self.to_string()
+    }
+}
+
+/// How trustworthy a method mapping is. Chained mappers are as confident as their least
+/// confident step.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Confidence {
+    /// The method wasn't in its class, but exactly one class has a method of that name.
+    #[display(fmt = "low")]
+    Low,
+    /// The method was found in its class by name alone. Stacktrace frames have no descriptors,
+    /// so this is the best they get.
+    #[display(fmt = "medium")]
+    Medium,
+    /// The method was found in its class with its exact descriptor.
+    #[display(fmt = "high")]
+    High,
 }
 
 pub trait MapSelfOnlyClass {
@@ -108,17 +293,41 @@ pub trait MapSelf {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MethodId {
     pub name: String,
     pub descriptor: Descriptor,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Descriptor {
     pub params: Vec<Type>,
     pub return_type: Type,
 }
 
+impl Descriptor {
+    /// Parse a descriptor in the JVM internal form, e.g. `(ILjava/lang/String;)V`.
+    pub fn parse_internal(input: &str) -> Result<Self, Report<SPError>> {
+        let res = parse_recovery_debuggable(descriptor().then_ignore(end()), input);
+        handle_errors(input, res, "Failed to parse descriptor")
+    }
+
+    /// Format this descriptor in source form, e.g. `void (int, java.lang.String)`.
+    pub fn source_descriptor(&self) -> String {
+        format!("{} ({})", self.return_type, self.params.iter().join(", "))
+    }
+
+    /// Format this descriptor in the JVM internal form, e.g. `(ILjava/lang/String;)V`.
+    pub fn internal_descriptor(&self) -> String {
+        format!(
+            "({}){}",
+            self.params.iter().map(Type::internal_descriptor).join(""),
+            self.return_type.internal_descriptor()
+        )
+    }
+}
+
 impl MapSelfOnlyClass for Descriptor {
     fn map_self(self, mapper: &impl ClassMapper) -> Self {
         Self {
@@ -159,6 +368,21 @@ pub enum Type {
     Array(Box<Type>),
 }
 
+/// Types are written in their source form, e.g. `java.lang.String[]`, rather than as a tree.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Type {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Type {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from_source_name)
+    }
+}
+
 impl Type {
     pub fn from_source_name(name: String) -> Self {
         match name.as_str() {
@@ -180,6 +404,29 @@ impl Type {
             }
         }
     }
+
+    /// Parse a type in the JVM internal form, e.g. `[Ljava/lang/String;`.
+    pub fn parse_internal(input: &str) -> Result<Self, Report<SPError>> {
+        let res = parse_recovery_debuggable(descriptor_type().then_ignore(end()), input);
+        handle_errors(input, res, "Failed to parse type descriptor")
+    }
+
+    /// Format this type in the JVM internal form, e.g. `[Ljava/lang/String;`.
+    pub fn internal_descriptor(&self) -> String {
+        match self {
+            Self::Void => "V".into(),
+            Self::Boolean => "Z".into(),
+            Self::Byte => "B".into(),
+            Self::Char => "C".into(),
+            Self::Short => "S".into(),
+            Self::Int => "I".into(),
+            Self::Long => "J".into(),
+            Self::Float => "F".into(),
+            Self::Double => "D".into(),
+            Self::Object(name) => format!("L{};", name.replace('.', "/")),
+            Self::Array(ty) => format!("[{}", ty.internal_descriptor()),
+        }
+    }
 }
 
 impl MapSelfOnlyClass for Type {
@@ -226,6 +473,10 @@ impl<D: Debug, F: for<'a> Fn(&'a D, &str) -> Option<&'a str>> ClassMapper for Fn
     }
 }
 
+/// Maps names between two [`NamesType`]s for a single version.
+///
+/// Mappers are immutable once built, so they are `Send + Sync` and one mapper can be shared
+/// between threads, e.g. through an `Arc`.
 #[derive(Debug, Display)]
 #[display(fmt = "{} -> {} for {}", from, to, version)]
 pub struct BaseMapper {
@@ -233,6 +484,20 @@ pub struct BaseMapper {
     to: NamesType,
     version: String,
     mappings: Mappings,
+    /// Only known for mappers loaded from a source.
+    origin: Option<MappingOrigin>,
+}
+
+impl BaseMapper {
+    /// Where the mappings came from, if they were loaded from a source.
+    pub fn origin(&self) -> Option<&MappingOrigin> {
+        self.origin.as_ref()
+    }
+
+    /// Check if `name` is one of the class names this maps to.
+    pub fn is_target_class(&self, name: &str) -> bool {
+        self.mappings.classes.values().any(|c| c.to_name == name)
+    }
 }
 
 impl ClassMapper for BaseMapper {
@@ -250,6 +515,62 @@ impl MethodMapper for BaseMapper {
         name: &str,
         descriptor: Option<&Descriptor>,
     ) -> Vec<(&str, &MethodId)> {
+        self.map_method_with_confidence(from_class_name, name, descriptor)
+            .0
+    }
+
+    fn map_method_with_confidence(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (Vec<(&str, &MethodId)>, Confidence) {
+        match self.lookup_method(from_class_name, name, descriptor) {
+            (LookupHeuristic::Scoped, found) if descriptor.is_some() => (found, Confidence::High),
+            (LookupHeuristic::Scoped, found) => (found, Confidence::Medium),
+            (LookupHeuristic::UniqueName, found) => (found, Confidence::Low),
+            (LookupHeuristic::AmbiguousName | LookupHeuristic::NotFound, _) => {
+                (vec![], Confidence::Low)
+            }
+        }
+    }
+
+    /// The source of the mappings if they were loaded from one, e.g. `mojang`.
+    fn provenance(&self, _from_class_name: &str) -> String {
+        match &self.origin {
+            Some(origin) => origin.source.clone(),
+            None => self.to_string(),
+        }
+    }
+
+    #[tracing::instrument(ret, skip(self), fields(self_d = %self), level = "debug")]
+    fn map_field(&self, from_class_name: Option<&str>, name: &str) -> Vec<(&str, &str)> {
+        match from_class_name {
+            Some(class) => self
+                .mappings
+                .classes
+                .get(class)
+                .into_iter()
+                .flat_map(|c| extract_field(name, c))
+                .collect(),
+            None => self
+                .mappings
+                .classes
+                .values()
+                .flat_map(|c| extract_field(name, c))
+                .collect(),
+        }
+    }
+}
+
+impl BaseMapper {
+    /// Find the methods that [`MethodMapper::map_method`] would consider, and how they were found.
+    fn lookup_method(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (LookupHeuristic, Vec<(&str, &MethodId)>) {
         let scoped_result: Vec<_> = self
             .mappings
             .classes
@@ -258,7 +579,7 @@ impl MethodMapper for BaseMapper {
             .flat_map(|c| extract_method(name, descriptor, c))
             .collect();
         if !scoped_result.is_empty() {
-            return scoped_result;
+            return (LookupHeuristic::Scoped, scoped_result);
         }
         let unscoped_result: Vec<_> = self
             .mappings
@@ -266,10 +587,12 @@ impl MethodMapper for BaseMapper {
             .values()
             .flat_map(|c| extract_method(name, descriptor, c))
             .collect();
-        if unscoped_result.len() <= 1 {
-            return unscoped_result;
-        }
-        vec![]
+        let heuristic = match unscoped_result.len() {
+            0 => LookupHeuristic::NotFound,
+            1 => LookupHeuristic::UniqueName,
+            _ => LookupHeuristic::AmbiguousName,
+        };
+        (heuristic, unscoped_result)
     }
 }
 
@@ -295,17 +618,159 @@ fn extract_method<'a>(
     }
 }
 
+fn extract_field<'a>(name: &str, c: &'a ClassMapping) -> Vec<(&'a str, &'a str)> {
+    c.fields
+        .iter()
+        .filter_map(|(from, to)| (from == name).then_some((c.to_name.as_str(), to.as_str())))
+        .collect()
+}
+
+fn find_path(from: NamesType, to: NamesType) -> Result<Vec<NamesType>, Report<SPError>> {
+    let path = astar(&mappings_graph(), from, |finish| finish == to, |_| 1, |_| 0)
+        .ok_or_else(|| Report::from(SPError::UnsupportedPath))
+        .attach_printable_lazy(|| format!("No path from {} to {}", from, to))?
+        .1;
+    assert!(path.len() >= 2, "Path must have at least two elements");
+    Ok(path)
+}
+
+/// How a single step of a path would be loaded, see [`explain_path`].
+#[derive(Debug)]
+pub struct PathStep {
+    pub from: NamesType,
+    pub to: NamesType,
+    /// The sources that could provide the mappings, in the order they are tried.
+    pub sources: Vec<StepSource>,
+}
+
+/// A source that could provide the mappings for a [`PathStep`].
+#[derive(Debug)]
+pub struct StepSource {
+    pub name: String,
+    /// Where the mappings would be fetched from, if known without fetching.
+    pub location: Option<String>,
+    /// If the mappings are available without network access, e.g. from an imported bundle.
+    pub cached: bool,
+}
+
+/// Explain the path that [`generate_mapper`] would take from `from` to `to` names for the
+/// `version`, without fetching anything.
+pub fn explain_path(
+    version: &str,
+    from: NamesType,
+    to: NamesType,
+) -> Result<Vec<PathStep>, Report<SPError>> {
+    let path = find_path(from, to)?;
+    let sources = source::sources();
+    let mut steps = Vec::with_capacity(path.len() - 1);
+    for step in path.windows(2) {
+        let mut step_sources = Vec::new();
+        for source in &sources {
+            let names = (source.primary_names(), source.secondary_names());
+            if names != (step[0], step[1]) && names != (step[1], step[0]) {
+                continue;
+            }
+            step_sources.push(StepSource {
+                name: source.name().to_string(),
+                location: source.location(version),
+                cached: bundle::is_bundled(source.name(), version) || source.is_cached(version),
+            });
+        }
+        steps.push(PathStep {
+            from: step[0],
+            to: step[1],
+            sources: step_sources,
+        });
+    }
+    Ok(steps)
+}
+
+/// The mappings graph: the names that can be mapped between, and the registered sources that map
+/// between them, see [`describe_graph`].
+#[derive(Debug)]
+pub struct GraphDescription {
+    /// The names that any source maps from or to.
+    pub nodes: Vec<NamesType>,
+    /// Every registered source, in the order they are tried.
+    pub edges: Vec<GraphEdge>,
+}
+
+/// A registered source in the mappings graph. Its mappings are used in both directions.
+#[derive(Debug)]
+pub struct GraphEdge {
+    pub source: String,
+    pub primary: NamesType,
+    pub secondary: NamesType,
+    /// Where the mappings for the described version would be fetched from, if known without
+    /// fetching.
+    pub location: Option<String>,
+    /// If the mappings for the described version are available without network access.
+    pub cached: bool,
+}
+
+/// Describe the mappings graph as it is now, including the sources registered with
+/// [`register_source`](source::register_source). With a `version`, each source says where its
+/// mappings for it would come from. Nothing is fetched.
+pub fn describe_graph(version: Option<&str>) -> GraphDescription {
+    let mut nodes: Vec<NamesType> = mappings_graph().nodes().collect();
+    nodes.sort();
+    let edges = source::sources()
+        .iter()
+        .map(|source| GraphEdge {
+            source: source.name().to_string(),
+            primary: source.primary_names(),
+            secondary: source.secondary_names(),
+            location: version.and_then(|version| source.location(version)),
+            cached: version.is_some_and(|version| {
+                bundle::is_bundled(source.name(), version) || source.is_cached(version)
+            }),
+        })
+        .collect();
+    GraphDescription { nodes, edges }
+}
+
+impl GraphDescription {
+    /// Write the graph in Graphviz's DOT language, with an edge labeled with its name for each
+    /// source.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph mappings {\n");
+        for node in &self.nodes {
+            dot.push_str(&format!("    \"{}\";\n", node));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    \"{}\" -- \"{}\" [label=\"{}\"];\n",
+                edge.primary,
+                edge.secondary,
+                edge.source.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Download and parse everything needed to map from obfuscated names to `names` for the
+/// `version`, so that later runs only need the cache.
+pub fn preload(version: &str, names: NamesType) -> Result<(), Report<SPError>> {
+    if names == NamesType::Obfuscated {
+        return Ok(());
+    }
+    let path = find_path(NamesType::Obfuscated, names)?;
+    check_path_available(version, &path)?;
+    for step in path.windows(2) {
+        load_step(version, step[0], step[1])?;
+    }
+    Ok(())
+}
+
 pub fn generate_mapper(
     version: String,
     from: NamesType,
     to: NamesType,
 ) -> Result<EitherMapper, Report<SPError>> {
-    let g = &*MAPPINGS_GRAPH;
-    let path = astar(g, from, |finish| finish == to, |_| 1, |_| 0)
-        .ok_or_else(|| Report::from(SPError))
-        .attach_printable_lazy(|| format!("No path from {} to {}", from, to))?
-        .1;
-    assert!(path.len() >= 2, "Path must have at least two elements");
+    let path = find_path(from, to)?;
+    check_path_available(&version, &path)?;
 
     fn sanity_check_mapper(m: BaseMapper, from: NamesType, to: NamesType) -> BaseMapper {
         assert_eq!(
@@ -322,29 +787,103 @@ pub fn generate_mapper(
     }
 
     if path.len() == 2 {
-        return MAPPINGS_GRAPH
-            .edge_weight(path[0], path[1])
-            .expect("astar gave a path with no edge")
-            .load(version)
+        return load_step(&version, path[0], path[1])
             .map(|b| EitherMapper::Base(sanity_check_mapper(b, from, to)));
     }
     let mut mappers = Vec::with_capacity(path.len() - 1);
     for i in 0..path.len() - 1 {
-        let mapper = MAPPINGS_GRAPH
-            .edge_weight(path[i], path[i + 1])
-            .expect("astar gave a path with no edge")
-            .load(version.clone())?;
+        let mapper = load_step(&version, path[i], path[i + 1])?;
         mappers.push(sanity_check_mapper(mapper, path[i], path[i + 1]));
     }
     Ok(EitherMapper::Multi(MultiMapper { mappers }))
 }
 
+/// Either a single mapper or a chain of them. Like [`BaseMapper`], this is `Send + Sync`.
 #[derive(Debug)]
 pub enum EitherMapper {
     Base(BaseMapper),
     Multi(MultiMapper),
 }
 
+/// Counts of what a mapper maps, see [`EitherMapper::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapperStats {
+    /// The classes in the `from` names that the mappings know of.
+    pub classes: usize,
+    /// The methods in the `from` names that the mappings know of.
+    pub methods: usize,
+    /// The classes that map all the way to the `to` names.
+    pub mapped_classes: usize,
+    /// The methods that map all the way to the `to` names.
+    pub mapped_methods: usize,
+    /// The mapped classes with the same name in both names.
+    pub identical_classes: usize,
+    /// The mapped methods with the same name in both names.
+    pub identical_methods: usize,
+}
+
+impl EitherMapper {
+    /// Where the mappings of each step came from, for the steps loaded from a source.
+    pub fn origins(&self) -> Vec<&MappingOrigin> {
+        match self {
+            EitherMapper::Base(m) => m.origin().into_iter().collect(),
+            EitherMapper::Multi(m) => m.mappers.iter().filter_map(|m| m.origin()).collect(),
+        }
+    }
+
+    /// Count the classes and methods this maps, and how many map all the way through.
+    pub fn stats(&self) -> MapperStats {
+        let mappers = match self {
+            EitherMapper::Base(m) => std::slice::from_ref(m),
+            EitherMapper::Multi(m) => m.mappers.as_slice(),
+        };
+        let Some((first, rest)) = mappers.split_first() else {
+            return MapperStats::default();
+        };
+        let mut stats = MapperStats::default();
+        for (name, class) in &first.mappings.classes {
+            stats.classes += 1;
+            // Look up each step directly, the fallbacks of `map_method` are far too slow for this
+            let mapped_class = rest.iter().try_fold(class.to_name.as_str(), |name, m| {
+                m.mappings.classes.get(name).map(|c| c.to_name.as_str())
+            });
+            if let Some(mapped_class) = mapped_class {
+                stats.mapped_classes += 1;
+                if mapped_class == name {
+                    stats.identical_classes += 1;
+                }
+            }
+            for (from_id, to_id) in &class.methods {
+                stats.methods += 1;
+                let mapped_method =
+                    rest.iter()
+                        .try_fold((class.to_name.as_str(), to_id), |(name, id), m| {
+                            let c = m.mappings.classes.get(name)?;
+                            Some((c.to_name.as_str(), c.methods.get(id)?))
+                        });
+                if let Some((_, mapped_method)) = mapped_method {
+                    stats.mapped_methods += 1;
+                    if mapped_method.name == from_id.name {
+                        stats.identical_methods += 1;
+                    }
+                }
+            }
+        }
+        stats
+    }
+
+    /// Check if `name` is one of the class names this maps to.
+    pub fn is_target_class(&self, name: &str) -> bool {
+        match self {
+            EitherMapper::Base(m) => m.is_target_class(name),
+            EitherMapper::Multi(m) => m
+                .mappers
+                .last()
+                .is_some_and(|last| last.is_target_class(name)),
+        }
+    }
+}
+
 impl Display for EitherMapper {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -375,8 +914,39 @@ impl MethodMapper for EitherMapper {
             EitherMapper::Multi(m) => m.map_method(from_class_name, name, descriptor),
         }
     }
+
+    fn map_method_with_confidence(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (Vec<(&str, &MethodId)>, Confidence) {
+        match self {
+            EitherMapper::Base(m) => {
+                m.map_method_with_confidence(from_class_name, name, descriptor)
+            }
+            EitherMapper::Multi(m) => {
+                m.map_method_with_confidence(from_class_name, name, descriptor)
+            }
+        }
+    }
+
+    fn map_field(&self, from_class_name: Option<&str>, name: &str) -> Vec<(&str, &str)> {
+        match self {
+            EitherMapper::Base(m) => m.map_field(from_class_name, name),
+            EitherMapper::Multi(m) => m.map_field(from_class_name, name),
+        }
+    }
+
+    fn provenance(&self, from_class_name: &str) -> String {
+        match self {
+            EitherMapper::Base(m) => m.provenance(from_class_name),
+            EitherMapper::Multi(m) => m.provenance(from_class_name),
+        }
+    }
 }
 
+/// Maps names through a chain of [`BaseMapper`]s. Like them, this is `Send + Sync`.
 #[derive(Debug)]
 pub struct MultiMapper {
     mappers: Vec<BaseMapper>,
@@ -412,13 +982,28 @@ impl MethodMapper for MultiMapper {
         name: &str,
         descriptor: Option<&Descriptor>,
     ) -> Vec<(&str, &MethodId)> {
+        self.map_method_with_confidence(from_class_name, name, descriptor)
+            .0
+    }
+
+    fn map_method_with_confidence(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (Vec<(&str, &MethodId)>, Confidence) {
         let mut push_data = vec![(from_class_name, name, descriptor)];
         let mut ret_ids = vec![];
+        let mut confidence = Confidence::High;
         for mapper in &self.mappers {
             // drop existing set of return values first
             ret_ids.clear();
             for (class_name, name, descriptor) in push_data.drain(..) {
-                let mapped_names = mapper.map_method(class_name, name, descriptor);
+                let (mapped_names, step_confidence) =
+                    mapper.map_method_with_confidence(class_name, name, descriptor);
+                if !mapped_names.is_empty() {
+                    confidence = confidence.min(step_confidence);
+                }
                 ret_ids.extend(mapped_names);
             }
             // Now that we have all the values mapped, setup for the next iteration
@@ -429,6 +1014,33 @@ impl MethodMapper for MultiMapper {
                     .map(|(cname, id)| (cname, id.name.as_str(), Some(&id.descriptor))),
             );
         }
-        ret_ids
+        if ret_ids.is_empty() {
+            confidence = Confidence::Low;
+        }
+        (ret_ids, confidence)
+    }
+
+    #[tracing::instrument(ret, skip(self), fields(self_d = %self), level = "debug")]
+    fn map_field(&self, from_class_name: Option<&str>, name: &str) -> Vec<(&str, &str)> {
+        let Some((first, rest)) = self.mappers.split_first() else {
+            return Vec::new();
+        };
+        // Only the first step may need to search every class, after that the class is known
+        let mut found = first.map_field(from_class_name, name);
+        for mapper in rest {
+            found = found
+                .into_iter()
+                .flat_map(|(class, name)| mapper.map_field(Some(class), name))
+                .collect();
+        }
+        found
+    }
+
+    /// The provenance of each step, e.g. `fabric_intermediary -> yarn_intermediary_named`.
+    fn provenance(&self, from_class_name: &str) -> String {
+        self.mappers
+            .iter()
+            .map(|m| m.provenance(from_class_name))
+            .join(" -> ")
     }
 }
@@ -0,0 +1,116 @@
+use error_stack::Report;
+
+use crate::mappings::proguard::PGMappings;
+use crate::mappings::raw::convert_mappings;
+use crate::mappings::source::{RawClassMapping, RawMethodMapping};
+use crate::mappings::tiny::TinyMappings;
+use crate::mappings::{BaseMapper, EitherMapper, MultiMapper};
+use crate::names::NamesType;
+use crate::SPError;
+
+/// Builds a mapper from mappings that are already in memory, without fetching or caching
+/// anything, e.g. for tests or services that have the mapping files at hand.
+///
+/// Each step maps between two names, and the steps are chained in the order they are added, so
+/// each step must start with the names the previous one ends with.
+#[derive(Debug)]
+pub struct MapperBuilder {
+    version: String,
+    steps: Vec<BaseMapper>,
+}
+
+impl MapperBuilder {
+    /// Start a mapper for the `version`, which is only used to describe the mapper.
+    pub fn new(version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Add a step from raw mappings between the `primary` and `secondary` names, given in that
+    /// order. The step maps from `from` to `to`, which must be the same two names, flipping the
+    /// mappings if needed.
+    ///
+    /// Fails if `from` and `to` aren't the names of the mappings.
+    pub fn raw_step<CM, M>(
+        mut self,
+        primary: NamesType,
+        secondary: NamesType,
+        from: NamesType,
+        to: NamesType,
+        mappings: CM,
+    ) -> Result<Self, Report<SPError>>
+    where
+        CM: IntoIterator<Item = RawClassMapping<M>>,
+        M: IntoIterator<Item = RawMethodMapping>,
+    {
+        if (primary, secondary) != (from, to) && (secondary, primary) != (from, to) {
+            return Err(
+                Report::new(SPError::UnsupportedPath).attach_printable(format!(
+                    "Step {} -> {} doesn't match the mappings between {} and {}",
+                    from, to, primary, secondary
+                )),
+            );
+        }
+        self.steps.push(convert_mappings(
+            primary,
+            secondary,
+            self.version.clone(),
+            mappings,
+            (secondary, primary) == (from, to),
+        ));
+        Ok(self)
+    }
+
+    /// Add a step from ProGuard mappings, such as Mojang's, which map the `to` names to the
+    /// `from` names. The step maps from `from` to `to`.
+    pub fn proguard_step(
+        self,
+        from: NamesType,
+        to: NamesType,
+        mappings: PGMappings,
+    ) -> Result<Self, Report<SPError>> {
+        self.raw_step(to, from, from, to, mappings.into_raw())
+    }
+
+    /// Add a step from tiny mappings, mapping from the `from_namespace` as `from` names to the
+    /// `to_namespace` as `to` names. The mappings are borrowed, so a file with more namespaces can
+    /// give several steps.
+    pub fn tiny_step(
+        self,
+        from: NamesType,
+        to: NamesType,
+        mappings: &TinyMappings,
+        from_namespace: &str,
+        to_namespace: &str,
+    ) -> Result<Self, Report<SPError>> {
+        let mappings = mappings.to_raw(from_namespace, to_namespace)?;
+        self.raw_step(from, to, from, to, mappings)
+    }
+
+    /// Build the mapper, failing if there are no steps or they don't chain.
+    pub fn build(mut self) -> Result<EitherMapper, Report<SPError>> {
+        if self.steps.is_empty() {
+            return Err(Report::new(SPError::UnsupportedPath)
+                .attach_printable("A mapper needs at least one step"));
+        }
+        for pair in self.steps.windows(2) {
+            if pair[0].to != pair[1].from {
+                return Err(
+                    Report::new(SPError::UnsupportedPath).attach_printable(format!(
+                        "Step {} doesn't continue from step {}",
+                        pair[1], pair[0]
+                    )),
+                );
+            }
+        }
+        Ok(if self.steps.len() == 1 {
+            EitherMapper::Base(self.steps.remove(0))
+        } else {
+            EitherMapper::Multi(MultiMapper {
+                mappers: self.steps,
+            })
+        })
+    }
+}
@@ -0,0 +1,146 @@
+//! Bundles of fetched mappings, to carry them to machines without network access.
+//!
+//! A bundle is a zstd-compressed tar with an entry named `<source>/<version>` for the mappings of
+//! each [`MappingSource`](super::source::MappingSource) and version. Imported mappings are used
+//! instead of fetching them from their source.
+
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use error_stack::{Report, ResultExt};
+
+use crate::mappings::cache::cache_dir;
+use crate::mappings::{fetch_step, find_path};
+use crate::names::NamesType;
+use crate::SPError;
+
+/// Fetch everything needed to map from obfuscated names to each of the `names`, for each of the
+/// `versions`, and write it to a bundle.
+///
+/// # Returns
+/// The number of mappings in the bundle.
+pub fn export_bundle(
+    versions: &[String],
+    names: &[NamesType],
+    output: impl Write,
+) -> Result<usize, Report<SPError>> {
+    let encoder = zstd::Encoder::new(output, 0)
+        .change_context(SPError::IoError)
+        .attach_printable("Failed to start compressing bundle")?;
+    let mut builder = tar::Builder::new(encoder);
+    let mut written = HashSet::new();
+    for version in versions {
+        for &names in names {
+            if names == NamesType::Obfuscated {
+                continue;
+            }
+            for step in find_path(NamesType::Obfuscated, names)?.windows(2) {
+                let fetched = fetch_step(version, step[0], step[1])?;
+                let entry_name = format!("{}/{}", fetched.source.name(), version);
+                if !written.insert(entry_name.clone()) {
+                    continue;
+                }
+                tracing::info!("Bundling {}...", entry_name);
+                let mut header = tar::Header::new_gnu();
+                header.set_size(fetched.content.len() as u64);
+                header.set_mode(0o644);
+                builder
+                    .append_data(&mut header, &entry_name, fetched.content.as_bytes())
+                    .change_context(SPError::IoError)
+                    .attach_printable_lazy(|| {
+                        format!("Failed to write {} to bundle", entry_name)
+                    })?;
+            }
+        }
+    }
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut output| output.flush())
+        .change_context(SPError::IoError)
+        .attach_printable("Failed to finish bundle")?;
+    Ok(written.len())
+}
+
+/// Import the mappings in a bundle written by [`export_bundle`], replacing any imported before.
+///
+/// # Returns
+/// The number of mappings imported.
+pub fn import_bundle(input: impl Read) -> Result<usize, Report<SPError>> {
+    let decoder = zstd::Decoder::new(input)
+        .change_context(SPError::IoError)
+        .attach_printable("Failed to start decompressing bundle")?;
+    let mut archive = tar::Archive::new(decoder);
+    let entries = archive
+        .entries()
+        .change_context(SPError::IoError)
+        .attach_printable("Failed to read bundle")?;
+    let mut imported = 0;
+    for entry in entries {
+        let mut entry = entry
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to read bundle")?;
+        let name = entry
+            .path()
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to read bundle entry name")?
+            .to_string_lossy()
+            .into_owned();
+        let target = name
+            .split_once('/')
+            .and_then(|(source, version)| bundled_path(source, version))
+            .ok_or_else(|| {
+                Report::new(SPError::ParseError)
+                    .attach_printable(format!("Invalid bundle entry {}", name))
+            })?;
+        let mut content = Vec::new();
+        entry
+            .read_to_end(&mut content)
+            .change_context(SPError::IoError)
+            .attach_printable_lazy(|| format!("Failed to read {} from bundle", name))?;
+        std::fs::create_dir_all(target.parent().expect("bundled path has a parent"))
+            .and_then(|_| std::fs::write(&target, content))
+            .change_context(SPError::CacheError)
+            .attach_printable_lazy(|| format!("Failed to write {}", target.display()))?;
+        tracing::info!("Imported {}", name);
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// Read the imported mappings of a source for the `version`, if there are any.
+pub(crate) fn read_bundled(source: &str, version: &str) -> Result<Option<String>, Report<SPError>> {
+    let Some(path) = bundled_path(source, version) else {
+        return Ok(None);
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(content) => {
+            tracing::debug!("Using imported mappings {}", path.display());
+            Ok(Some(content))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Report::new(e)
+            .change_context(SPError::CacheError)
+            .attach_printable(format!("Failed to read {}", path.display()))),
+    }
+}
+
+/// Check if there are imported mappings of a source for the `version`.
+pub(crate) fn is_bundled(source: &str, version: &str) -> bool {
+    bundled_path(source, version).is_some_and(|path| path.is_file())
+}
+
+/// Where the imported mappings of a source for the `version` are kept, if both are valid file
+/// names.
+fn bundled_path(source: &str, version: &str) -> Option<PathBuf> {
+    let is_file_name = |name: &str| {
+        let mut components = Path::new(name).components();
+        matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+    };
+    (is_file_name(source) && is_file_name(version))
+        .then(|| cache_dir().join("bundled").join(source).join(version))
+}
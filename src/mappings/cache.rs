@@ -1,18 +1,61 @@
-use crate::http::good_error_request;
+use crate::http::{good_error_request, good_error_request_from, is_range_not_satisfiable};
+use crate::progress::{PROGRESS_TARGET, TIMINGS_TARGET};
 use crate::SPError;
 use digest::Output;
 use directories::ProjectDirs;
 use error_stack::{Report, ResultExt};
+use itertools::Itertools;
+use md5::Md5;
 use once_cell::sync::Lazy;
 use sha1::{Digest, Sha1};
-use sha2::Sha512;
+use sha2::{Sha256, Sha512};
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 static DIRS: Lazy<ProjectDirs> = Lazy::new(|| {
     ProjectDirs::from("net", "octyl", "stacked-portrayals").expect("Failed to get project dirs")
 });
 
+static REFRESH: AtomicBool = AtomicBool::new(false);
+static ALLOW_UNVERIFIED: AtomicBool = AtomicBool::new(false);
+
+/// The cache files that were already downloaded again since [`refresh_cache`].
+static REFRESHED: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(Mutex::default);
+
+/// A lock for each cache file that was loaded, so threads loading the same mappings at once, e.g.
+/// while preloading, don't download them into the same files. The later ones wait, then find the
+/// mappings cached.
+static LOADING: Lazy<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = Lazy::new(Mutex::default);
+
+/// Ignore the cached mappings for the rest of the process, e.g. because an artifact was
+/// republished under the same version. Each file is downloaded and verified again the first time
+/// it is needed, replacing the cached copy. The Gradle cache of Fabric Loom is ignored too.
+pub fn refresh_cache() {
+    REFRESH.store(true, Ordering::Relaxed);
+}
+
+/// Check if the cached mappings are being ignored, see [`refresh_cache`].
+pub(crate) fn is_refreshing() -> bool {
+    REFRESH.load(Ordering::Relaxed)
+}
+
+/// Allow downloading mappings that have no hash to verify them with, for the rest of the process.
+/// Otherwise, sources refuse to download such mappings.
+pub fn allow_unverified() {
+    ALLOW_UNVERIFIED.store(true, Ordering::Relaxed);
+}
+
+/// Check if mappings without a hash may be downloaded, see [`allow_unverified`].
+#[cfg(feature = "fabric")]
+pub(crate) fn allows_unverified() -> bool {
+    ALLOW_UNVERIFIED.load(Ordering::Relaxed)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MappingDownload {
     pub kind: String,
@@ -23,29 +66,64 @@ pub struct MappingDownload {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HashCode {
+    Md5(String),
     Sha1(String),
+    Sha256(String),
     Sha512(String),
+    /// No hash is known, so the mappings are never verified, only checked against their size if
+    /// it is known. The string only names them in the cache, e.g. a hash of their URL. See
+    /// [`allow_unverified`].
+    Unverified(String),
 }
 
 impl HashCode {
-    fn name(&self) -> &'static str {
+    /// Recover the hash from a cache file name, e.g. `sha1.<hash>.mapsrc.zst`, or
+    /// `sha1.<hash>.mapsrc` if it was cached uncompressed.
+    fn from_file_name(file_name: &str) -> Option<Self> {
+        let file_name = file_name.strip_suffix(".zst").unwrap_or(file_name);
+        let (name, rest) = file_name.strip_suffix(".mapsrc")?.split_once('.')?;
+        Self::from_name(name, rest.to_string())
+    }
+
+    /// Make a hash from its name, e.g. `sha1`, and its `value`.
+    pub(crate) fn from_name(name: &str, value: String) -> Option<Self> {
+        match name {
+            "md5" => Some(Self::Md5(value)),
+            "sha1" => Some(Self::Sha1(value)),
+            "sha256" => Some(Self::Sha256(value)),
+            "sha512" => Some(Self::Sha512(value)),
+            "unverified" => Some(Self::Unverified(value)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
         match self {
+            Self::Md5(_) => "md5",
             Self::Sha1(_) => "sha1",
+            Self::Sha256(_) => "sha256",
             Self::Sha512(_) => "sha512",
+            Self::Unverified(_) => "unverified",
         }
     }
 
-    fn value(&self) -> &str {
+    pub(crate) fn value(&self) -> &str {
         match self {
+            Self::Md5(v) => v,
             Self::Sha1(v) => v,
+            Self::Sha256(v) => v,
             Self::Sha512(v) => v,
+            Self::Unverified(v) => v,
         }
     }
 
     fn verify(&self, mut content: impl Read) -> Result<(), Report<SPError>> {
         match self {
+            Self::Md5(expect) => self.verify_impl::<Md5>(&mut content, expect),
             Self::Sha1(expect) => self.verify_impl::<Sha1>(&mut content, expect),
+            Self::Sha256(expect) => self.verify_impl::<Sha256>(&mut content, expect),
             Self::Sha512(expect) => self.verify_impl::<Sha512>(&mut content, expect),
+            Self::Unverified(_) => Ok(()),
         }
     }
 
@@ -56,11 +134,11 @@ impl HashCode {
     {
         let mut digest = D::new();
         std::io::copy(&mut content, &mut digest)
-            .change_context(SPError)
+            .change_context(SPError::CacheError)
             .attach_printable("Failed to hash content")?;
         let hash = format!("{:x}", digest.finalize());
         if hash != expect.to_ascii_lowercase() {
-            return Err(Report::new(SPError).attach_printable(format!(
+            return Err(Report::new(SPError::CacheError).attach_printable(format!(
                 "Mappings file had hash {}, expected {}",
                 hash, expect
             )));
@@ -69,74 +147,154 @@ impl HashCode {
     }
 }
 
-pub fn load_mappings(dl: MappingDownload) -> Result<File, Report<SPError>> {
-    let cache_file = DIRS.cache_dir().join(format!(
-        "{}/{}.{}.mapsrc",
+/// The directory that everything is cached in.
+pub(crate) fn cache_dir() -> &'static Path {
+    DIRS.cache_dir()
+}
+
+fn cache_path(dl: &MappingDownload, extension: &str) -> PathBuf {
+    DIRS.cache_dir().join(format!(
+        "{}/{}.{}.{}",
         dl.kind,
         dl.hash.name(),
-        dl.hash.value()
-    ));
+        dl.hash.value(),
+        extension
+    ))
+}
+
+/// Read a file derived from the mappings, e.g. their extracted contents, if it was cached with
+/// [`write_derived`].
+pub fn read_derived(dl: &MappingDownload, extension: &str) -> Option<String> {
+    if is_refreshing() {
+        return None;
+    }
+    let path = cache_path(dl, extension);
+    let content = std::fs::read_to_string(&path).ok()?;
+    tracing::debug!("Using cached {}", path.display());
+    Some(content)
+}
+
+/// Cache a file derived from the mappings next to them, with the `extension` instead of
+/// `.mapsrc`. It can always be derived again, so failures are only logged.
+pub fn write_derived(dl: &MappingDownload, extension: &str, content: &str) {
+    let path = cache_path(dl, extension);
+    let dir = path.parent().expect("cache path has a parent");
+    // Write to a temporary file first, so a partial write is never read back
+    let result = tempfile::NamedTempFile::new_in(dir).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.persist(&path).map_err(|e| e.error)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        tracing::debug!("Failed to cache {}: {}", path.display(), e);
+    }
+}
+
+/// Check if the mappings are cached, so loading them needs no network access. They aren't while
+/// refreshing the cache, as they are downloaded again.
+pub fn is_cached(dl: &MappingDownload) -> bool {
+    !is_refreshing()
+        && (cache_path(dl, "mapsrc.zst").is_file() || cache_path(dl, "mapsrc").is_file())
+}
+
+/// Load the mappings from the cache, downloading and verifying them first if needed.
+///
+/// Cached mappings are kept compressed, so this gives a decompressed copy in an unlinked temporary
+/// file, which can be memory-mapped with [`Content::map_file`](super::source::Content::map_file)
+/// rather than read into memory. Mappings that were cached uncompressed are compressed the first
+/// time they are loaded.
+pub fn load_mappings(dl: MappingDownload) -> Result<File, Report<SPError>> {
+    let cache_file = cache_path(&dl, "mapsrc.zst");
+    let legacy_file = cache_path(&dl, "mapsrc");
+    let lock = LOADING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(cache_file.clone())
+        .or_default()
+        .clone();
+    let _loading = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if is_refreshing()
+        && REFRESHED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(cache_file.clone())
+    {
+        tracing::debug!("Ignoring cached {}", cache_file.display());
+        for file in [&cache_file, &legacy_file, &part_path(&cache_file)] {
+            let _ = std::fs::remove_file(stamp_path(file));
+            match std::fs::remove_file(file) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(Report::new(e)
+                        .change_context(SPError::CacheError)
+                        .attach_printable(format!(
+                            "Failed to remove cached mappings {}",
+                            file.display()
+                        )))
+                }
+            }
+        }
+    }
+    if legacy_file.is_file() && !cache_file.exists() {
+        if let Err(e) = migrate_legacy(&dl, &legacy_file, &cache_file) {
+            tracing::debug!("Failed to compress {}: {:?}", legacy_file.display(), e);
+        }
+    }
     let mut failures = Vec::new();
     for _attempt in 0..5 {
-        let mut file = match File::open(&cache_file) {
+        let file = match File::open(&cache_file) {
             Ok(f) => f,
             Err(e) if e.kind() == ErrorKind::NotFound => {
-                std::fs::create_dir_all(cache_file.parent().unwrap())
-                    .change_context(SPError)
-                    .attach_printable(format!(
-                        "Failed to create cache directory {}",
-                        cache_file.parent().unwrap().display()
-                    ))?;
-                let mut file: File = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .read(true)
-                    .open(&cache_file)
-                    .change_context(SPError)
-                    .attach_printable(format!(
-                        "Failed to create mappings cache file {}",
-                        cache_file.display()
-                    ))?;
-                let mut download = good_error_request(&dl.source)?;
-                download
-                    .copy_to(&mut file)
-                    .change_context(SPError)
+                download_compressed(&dl, &cache_file)?;
+                File::open(&cache_file)
+                    .change_context(SPError::CacheError)
                     .attach_printable(format!(
-                        "Failed to copy mappings to cache file {}",
+                        "Failed to open downloaded mappings {}",
                         cache_file.display()
-                    ))?;
-                file.seek(SeekFrom::Start(0))
-                    .change_context(SPError)
-                    .attach_printable("Failed to reset cached mappings file position")?;
-                file
+                    ))?
             }
             Err(e) => {
                 // likely unrecoverable, bail
                 return Err(Report::new(e)
-                    .change_context(SPError)
+                    .change_context(SPError::CacheError)
                     .attach_printable(format!(
                         "Failed to open cached mappings {}",
                         cache_file.display()
                     )));
             }
         };
-        let Err(validate_error) = validate_mappings(&dl, &mut file) else {
-            file.seek(SeekFrom::Start(0))
-                .change_context(SPError)
+        let trusted =
+            read_stamp(&cache_file).is_some_and(|stamp| Some(stamp) == make_stamp(&dl, &file));
+        let result = decompress(&file).and_then(|mut decompressed| {
+            if trusted {
+                tracing::debug!("Trusting verified cache file {}", cache_file.display());
+            } else {
+                validate_mappings(&dl, &mut decompressed)?;
+                write_stamp(&cache_file, &dl, &file);
+            }
+            decompressed
+                .seek(SeekFrom::Start(0))
+                .change_context(SPError::CacheError)
                 .attach_printable("Failed to reset cached mappings file position")?;
-            return Ok(file);
+            Ok(decompressed)
+        });
+        let validate_error = match result {
+            Ok(decompressed) => return Ok(decompressed),
+            Err(e) => e,
         };
         drop(file);
         failures.push(validate_error.attach_printable(format!("Source: {}", dl.source)));
         // delete and try again
+        let _ = std::fs::remove_file(stamp_path(&cache_file));
         std::fs::remove_file(&cache_file)
-            .change_context(SPError)
+            .change_context(SPError::CacheError)
             .attach_printable(format!(
                 "Failed to remove invalid cached mappings file {}",
                 cache_file.display()
             ))?;
     }
-    let mut report = Report::new(SPError).attach_printable(format!(
+    let mut report = Report::new(SPError::NetworkError).attach_printable(format!(
         "Failed to download and validate mappings {}",
         cache_file.display()
     ));
@@ -146,15 +304,300 @@ pub fn load_mappings(dl: MappingDownload) -> Result<File, Report<SPError>> {
     Err(report)
 }
 
+/// Download the mappings and compress them into the `cache_file`.
+///
+/// The download is kept uncompressed in a `.part` file next to the `cache_file` until it is
+/// complete, so an interrupted download is resumed by the next attempt if the server supports it.
+fn download_compressed(dl: &MappingDownload, cache_file: &Path) -> Result<(), Report<SPError>> {
+    let dir = cache_file.parent().expect("cache path has a parent");
+    std::fs::create_dir_all(dir)
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to create cache directory {}", dir.display()))?;
+    let part_file = part_path(cache_file);
+    let mut part = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .read(true)
+        .open(&part_file)
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to open {}", part_file.display()))?;
+    let offset = part
+        .metadata()
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to get size of {}", part_file.display()))?
+        .len();
+    tracing::info!(
+        target: PROGRESS_TARGET,
+        event = "download_started",
+        kind = dl.kind,
+        url = dl.source
+    );
+    let _span = tracing::info_span!(target: TIMINGS_TARGET, "download").entered();
+    let mut download = match good_error_request_from(&dl.source, offset) {
+        Ok(download) => download,
+        // The partial download can't be resumed, e.g. because it is already complete
+        Err(e) if offset > 0 && is_range_not_satisfiable(&e) => {
+            truncate(&part, &part_file)?;
+            good_error_request(&dl.source)?
+        }
+        Err(e) => return Err(e),
+    };
+    let offset = if download.status == 206 {
+        tracing::info!("Resuming download of {} from byte {}", dl.source, offset);
+        offset
+    } else {
+        // The server sent everything
+        truncate(&part, &part_file)?;
+        0
+    };
+    let copied = download.copy_to(&mut part).attach_printable_lazy(|| {
+        format!(
+            "Failed to copy mappings to cache file {}",
+            part_file.display()
+        )
+    })?;
+    tracing::info!(
+        target: PROGRESS_TARGET,
+        event = "download_finished",
+        kind = dl.kind,
+        url = dl.source,
+        bytes = offset + copied
+    );
+    part.seek(SeekFrom::Start(0))
+        .change_context(SPError::CacheError)
+        .attach_printable("Failed to reset downloaded mappings file position")?;
+    compress_into(&part, cache_file)?;
+    drop(part);
+    if let Err(e) = std::fs::remove_file(&part_file) {
+        tracing::debug!("Failed to remove {}: {}", part_file.display(), e);
+    }
+    Ok(())
+}
+
+/// Where a download is kept until it is complete, see [`download_compressed`].
+fn part_path(cache_file: &Path) -> PathBuf {
+    cache_file.with_extension("part")
+}
+
+fn truncate(file: &File, path: &Path) -> Result<(), Report<SPError>> {
+    file.set_len(0)
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to truncate {}", path.display()))
+}
+
+/// Compress the `source` into the `cache_file`. It is written to a temporary file first, so a
+/// partial write is never read back.
+fn compress_into(source: &File, cache_file: &Path) -> Result<(), Report<SPError>> {
+    let dir = cache_file.parent().expect("cache path has a parent");
+    tempfile::NamedTempFile::new_in(dir)
+        .change_context(SPError::CacheError)
+        .and_then(|mut temp| {
+            zstd::stream::copy_encode(source, &mut temp, 0).change_context(SPError::CacheError)?;
+            temp.persist(cache_file)
+                .map_err(|e| e.error)
+                .change_context(SPError::CacheError)
+        })
+        .attach_printable_lazy(|| format!("Failed to write cache file {}", cache_file.display()))?;
+    Ok(())
+}
+
+/// Decompress a cache file into a temporary file, positioned at its start.
+fn decompress(file: &File) -> Result<File, Report<SPError>> {
+    let mut decompressed = tempfile::tempfile()
+        .change_context(SPError::CacheError)
+        .attach_printable("Failed to create temporary file for mappings")?;
+    zstd::stream::copy_decode(file, &mut decompressed)
+        .change_context(SPError::CacheError)
+        .attach_printable("Failed to decompress cached mappings")?;
+    decompressed
+        .seek(SeekFrom::Start(0))
+        .change_context(SPError::CacheError)
+        .attach_printable("Failed to reset decompressed mappings file position")?;
+    Ok(decompressed)
+}
+
+/// Compress mappings that were cached uncompressed, if they are still valid, and remove them.
+fn migrate_legacy(
+    dl: &MappingDownload,
+    legacy_file: &Path,
+    cache_file: &Path,
+) -> Result<(), Report<SPError>> {
+    let mut legacy = File::open(legacy_file)
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to open {}", legacy_file.display()))?;
+    let trusted =
+        read_stamp(legacy_file).is_some_and(|stamp| Some(stamp) == make_stamp(dl, &legacy));
+    if trusted || validate_mappings(dl, &mut legacy).is_ok() {
+        tracing::debug!("Compressing {}", legacy_file.display());
+        legacy
+            .seek(SeekFrom::Start(0))
+            .change_context(SPError::CacheError)?;
+        compress_into(&legacy, cache_file)?;
+    }
+    drop(legacy);
+    let _ = std::fs::remove_file(stamp_path(legacy_file));
+    std::fs::remove_file(legacy_file)
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to remove {}", legacy_file.display()))
+}
+
+/// Where the stamp of a cache file is kept. The stamp records that the file was verified, so it
+/// isn't hashed again while it is unchanged.
+fn stamp_path(cache_file: &Path) -> PathBuf {
+    cache_file.with_extension("stamp")
+}
+
+/// Make the stamp for a cache file from its size and modification time, and a hash of the hash it
+/// was verified against.
+fn make_stamp(dl: &MappingDownload, file: &File) -> Option<String> {
+    let metadata = file.metadata().ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?;
+    let hash_hash = Sha1::digest(format!("{}:{}", dl.hash.name(), dl.hash.value()));
+    Some(format!(
+        "{} {} {:x}",
+        metadata.len(),
+        modified.as_nanos(),
+        hash_hash
+    ))
+}
+
+fn read_stamp(cache_file: &Path) -> Option<String> {
+    std::fs::read_to_string(stamp_path(cache_file)).ok()
+}
+
+/// Record that a cache file was verified. This is only an optimization, so failures are ignored.
+fn write_stamp(cache_file: &Path, dl: &MappingDownload, file: &File) {
+    let Some(stamp) = make_stamp(dl, file) else {
+        return;
+    };
+    if let Err(e) = std::fs::write(stamp_path(cache_file), stamp) {
+        tracing::debug!("Failed to write stamp for {}: {}", cache_file.display(), e);
+    }
+}
+
+/// The outcome of [`verify_cache`].
+#[derive(Debug, Default)]
+pub struct CacheVerification {
+    /// The number of cached mappings that were checked.
+    pub checked: usize,
+    /// The cached mappings that didn't match their hash.
+    pub corrupt: Vec<PathBuf>,
+}
+
+/// Re-check every cached mappings file against its hash, deleting the corrupt ones and the files
+/// derived from them if `delete` is set. Each corrupt file is logged.
+pub fn verify_cache(delete: bool) -> Result<CacheVerification, Report<SPError>> {
+    let mut verification = CacheVerification::default();
+    let kind_dirs = match std::fs::read_dir(DIRS.cache_dir()) {
+        Ok(dirs) => dirs,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(verification),
+        Err(e) => {
+            return Err(Report::new(e)
+                .change_context(SPError::CacheError)
+                .attach_printable("Failed to read cache directory"))
+        }
+    };
+    for kind_dir in kind_dirs {
+        let kind_dir = kind_dir
+            .change_context(SPError::CacheError)
+            .attach_printable("Failed to read cache directory")?
+            .path();
+        if !kind_dir.is_dir() {
+            continue;
+        }
+        let files = std::fs::read_dir(&kind_dir)
+            .change_context(SPError::CacheError)
+            .attach_printable_lazy(|| format!("Failed to read {}", kind_dir.display()))?;
+        for file in files {
+            let path = file
+                .change_context(SPError::CacheError)
+                .attach_printable_lazy(|| format!("Failed to read {}", kind_dir.display()))?
+                .path();
+            let Some(hash) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(HashCode::from_file_name)
+            else {
+                continue;
+            };
+            verification.checked += 1;
+            let dl = MappingDownload {
+                kind: kind_dir.file_name().unwrap().to_string_lossy().into_owned(),
+                source: path.display().to_string(),
+                hash,
+                size: None,
+            };
+            let compressed = path.extension().is_some_and(|e| e == "zst");
+            let result = File::open(&path)
+                .change_context(SPError::CacheError)
+                .and_then(|file| {
+                    if compressed {
+                        let decoder = zstd::Decoder::new(&file)
+                            .change_context(SPError::CacheError)
+                            .attach_printable("Failed to start decompressing")?;
+                        dl.hash.verify(decoder)?;
+                    } else {
+                        dl.hash.verify(&file)?;
+                    }
+                    Ok(file)
+                });
+            match result {
+                Ok(file) => write_stamp(&path, &dl, &file),
+                Err(e) => {
+                    tracing::warn!("Corrupt cache file {}: {:?}", path.display(), e);
+                    if delete {
+                        delete_with_derived(&path)?;
+                    }
+                    verification.corrupt.push(path);
+                }
+            }
+        }
+    }
+    Ok(verification)
+}
+
+/// Delete a cache file along with its stamp and the files derived from it, which share the
+/// `<hash name>.<hash>` at the start of its name.
+fn delete_with_derived(cache_file: &Path) -> Result<(), Report<SPError>> {
+    let name = cache_file
+        .file_name()
+        .expect("cache file has a name")
+        .to_string_lossy();
+    let prefix = format!("{}.", name.split('.').take(2).join("."));
+    let dir = cache_file.parent().expect("cache file has a parent");
+    let files = std::fs::read_dir(dir)
+        .change_context(SPError::CacheError)
+        .attach_printable_lazy(|| format!("Failed to read {}", dir.display()))?;
+    for file in files {
+        let path = file
+            .change_context(SPError::CacheError)
+            .attach_printable_lazy(|| format!("Failed to read {}", dir.display()))?
+            .path();
+        if path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with(&prefix))
+        {
+            std::fs::remove_file(&path)
+                .change_context(SPError::CacheError)
+                .attach_printable_lazy(|| format!("Failed to delete {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
 fn validate_mappings(dl: &MappingDownload, mut output: &mut File) -> Result<(), Report<SPError>> {
     let size = output
         .metadata()
         .map(|m| m.len())
-        .change_context(SPError)
+        .change_context(SPError::CacheError)
         .attach_printable("Failed to get tempfile size")?;
     if let Some(expect_size) = dl.size {
         if size != expect_size {
-            return Err(Report::new(SPError).attach_printable(format!(
+            return Err(Report::new(SPError::CacheError).attach_printable(format!(
                 "Mappings file was {} bytes, expected {}",
                 size, expect_size
             )));
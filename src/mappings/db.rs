@@ -1,2 +1,3 @@
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct MappingsDb {}
@@ -0,0 +1,106 @@
+//! Explanations of how a method is mapped, for debugging ambiguous or suspicious mappings.
+
+use derive_more::Display;
+
+use crate::mappings::{BaseMapper, Descriptor, EitherMapper, MethodId};
+
+/// How a single mapper looked up a method.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum LookupHeuristic {
+    /// The method was found in its class.
+    #[display(fmt = "found in its class")]
+    Scoped,
+    /// The method wasn't in its class, but exactly one class has a method of that name. This
+    /// finds synthetic methods, which are often missing from their class' mappings.
+    #[display(fmt = "not in its class, but one class has a method of that name")]
+    UniqueName,
+    /// The method wasn't in its class, and several classes have methods of that name, so all of
+    /// them were rejected.
+    #[display(fmt = "not in its class, and several classes have methods of that name, rejected")]
+    AmbiguousName,
+    /// No class has a method of that name.
+    #[display(fmt = "not found")]
+    NotFound,
+}
+
+/// A method that a mapper looked up, and what it found.
+#[derive(Debug)]
+pub struct MethodLookup {
+    pub class: String,
+    pub name: String,
+    pub descriptor: Option<Descriptor>,
+    pub heuristic: LookupHeuristic,
+    /// The methods found, with their mapped class names. They are candidates unless the
+    /// heuristic rejected them.
+    pub found: Vec<(String, MethodId)>,
+}
+
+/// What one step of a mapper did with a method, see [`EitherMapper::explain_method`].
+#[derive(Debug)]
+pub struct StepExplanation {
+    /// The mapper of this step, e.g. `obf -> mojang for 1.20.1`.
+    pub mapper: String,
+    /// The candidates of the previous step that were looked up, or the original method.
+    pub lookups: Vec<MethodLookup>,
+}
+
+impl EitherMapper {
+    /// Explain how each step of this mapper maps a method, including the candidates that were
+    /// rejected.
+    pub fn explain_method(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> Vec<StepExplanation> {
+        let mappers = match self {
+            EitherMapper::Base(m) => std::slice::from_ref(m),
+            EitherMapper::Multi(m) => m.mappers.as_slice(),
+        };
+        let mut push_data = vec![(
+            from_class_name.to_string(),
+            name.to_string(),
+            descriptor.cloned(),
+        )];
+        let mut steps = Vec::with_capacity(mappers.len());
+        for mapper in mappers {
+            let lookups: Vec<_> = push_data
+                .drain(..)
+                .map(|(class, name, descriptor)| lookup(mapper, class, name, descriptor))
+                .collect();
+            for lookup in &lookups {
+                if lookup.heuristic == LookupHeuristic::AmbiguousName {
+                    continue;
+                }
+                push_data.extend(lookup.found.iter().map(|(class, id)| {
+                    (class.clone(), id.name.clone(), Some(id.descriptor.clone()))
+                }));
+            }
+            steps.push(StepExplanation {
+                mapper: mapper.to_string(),
+                lookups,
+            });
+        }
+        steps
+    }
+}
+
+fn lookup(
+    mapper: &BaseMapper,
+    class: String,
+    name: String,
+    descriptor: Option<Descriptor>,
+) -> MethodLookup {
+    let (heuristic, found) = mapper.lookup_method(&class, &name, descriptor.as_ref());
+    let found = found
+        .into_iter()
+        .map(|(class, id)| (class.to_string(), id.clone()))
+        .collect();
+    MethodLookup {
+        class,
+        name,
+        descriptor,
+        heuristic,
+        found,
+    }
+}
@@ -1,95 +1,266 @@
+use std::fs::File;
 use std::io::Read;
 
 use error_stack::{Report, ResultExt};
+use itertools::Itertools;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
 use zip::ZipArchive;
 
-use crate::http::good_error_request;
-use crate::mappings::cache::{load_mappings, HashCode, MappingDownload};
-use crate::mappings::raw::{RawClassMapping, RawMethodMapping};
+use crate::http::{
+    good_error_head_request, good_error_request, good_error_request_if_changed,
+    good_error_request_json, is_not_found,
+};
+use crate::mappings::cache::{
+    allows_unverified, is_cached, load_mappings, read_derived, write_derived, HashCode,
+    MappingDownload,
+};
+use crate::mappings::meta;
+use crate::mappings::source::{Content, MappingSource, RawMappings};
 use crate::mappings::tiny::parse_tiny_v2;
-use crate::mappings::{raw, BaseMapper};
 use crate::names::NamesType;
+use crate::progress::TIMINGS_TARGET;
 use crate::SPError;
 
-pub fn load(version: String, obf_to_fabric: bool) -> Result<BaseMapper, Report<SPError>> {
-    let content = extract_mappings(&version)?;
+/// Fabric's intermediary mappings, from their Maven repository.
+#[derive(Debug)]
+pub struct FabricIntermediarySource;
 
-    let mappings = parse_tiny_v2(&content)?;
+impl MappingSource for FabricIntermediarySource {
+    fn name(&self) -> &str {
+        "fabric_intermediary"
+    }
 
-    // Sanity check that we got the mapping we expected.
-    if mappings.header.namespace_a != "official" || mappings.header.namespace_b != "intermediary" {
-        return Err(Report::new(SPError)
-            .attach_printable(format!("Invalid tiny mappings for {}", version))
-            .attach_printable(format!("Header: {:?}", mappings.header)));
+    fn primary_names(&self) -> NamesType {
+        NamesType::Obfuscated
     }
 
-    Ok(raw::convert_mappings(
-        NamesType::Obfuscated,
-        NamesType::FabricIntermediary,
-        version,
-        mappings.content.classes.into_iter().filter_map(|c| {
-            Some(RawClassMapping {
-                mapping: (
-                    c.mapping.primary_name,
-                    c.mapping
-                        .mapped_names
-                        .into_iter()
-                        .next()
-                        .expect("missing first mapped name")?,
-                ),
-                methods: c.methods.into_iter().filter_map(|m| {
-                    Some(RawMethodMapping {
-                        descriptor: m.primary_desc,
-                        mapping: (
-                            m.mapping.primary_name,
-                            m.mapping
-                                .mapped_names
-                                .into_iter()
-                                .next()
-                                .expect("missing first mapped name")?,
-                        ),
-                    })
-                }),
-            })
-        }),
-        !obf_to_fabric,
-    ))
+    fn secondary_names(&self) -> NamesType {
+        NamesType::FabricIntermediary
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        extract_mappings(version).map(Content::from)
+    }
+
+    fn location(&self, version: &str) -> Option<String> {
+        Some(artifact_url(version))
+    }
+
+    fn is_cached(&self, version: &str) -> bool {
+        meta::recorded_download(&artifact_url(version), KIND).is_some_and(|dl| is_cached(&dl))
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        parse_tiny_v2(content)?
+            .to_raw("official", "intermediary")
+            .attach_printable_lazy(|| format!("Invalid tiny mappings for {}", version))
+    }
+
+    fn has_version(&self, version: &str) -> Option<bool> {
+        match fetch_mappings_info(version) {
+            Ok(_) => Some(true),
+            Err(e) if *e.current_context() == SPError::UnknownVersion => Some(false),
+            Err(_) => None,
+        }
+    }
 }
 
-fn extract_mappings(version: &String) -> Result<String, Report<SPError>> {
+fn extract_mappings(version: &str) -> Result<String, Report<SPError>> {
     let dl = fetch_mappings_info(version)?;
-    let mappings = load_mappings(dl)?;
-    let mut zip = ZipArchive::new(mappings)
-        .change_context(SPError)
+    // Skip decompressing the JAR if it was done before
+    if let Some(content) = read_derived(&dl, "tiny") {
+        return Ok(content);
+    }
+    let content = read_mappings_jar(load_mappings(dl.clone())?, version)?;
+    write_derived(&dl, "tiny", &content);
+    Ok(content)
+}
+
+/// Read the tiny mappings out of an intermediary JAR.
+pub fn read_mappings_jar(jar: File, version: &str) -> Result<String, Report<SPError>> {
+    let mut zip = ZipArchive::new(jar)
+        .change_context(SPError::CacheError)
         .attach_printable_lazy(|| format!("Failed to open mappings JAR for {}", version))?;
     let mut tiny_file = zip
         .by_name("mappings/mappings.tiny")
-        .change_context(SPError)
+        .change_context(SPError::CacheError)
         .attach_printable_lazy(|| format!("Failed to get mappings.tiny for {}", version))?;
     let mut content = String::new();
     tiny_file
         .read_to_string(&mut content)
-        .change_context(SPError)
+        .change_context(SPError::CacheError)
         .attach_printable_lazy(|| format!("Failed to read mappings.tiny for {}", version))?;
     Ok(content)
 }
 
+/// The kind of the downloads, which names their directory in the cache.
+const KIND: &str = "fabric_intermediary";
+
 const BASE_URL: &str = "https://maven.fabricmc.net/net/fabricmc/intermediary";
 
 fn artifact_url(version: &str) -> String {
     format!("{}/{}/intermediary-{}-v2.jar", BASE_URL, version, version)
 }
 
+/// The checksum files that Maven may have next to an artifact, best first. Some old artifacts lack
+/// the better ones.
+const CHECKSUMS: [&str; 4] = ["sha512", "sha256", "sha1", "md5"];
+
+/// Fabric's list of the versions it has intermediary for.
+const META_URL: &str = "https://meta.fabricmc.net/v2/versions/intermediary";
+
+#[derive(Deserialize, Debug)]
+struct MetaVersion {
+    version: String,
+}
+
+/// Find the version that Fabric publishes the intermediary of the launcher's `version` id under,
+/// if it normalized the name, e.g. for some snapshots. Names are compared ignoring case and
+/// punctuation.
+fn maven_version(version: &str) -> Result<Option<String>, Report<SPError>> {
+    fn normalize(version: &str) -> String {
+        version
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+    let versions: Vec<MetaVersion> = good_error_request_json(META_URL)?;
+    let normalized = normalize(version);
+    Ok(versions
+        .into_iter()
+        .filter(|v| v.version != version && normalize(&v.version) == normalized)
+        .map(|v| v.version)
+        .exactly_one()
+        .ok())
+}
+
 fn fetch_mappings_info(version: &str) -> Result<MappingDownload, Report<SPError>> {
+    let key = artifact_url(version);
+    if let Some(dl) = meta::fresh_download(&key, KIND) {
+        return Ok(dl);
+    }
+    let _span = tracing::info_span!(target: TIMINGS_TARGET, "fetch_manifest").entered();
+    if let Some(dl) = revalidate(&key) {
+        return Ok(dl);
+    }
+    let (dl, etag) = look_up_mappings_info(version)?;
+    meta::record(&key, &dl, etag.as_deref());
+    Ok(dl)
+}
+
+/// Check the stale download recorded for the `key` against the `ETag` of its checksum, giving it
+/// if the checksum is unchanged.
+fn revalidate(key: &str) -> Option<MappingDownload> {
+    let entry = meta::lookup(key)?;
+    let etag = entry.etag.as_deref()?;
+    let dl = entry.download(KIND)?;
+    let checksum_url = format!("{}.{}", dl.source, dl.hash.name());
+    match good_error_request_if_changed(&checksum_url, Some(etag)) {
+        Ok(None) => {
+            tracing::debug!("Recorded metadata for {} is unchanged", key);
+            meta::touch(key);
+            Some(dl)
+        }
+        Ok(Some(_)) => None,
+        Err(e) => {
+            tracing::debug!("Failed to check recorded metadata for {}: {:?}", key, e);
+            None
+        }
+    }
+}
+
+/// Look up the download of the mappings, and the `ETag` of the checksum it was verified with.
+fn look_up_mappings_info(
+    version: &str,
+) -> Result<(MappingDownload, Option<String>), Report<SPError>> {
+    match fetch_artifact_info(version) {
+        Err(e) if *e.current_context() == SPError::UnknownVersion => match maven_version(version) {
+            Ok(Some(maven_version)) => {
+                tracing::debug!("Fabric publishes {} as {}", version, maven_version);
+                fetch_artifact_info(&maven_version)
+            }
+            Ok(None) => Err(e),
+            Err(meta_error) => {
+                tracing::debug!(
+                    "Failed to look up {} on Fabric meta: {:?}",
+                    version,
+                    meta_error
+                );
+                Err(e)
+            }
+        },
+        result => result,
+    }
+}
+
+fn fetch_artifact_info(
+    version: &str,
+) -> Result<(MappingDownload, Option<String>), Report<SPError>> {
     let url = artifact_url(version);
-    let sha512 = good_error_request(&format!("{}.sha512", url))?
-        .text()
-        .change_context(SPError)
-        .attach_printable_lazy(|| format!("Failed to get sha512 for {}", version))?;
-    Ok(MappingDownload {
-        kind: "fabric_intermediary".into(),
+    for extension in CHECKSUMS {
+        let response = match good_error_request(&format!("{}.{}", url, extension)) {
+            Ok(response) => response,
+            Err(e) if is_not_found(&e) => {
+                tracing::debug!("No {} for {}", extension, url);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let etag = response.header("ETag").map(str::to_string);
+        let hash = response
+            .text()
+            .change_context(SPError::NetworkError)
+            .attach_printable_lazy(|| format!("Failed to get {} for {}", extension, version))?;
+        // Some checksum files also name the artifact
+        let hash = hash
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let dl = MappingDownload {
+            kind: KIND.into(),
+            source: url,
+            hash: HashCode::from_name(extension, hash).expect("checksum is a known hash"),
+            size: None,
+        };
+        return Ok((dl, etag));
+    }
+
+    let response = good_error_head_request(&url).map_err(|e| {
+        // Maven has no intermediary for versions that don't exist, or that Fabric doesn't support
+        if is_not_found(&e) {
+            e.change_context(SPError::UnknownVersion)
+                .attach_printable(format!("No intermediary mappings for {}", version))
+        } else {
+            e
+        }
+    })?;
+    if !allows_unverified() {
+        return Err(Report::new(SPError::NetworkError).attach_printable(format!(
+            "No checksum to verify {} with, allow unverified mappings to use it anyway",
+            url
+        )));
+    }
+    // `content_length` is of the (empty) body of a HEAD response, so read the header instead
+    let size = response
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok());
+    tracing::warn!(
+        "Using unverified intermediary mappings for {}, {}",
+        version,
+        if size.is_some() {
+            "only checking their size"
+        } else {
+            "without any checks"
+        }
+    );
+    let dl = MappingDownload {
+        kind: KIND.into(),
+        hash: HashCode::Unverified(format!("{:x}", Sha1::digest(&url))),
         source: url,
-        hash: HashCode::Sha512(sha512),
-        size: None,
-    })
+        size,
+    };
+    Ok((dl, None))
 }
@@ -0,0 +1,195 @@
+//! Descriptor hints, which pick the overloads of ambiguous methods that the user knows frames are
+//! in, e.g. from decompiled sources.
+//!
+//! [`WithHints`] narrows the candidates of a hinted method to those with the hinted parameters,
+//! and maps everything else with the usual mapper.
+
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use derive_more::Display;
+use error_stack::{Context, Report};
+use itertools::Itertools;
+
+use crate::mappings::{ClassMapper, Confidence, Descriptor, MethodId, MethodMapper, Type};
+
+#[derive(Debug, Display)]
+pub struct DescriptorHintFromStrError;
+
+impl Context for DescriptorHintFromStrError {}
+
+/// The parameters of a method in the names it is mapped from, e.g.
+/// `abc.d.e=(int,java.lang.String)` for the method `e` of the class `abc.d`.
+///
+/// The parameter types are in the names the method is mapped to. Classes may be given by their
+/// simple name, e.g. `String`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorHint {
+    pub class: String,
+    pub method: String,
+    pub params: Vec<Type>,
+}
+
+impl DescriptorHint {
+    /// Check if a candidate with the `descriptor` has the hinted parameters.
+    fn matches(&self, descriptor: &Descriptor) -> bool {
+        self.params.len() == descriptor.params.len()
+            && self
+                .params
+                .iter()
+                .zip(&descriptor.params)
+                .all(|(hint, param)| type_matches(hint, param))
+    }
+}
+
+/// Check if the hinted type is the type `actual`, allowing the simple name of a class.
+fn type_matches(hint: &Type, actual: &Type) -> bool {
+    match (hint, actual) {
+        (Type::Object(hint), Type::Object(actual)) => {
+            hint == actual || (!hint.contains('.') && actual.rsplit('.').next() == Some(hint))
+        }
+        (Type::Array(hint), Type::Array(actual)) => type_matches(hint, actual),
+        (hint, actual) => hint == actual,
+    }
+}
+
+impl Display for DescriptorHint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}=({})",
+            self.class,
+            self.method,
+            self.params.iter().join(",")
+        )
+    }
+}
+
+impl FromStr for DescriptorHint {
+    type Err = Report<DescriptorHintFromStrError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Report::new(DescriptorHintFromStrError).attach_printable(format!(
+                "Invalid hint {}, expected e.g. abc.d.e=(int,java.lang.String)",
+                s
+            ))
+        };
+        let (method, params) = s.split_once('=').ok_or_else(invalid)?;
+        let (class, method) = method.trim().rsplit_once('.').ok_or_else(invalid)?;
+        let params = params
+            .trim()
+            .strip_prefix('(')
+            .and_then(|p| p.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+        if class.is_empty() || method.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            class: class.to_string(),
+            method: method.to_string(),
+            params: params
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|p| Type::from_source_name(p.to_string()))
+                .collect(),
+        })
+    }
+}
+
+/// A mapper that narrows the candidates of the hinted methods with their [`DescriptorHint`]s,
+/// using the base mapper for everything. A hint that matches none of the candidates is ignored.
+#[derive(Debug)]
+pub struct WithHints<M> {
+    base: M,
+    hints: Vec<DescriptorHint>,
+}
+
+impl<M> WithHints<M> {
+    pub fn new(base: M, hints: Vec<DescriptorHint>) -> Self {
+        Self { base, hints }
+    }
+
+    pub fn base(&self) -> &M {
+        &self.base
+    }
+
+    /// The hint for the method `name` of the class `from_class_name`, if any.
+    fn hint_for(&self, from_class_name: &str, name: &str) -> Option<&DescriptorHint> {
+        self.hints
+            .iter()
+            .find(|h| h.class == from_class_name && h.method == name)
+    }
+}
+
+impl<M: Display> Display for WithHints<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.base, f)?;
+        if !self.hints.is_empty() {
+            write!(f, " with {} descriptor hints", self.hints.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: ClassMapper> ClassMapper for WithHints<M> {
+    fn map_class(&self, name: &str) -> Option<&str> {
+        self.base.map_class(name)
+    }
+}
+
+impl<M: MethodMapper> MethodMapper for WithHints<M> {
+    fn map_method(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> Vec<(&str, &MethodId)> {
+        self.map_method_with_confidence(from_class_name, name, descriptor)
+            .0
+    }
+
+    fn map_method_with_confidence(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (Vec<(&str, &MethodId)>, Confidence) {
+        let (found, confidence) =
+            self.base
+                .map_method_with_confidence(from_class_name, name, descriptor);
+        // A known descriptor is better than a hint
+        let Some(hint) = self
+            .hint_for(from_class_name, name)
+            .filter(|_| descriptor.is_none())
+        else {
+            return (found, confidence);
+        };
+        let hinted = found
+            .iter()
+            .filter(|(_, id)| hint.matches(&id.descriptor))
+            .copied()
+            .collect::<Vec<_>>();
+        if hinted.is_empty() {
+            if !found.is_empty() {
+                tracing::warn!("Hint {} matches none of the candidates, ignoring it", hint);
+            }
+            return (found, confidence);
+        }
+        // Methods that weren't found in their class are still a guess
+        let confidence = match confidence {
+            Confidence::Low => Confidence::Low,
+            Confidence::Medium | Confidence::High => Confidence::High,
+        };
+        (hinted, confidence)
+    }
+
+    fn map_field(&self, from_class_name: Option<&str>, name: &str) -> Vec<(&str, &str)> {
+        self.base.map_field(from_class_name, name)
+    }
+
+    fn provenance(&self, from_class_name: &str) -> String {
+        self.base.provenance(from_class_name)
+    }
+}
@@ -0,0 +1,167 @@
+//! Sources for the mappings that Fabric Loom and Gradle already downloaded, so that development
+//! machines don't need the network.
+//!
+//! The Gradle user home is `$GRADLE_USER_HOME`, or `~/.gradle` if that isn't set. It is ignored
+//! while [refreshing](super::cache::refresh_cache) the cache.
+
+use std::path::PathBuf;
+
+use directories::BaseDirs;
+use error_stack::{Report, ResultExt};
+
+use crate::mappings::cache::is_refreshing;
+#[cfg(feature = "fabric")]
+use crate::mappings::fabric_intermediary::{read_mappings_jar, FabricIntermediarySource};
+#[cfg(feature = "mojang")]
+use crate::mappings::mojang::MojangSource;
+use crate::mappings::source::{Content, MappingSource, RawMappings};
+use crate::names::NamesType;
+use crate::SPError;
+
+fn gradle_home() -> Option<PathBuf> {
+    if is_refreshing() {
+        return None;
+    }
+    match std::env::var_os("GRADLE_USER_HOME") {
+        Some(home) => Some(home.into()),
+        None => BaseDirs::new().map(|dirs| dirs.home_dir().join(".gradle")),
+    }
+}
+
+/// Loom's cache for the `version`, where it keeps the Minecraft JARs and their mappings.
+fn loom_version_dir(version: &str) -> Option<PathBuf> {
+    Some(gradle_home()?.join("caches/fabric-loom").join(version))
+}
+
+fn map_file(path: PathBuf, version: &str) -> Result<Content, Report<SPError>> {
+    tracing::debug!("Using mappings from the Gradle cache at {}", path.display());
+    std::fs::File::open(&path)
+        .change_context(SPError::CacheError)
+        .and_then(|file| Content::map_file(&file).change_context(SPError::CacheError))
+        .attach_printable_lazy(|| format!("Failed to read {} for {}", path.display(), version))
+}
+
+fn not_cached(version: &str) -> Report<SPError> {
+    Report::new(SPError::UnknownVersion)
+        .attach_printable(format!("No mappings for {} in the Gradle cache", version))
+}
+
+/// Intermediary mappings from Loom's cache, or the Gradle dependency cache.
+#[cfg(feature = "fabric")]
+#[derive(Debug)]
+pub struct LoomIntermediarySource;
+
+#[cfg(feature = "fabric")]
+impl LoomIntermediarySource {
+    fn find_tiny(version: &str) -> Option<PathBuf> {
+        loom_version_dir(version)
+            .map(|dir| dir.join("intermediary-v2.tiny"))
+            .filter(|tiny| tiny.is_file())
+    }
+
+    fn find_jar(version: &str) -> Option<PathBuf> {
+        let artifact_dir = gradle_home()?
+            .join("caches/modules-2/files-2.1/net.fabricmc/intermediary")
+            .join(version);
+        let jar_name = format!("intermediary-{}-v2.jar", version);
+        // Each copy of the artifact is in a directory named after its hash
+        std::fs::read_dir(artifact_dir)
+            .ok()?
+            .filter_map(|entry| Some(entry.ok()?.path().join(&jar_name)))
+            .find(|jar| jar.is_file())
+    }
+}
+
+#[cfg(feature = "fabric")]
+impl MappingSource for LoomIntermediarySource {
+    fn name(&self) -> &str {
+        "loom_intermediary"
+    }
+
+    fn primary_names(&self) -> NamesType {
+        FabricIntermediarySource.primary_names()
+    }
+
+    fn secondary_names(&self) -> NamesType {
+        FabricIntermediarySource.secondary_names()
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        if let Some(tiny) = Self::find_tiny(version) {
+            return map_file(tiny, version);
+        }
+        let jar = Self::find_jar(version).ok_or_else(|| not_cached(version))?;
+        tracing::debug!("Using mappings from the Gradle cache at {}", jar.display());
+        let file = std::fs::File::open(&jar)
+            .change_context(SPError::CacheError)
+            .attach_printable_lazy(|| format!("Failed to open {}", jar.display()))?;
+        read_mappings_jar(file, version).map(Content::from)
+    }
+
+    fn location(&self, version: &str) -> Option<String> {
+        Self::find_tiny(version)
+            .or_else(|| Self::find_jar(version))
+            .or_else(|| loom_version_dir(version).map(|dir| dir.join("intermediary-v2.tiny")))
+            .map(|path| path.display().to_string())
+    }
+
+    fn is_cached(&self, version: &str) -> bool {
+        Self::find_tiny(version).is_some() || Self::find_jar(version).is_some()
+    }
+
+    fn has_version(&self, version: &str) -> Option<bool> {
+        Some(self.is_cached(version))
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        FabricIntermediarySource.parse(version, content)
+    }
+}
+
+#[cfg(feature = "mojang")]
+fn find_client_mappings(version: &str) -> Option<PathBuf> {
+    loom_version_dir(version)
+        .map(|dir| dir.join("client.txt"))
+        .filter(|mappings| mappings.is_file())
+}
+
+/// Mojang's mappings from Loom's cache, where projects using them keep a copy.
+#[cfg(feature = "mojang")]
+#[derive(Debug)]
+pub struct LoomMojangSource;
+
+#[cfg(feature = "mojang")]
+impl MappingSource for LoomMojangSource {
+    fn name(&self) -> &str {
+        "loom_mojang"
+    }
+
+    fn primary_names(&self) -> NamesType {
+        MojangSource.primary_names()
+    }
+
+    fn secondary_names(&self) -> NamesType {
+        MojangSource.secondary_names()
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        let mappings = find_client_mappings(version).ok_or_else(|| not_cached(version))?;
+        map_file(mappings, version)
+    }
+
+    fn location(&self, version: &str) -> Option<String> {
+        loom_version_dir(version).map(|dir| dir.join("client.txt").display().to_string())
+    }
+
+    fn is_cached(&self, version: &str) -> bool {
+        find_client_mappings(version).is_some()
+    }
+
+    fn has_version(&self, version: &str) -> Option<bool> {
+        Some(self.is_cached(version))
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        MojangSource.parse(version, content)
+    }
+}
@@ -0,0 +1,137 @@
+//! The metadata of the mappings artifacts that sources looked up, kept in `meta.json` in the cache
+//! directory. A source that looked up where to download a version's mappings recently can use the
+//! recorded download without any network access. Once it is stale, the source can check it again
+//! with the recorded `ETag`, so the metadata is only downloaded again if it changed.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::mappings::cache::{cache_dir, is_refreshing, HashCode, MappingDownload};
+
+/// How long a recorded download is used without checking it again.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+static ENTRIES: Lazy<Mutex<HashMap<String, MetaEntry>>> =
+    Lazy::new(|| Mutex::new(read_entries().unwrap_or_default()));
+
+/// What was looked up for an artifact, keyed by the URL it was looked up with, e.g. that of its
+/// checksum.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(super) struct MetaEntry {
+    source: String,
+    hash_name: String,
+    hash: String,
+    size: Option<u64>,
+    /// The `ETag` of the response the hash came from, if the server gave one.
+    pub(super) etag: Option<String>,
+    /// When the entry was last checked, in seconds since the Unix epoch.
+    checked: u64,
+}
+
+impl MetaEntry {
+    /// The download that was recorded, if it is still usable.
+    pub(super) fn download(&self, kind: &str) -> Option<MappingDownload> {
+        Some(MappingDownload {
+            kind: kind.to_string(),
+            source: self.source.clone(),
+            hash: HashCode::from_name(&self.hash_name, self.hash.clone())?,
+            size: self.size,
+        })
+    }
+
+    fn is_fresh(&self) -> bool {
+        now().saturating_sub(self.checked) < MAX_AGE.as_secs()
+    }
+}
+
+fn meta_path() -> PathBuf {
+    cache_dir().join("meta.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn read_entries() -> Option<HashMap<String, MetaEntry>> {
+    let content = std::fs::read(meta_path()).ok()?;
+    serde_json::from_slice(&content)
+        .map_err(|e| tracing::debug!("Ignoring unreadable {}: {}", meta_path().display(), e))
+        .ok()
+}
+
+/// The entry recorded for the `url`, fresh or not. There is none while refreshing the cache.
+pub(super) fn lookup(url: &str) -> Option<MetaEntry> {
+    if is_refreshing() {
+        return None;
+    }
+    ENTRIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(url)
+        .cloned()
+}
+
+/// The download recorded for the `url`, if it was checked recently enough to use it as-is.
+pub(super) fn fresh_download(url: &str, kind: &str) -> Option<MappingDownload> {
+    let dl = lookup(url).filter(MetaEntry::is_fresh)?.download(kind)?;
+    tracing::debug!("Using recorded metadata for {}", url);
+    Some(dl)
+}
+
+/// The download recorded for the `url`, fresh or not, e.g. to find its cached mappings without
+/// network access.
+pub(super) fn recorded_download(url: &str, kind: &str) -> Option<MappingDownload> {
+    lookup(url)?.download(kind)
+}
+
+/// Record the download that was looked up for the `url`, and the `etag` of the response it came
+/// from. This is only an optimization, so failures to save it are only logged.
+pub(super) fn record(url: &str, dl: &MappingDownload, etag: Option<&str>) {
+    let entry = MetaEntry {
+        source: dl.source.clone(),
+        hash_name: dl.hash.name().to_string(),
+        hash: dl.hash.value().to_string(),
+        size: dl.size,
+        etag: etag.map(str::to_string),
+        checked: now(),
+    };
+    let mut entries = ENTRIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    entries.insert(url.to_string(), entry);
+    write_entries(&entries);
+}
+
+/// Record that the entry for the `url` was checked and is unchanged.
+pub(super) fn touch(url: &str) {
+    let mut entries = ENTRIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(entry) = entries.get_mut(url) {
+        entry.checked = now();
+        write_entries(&entries);
+    }
+}
+
+fn write_entries(entries: &HashMap<String, MetaEntry>) {
+    let path = meta_path();
+    // Write to a temporary file first, so a partial write is never read back
+    let result = std::fs::create_dir_all(cache_dir())
+        .and_then(|()| tempfile::NamedTempFile::new_in(cache_dir()))
+        .and_then(|mut file| {
+            file.write_all(&serde_json::to_vec(entries)?)?;
+            file.persist(&path).map_err(|e| e.error)?;
+            Ok(())
+        });
+    if let Err(e) = result {
+        tracing::debug!("Failed to save {}: {}", path.display(), e);
+    }
+}
@@ -0,0 +1,175 @@
+//! Mappings for mods that obfuscate their own classes, from the ProGuard map they ship, e.g. as
+//! `META-INF/mappings.txt` in their jar.
+//!
+//! [`WithModMappings`] maps the classes of such mods with their own mappings and everything else
+//! with the usual mapper, so a trace through both is mapped in one pass.
+
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+
+use error_stack::{Report, ResultExt};
+use itertools::Itertools;
+use zip::ZipArchive;
+
+use crate::mappings::proguard::{parse_proguard, PGMappings};
+use crate::mappings::raw::{flip_mappings, index_mappings};
+use crate::mappings::{
+    extract_field, extract_method, ClassMapper, Confidence, Descriptor, Mappings, MethodId,
+    MethodMapper,
+};
+use crate::SPError;
+
+/// The mappings of one mod, from its obfuscated names to the names it was written with.
+#[derive(Debug)]
+pub struct ModMappings {
+    mod_id: String,
+    mappings: Mappings,
+}
+
+impl ModMappings {
+    /// Index the ProGuard mappings of the mod `mod_id`.
+    pub fn from_proguard(mod_id: impl Into<String>, mappings: PGMappings) -> Self {
+        Self {
+            mod_id: mod_id.into(),
+            mappings: index_mappings(flip_mappings(mappings.into_raw())),
+        }
+    }
+
+    /// Load the ProGuard mappings of the mod `mod_id` from a file, or from a file in a jar given
+    /// as `jar:<path>!/<entry>`, e.g. `jar:mods/example.jar!/META-INF/mappings.txt`.
+    pub fn load(mod_id: impl Into<String>, location: &str) -> Result<Self, Report<SPError>> {
+        let mod_id = mod_id.into();
+        let content = read_location(location)
+            .attach_printable_lazy(|| format!("Failed to read mappings of mod {}", mod_id))?;
+        let mappings = parse_proguard(&content)
+            .attach_printable_lazy(|| format!("Invalid mappings of mod {}", mod_id))?;
+        Ok(Self::from_proguard(mod_id, mappings))
+    }
+
+    pub fn mod_id(&self) -> &str {
+        &self.mod_id
+    }
+}
+
+fn read_location(location: &str) -> Result<String, Report<SPError>> {
+    let Some((jar, entry)) = location
+        .strip_prefix("jar:")
+        .and_then(|l| l.rsplit_once("!/"))
+    else {
+        return std::fs::read_to_string(location)
+            .change_context(SPError::IoError)
+            .attach_printable_lazy(|| format!("Failed to read {}", location));
+    };
+    let mut zip = File::open(jar)
+        .change_context(SPError::IoError)
+        .and_then(|f| ZipArchive::new(f).change_context(SPError::IoError))
+        .attach_printable_lazy(|| format!("Failed to open {}", jar))?;
+    let mut content = String::new();
+    zip.by_name(entry)
+        .change_context(SPError::IoError)
+        .and_then(|mut f| {
+            f.read_to_string(&mut content)
+                .change_context(SPError::IoError)
+        })
+        .attach_printable_lazy(|| format!("Failed to read {} in {}", entry, jar))?;
+    Ok(content)
+}
+
+/// Maps the classes of mods with their [`ModMappings`], and everything else with the `base`
+/// mapper. A class in the mappings of a mod is mapped with them even if the base mapper knows a
+/// class of the same name.
+#[derive(Debug)]
+pub struct WithModMappings<M> {
+    base: M,
+    mods: Vec<ModMappings>,
+}
+
+impl<M> WithModMappings<M> {
+    pub fn new(base: M, mods: Vec<ModMappings>) -> Self {
+        Self { base, mods }
+    }
+
+    pub fn base(&self) -> &M {
+        &self.base
+    }
+
+    /// The mappings of the mod with the class `name`, if any.
+    fn mod_for(&self, name: &str) -> Option<&ModMappings> {
+        self.mods
+            .iter()
+            .find(|m| m.mappings.classes.contains_key(name))
+    }
+}
+
+impl<M: Display> Display for WithModMappings<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.base, f)?;
+        if !self.mods.is_empty() {
+            write!(
+                f,
+                " with mappings of {}",
+                self.mods.iter().map(|m| &m.mod_id).join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: ClassMapper> ClassMapper for WithModMappings<M> {
+    fn map_class(&self, name: &str) -> Option<&str> {
+        match self.mod_for(name) {
+            Some(m) => Some(m.mappings.classes[name].to_name.as_str()),
+            None => self.base.map_class(name),
+        }
+    }
+}
+
+impl<M: MethodMapper> MethodMapper for WithModMappings<M> {
+    fn map_method(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> Vec<(&str, &MethodId)> {
+        match self.mod_for(from_class_name) {
+            Some(m) => extract_method(name, descriptor, &m.mappings.classes[from_class_name]),
+            None => self.base.map_method(from_class_name, name, descriptor),
+        }
+    }
+
+    fn map_method_with_confidence(
+        &self,
+        from_class_name: &str,
+        name: &str,
+        descriptor: Option<&Descriptor>,
+    ) -> (Vec<(&str, &MethodId)>, Confidence) {
+        if self.mod_for(from_class_name).is_none() {
+            return self
+                .base
+                .map_method_with_confidence(from_class_name, name, descriptor);
+        }
+        // Mods are only looked up in the class, so there are no weaker fallbacks
+        let found = self.map_method(from_class_name, name, descriptor);
+        let confidence = match (found.is_empty(), descriptor) {
+            (true, _) => Confidence::Low,
+            (false, Some(_)) => Confidence::High,
+            (false, None) => Confidence::Medium,
+        };
+        (found, confidence)
+    }
+
+    fn map_field(&self, from_class_name: Option<&str>, name: &str) -> Vec<(&str, &str)> {
+        match from_class_name.and_then(|c| Some((c, self.mod_for(c)?))) {
+            Some((class, m)) => extract_field(name, &m.mappings.classes[class]),
+            None => self.base.map_field(from_class_name, name),
+        }
+    }
+
+    fn provenance(&self, from_class_name: &str) -> String {
+        match self.mod_for(from_class_name) {
+            Some(m) => format!("extra mappings of {}", m.mod_id),
+            None => self.base.provenance(from_class_name),
+        }
+    }
+}
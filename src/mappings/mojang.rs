@@ -1,57 +1,247 @@
-use std::io::Read;
-
 use error_stack::{Report, ResultExt};
+use itertools::Itertools;
 
-use crate::http::good_error_request_json;
-use crate::mappings::cache::load_mappings;
+use crate::http::{good_error_request_if_changed, good_error_request_json};
+use crate::mappings::cache::{is_cached, load_mappings, MappingDownload};
+use crate::mappings::meta;
 use crate::mappings::proguard::parse_proguard;
-use crate::mappings::raw::{RawClassMapping, RawMethodMapping};
-use crate::mappings::{raw, BaseMapper};
-use crate::mojang_api::{Download, VersionInfo, VersionManifest};
+use crate::mappings::source::{Content, MappingSource, RawMappings};
+use crate::mojang_api::{VersionInfo, VersionManifest};
 use crate::names::NamesType;
+use crate::progress::TIMINGS_TARGET;
 use crate::SPError;
 
-pub fn load(version: String, moj_to_obf: bool) -> Result<BaseMapper, Report<SPError>> {
-    let content = {
-        let dl = fetch_mappings_info(&version)?;
-        let mut mappings = load_mappings(dl.into())?;
-        let mut content = String::new();
-        mappings
-            .read_to_string(&mut content)
-            .change_context(SPError)
-            .attach_printable_lazy(|| format!("Failed to read mappings.tiny for {}", version))?;
-        content
+/// The kind of the downloads, which names their directory in the cache.
+const KIND: &str = "mojang";
+
+/// Mojang's official mappings, published with each version since 1.14.4.
+#[derive(Debug)]
+pub struct MojangSource;
+
+impl MappingSource for MojangSource {
+    fn name(&self) -> &str {
+        "mojang"
+    }
+
+    fn primary_names(&self) -> NamesType {
+        NamesType::Mojang
+    }
+
+    fn secondary_names(&self) -> NamesType {
+        NamesType::Obfuscated
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        let dl = fetch_mappings_info(version)?;
+        let mappings = load_mappings(dl)?;
+        Content::map_file(&mappings)
+            .change_context(SPError::CacheError)
+            .attach_printable_lazy(|| format!("Failed to read client mappings for {}", version))
+    }
+
+    fn parse(&self, _version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        Ok(parse_proguard(content)?.into_raw())
+    }
+
+    fn location(&self, version: &str) -> Option<String> {
+        // The download is only known once the manifest was fetched
+        Some(
+            meta::recorded_download(&manifest_key(version), KIND)
+                .map_or_else(|| MANIFEST_URL.to_string(), |dl| dl.source),
+        )
+    }
+
+    fn is_cached(&self, version: &str) -> bool {
+        meta::recorded_download(&manifest_key(version), KIND).is_some_and(|dl| is_cached(&dl))
+    }
+
+    fn has_version(&self, version: &str) -> Option<bool> {
+        match fetch_mappings_info(version) {
+            Ok(_) => Some(true),
+            Err(e) if *e.current_context() == SPError::UnknownVersion => Some(false),
+            Err(_) => None,
+        }
+    }
+}
+
+/// The ids of the `count` most recent releases, newest first.
+pub fn recent_releases(count: usize) -> Result<Vec<String>, Report<SPError>> {
+    let version_manifest = fetch_version_manifest()?;
+    Ok(version_manifest
+        .versions
+        .into_iter()
+        .filter(|v| v.kind == "release")
+        .take(count)
+        .map(|v| v.id)
+        .collect())
+}
+
+/// The ids of up to `count` versions on each side of `version` in Mojang's manifest, nearest
+/// first. If `version` is a release, only releases are given.
+pub fn neighboring_versions(version: &str, count: usize) -> Result<Vec<String>, Report<SPError>> {
+    let versions = fetch_version_manifest()?.versions;
+    let kind = versions
+        .iter()
+        .find(|v| v.id == version)
+        .map(|v| v.kind.clone())
+        .ok_or_else(|| {
+            Report::new(SPError::UnknownVersion)
+                .attach_printable(format!("No version id matched '{}'", version))
+        })?;
+    let versions = versions
+        .into_iter()
+        .filter(|v| kind != "release" || v.kind == "release")
+        .map(|v| v.id)
+        .collect::<Vec<_>>();
+    let position = versions
+        .iter()
+        .position(|id| id == version)
+        .expect("the version is of its own kind");
+    Ok((1..=count)
+        .flat_map(|distance| {
+            // The manifest is newest first, so newer versions come before
+            let newer = position.checked_sub(distance);
+            let older = Some(position + distance).filter(|&i| i < versions.len());
+            newer.into_iter().chain(older)
+        })
+        .map(|i| versions[i].clone())
+        .collect())
+}
+
+/// Expand the version ranges and patterns among the `patterns` into version ids, oldest first:
+/// - `a..b` gives every version from `a` to `b`, inclusive. If both are releases, it only gives
+///   releases.
+/// - Globs such as `1.19.*` give every release whose id matches. A trailing `.*` also matches the
+///   id without it, e.g. `1.19`.
+///
+/// Other ids are kept as they are, so Mojang's manifest is only fetched if there are ranges or
+/// patterns. Each version is only given once.
+pub fn expand_versions(patterns: &[String]) -> Result<Vec<String>, Report<SPError>> {
+    fn is_glob(pattern: &str) -> bool {
+        pattern.contains(['*', '?', '['])
+    }
+    if !patterns.iter().any(|p| p.contains("..") || is_glob(p)) {
+        return Ok(patterns.iter().unique().cloned().collect());
+    }
+    let mut versions = fetch_version_manifest()?.versions;
+    versions.reverse();
+    let mut expanded = Vec::new();
+    for pattern in patterns {
+        if let Some((start, end)) = pattern.split_once("..") {
+            let position = |id: &str| {
+                versions.iter().position(|v| v.id == id).ok_or_else(|| {
+                    Report::new(SPError::UnknownVersion)
+                        .attach_printable(format!("No version id matched '{}' in {}", id, pattern))
+                })
+            };
+            let (start, end) = (position(start)?, position(end)?);
+            if start > end {
+                return Err(Report::new(SPError::UnknownVersion)
+                    .attach_printable(format!("Range {} ends before it starts", pattern)));
+            }
+            let releases_only =
+                versions[start].kind == "release" && versions[end].kind == "release";
+            expanded.extend(
+                versions[start..=end]
+                    .iter()
+                    .filter(|v| !releases_only || v.kind == "release")
+                    .map(|v| v.id.clone()),
+            );
+        } else if is_glob(pattern) {
+            let glob = glob::Pattern::new(pattern)
+                .change_context(SPError::ParseError)
+                .attach_printable_lazy(|| format!("Invalid version pattern {}", pattern))?;
+            let bare = pattern.strip_suffix(".*");
+            let matched = versions
+                .iter()
+                .filter(|v| v.kind == "release")
+                .filter(|v| glob.matches(&v.id) || Some(v.id.as_str()) == bare)
+                .map(|v| v.id.clone())
+                .collect::<Vec<_>>();
+            if matched.is_empty() {
+                return Err(Report::new(SPError::UnknownVersion)
+                    .attach_printable(format!("No release matched '{}'", pattern)));
+            }
+            expanded.extend(matched);
+        } else {
+            expanded.push(pattern.clone());
+        }
+    }
+    Ok(expanded.into_iter().unique().collect())
+}
+
+const MANIFEST_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+
+fn fetch_version_manifest() -> Result<VersionManifest, Report<SPError>> {
+    let _span = tracing::info_span!(target: TIMINGS_TARGET, "fetch_manifest").entered();
+    good_error_request_json(MANIFEST_URL)
+}
+
+/// Like [`fetch_version_manifest`], but gives nothing if it still has the `etag`, and the `ETag`
+/// it has otherwise.
+fn fetch_version_manifest_if_changed(
+    etag: Option<&str>,
+) -> Result<Option<(VersionManifest, Option<String>)>, Report<SPError>> {
+    let _span = tracing::info_span!(target: TIMINGS_TARGET, "fetch_manifest").entered();
+    let Some(response) = good_error_request_if_changed(MANIFEST_URL, etag)? else {
+        return Ok(None);
     };
+    let etag = response.header("ETag").map(str::to_string);
+    let manifest = serde_json::from_reader(response.body)
+        .change_context(SPError::NetworkError)
+        .attach_printable_lazy(|| format!("Failed to parse JSON from {}", MANIFEST_URL))?;
+    Ok(Some((manifest, etag)))
+}
+
+/// The key of the download of the `version` in the [metadata](meta).
+fn manifest_key(version: &str) -> String {
+    format!("{}#{}", MANIFEST_URL, version)
+}
 
-    let mappings = parse_proguard(&content)?;
-
-    Ok(raw::convert_mappings(
-        NamesType::Mojang,
-        NamesType::Obfuscated,
-        version,
-        mappings.classes.into_iter().map(|c| RawClassMapping {
-            mapping: (c.mapping.primary_name, c.mapping.secondary_name),
-            methods: c.methods.into_iter().map(|m| RawMethodMapping {
-                descriptor: m.primary_descriptor,
-                mapping: (m.mapping.primary_name, m.mapping.secondary_name),
-            }),
-        }),
-        !moj_to_obf,
-    ))
-}
-
-fn fetch_mappings_info(version: &str) -> Result<Download, Report<SPError>> {
-    let version_manifest: VersionManifest =
-        good_error_request_json("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json")?;
+/// Look up the download of the mappings of the `version`. The download is recorded in the
+/// [metadata](meta) with the `ETag` of the manifest, since a version's mappings only change if the
+/// manifest does.
+fn fetch_mappings_info(version: &str) -> Result<MappingDownload, Report<SPError>> {
+    let key = manifest_key(version);
+    if let Some(dl) = meta::fresh_download(&key, KIND) {
+        return Ok(dl);
+    }
+    let entry = meta::lookup(&key);
+    let etag = entry.as_ref().and_then(|e| e.etag.as_deref());
+    let (version_manifest, etag) = match fetch_version_manifest_if_changed(etag)? {
+        Some(changed) => changed,
+        None => match entry.as_ref().and_then(|e| e.download(KIND)) {
+            Some(dl) => {
+                tracing::debug!("Recorded metadata for {} is unchanged", key);
+                meta::touch(&key);
+                return Ok(dl);
+            }
+            None => (fetch_version_manifest()?, None),
+        },
+    };
     let version = version_manifest
         .versions
         .into_iter()
         .find(|v| v.id == version)
         .ok_or_else(|| {
-            Report::new(SPError).attach_printable(format!("No version id matched '{}'", version))
+            Report::new(SPError::UnknownVersion)
+                .attach_printable(format!("No version id matched '{}'", version))
         })?;
 
-    let version_info: VersionInfo = good_error_request_json(&version.url)?;
-    let download = version_info.downloads.client_mappings;
-    Ok(download)
+    let version_info: VersionInfo = {
+        let _span = tracing::info_span!(target: TIMINGS_TARGET, "fetch_manifest").entered();
+        good_error_request_json(&version.url)?
+    };
+    let dl: MappingDownload = version_info
+        .downloads
+        .client_mappings
+        .ok_or_else(|| {
+            Report::new(SPError::UnknownVersion).attach_printable(format!(
+                "No Mojang mappings for {}, they are published since 1.14.4",
+                version.id
+            ))
+        })?
+        .into();
+    meta::record(&key, &dl, etag.as_deref());
+    Ok(dl)
 }
@@ -1,14 +1,17 @@
 use std::iter::once;
 use std::str;
 
-use chumsky::primitive::{empty, end, just};
+use chumsky::primitive::{end, filter, just};
 use chumsky::Parser;
 use error_stack::Report;
 
+use crate::mappings::source::{RawClassMapping, RawMappings, RawMethodMapping};
 use crate::mappings::{Descriptor, Type};
 use crate::parsing::{
-    eol, handle_errors, jname, jtype, parse_recovery_debuggable, u32_digits, CharParser,
+    eol, handle_errors, inline_whitespace, is_java_identifier_part, jtype,
+    parse_recovery_debuggable, u32_digits, CharParser,
 };
+use crate::progress::PROGRESS_TARGET;
 use crate::SPError;
 
 #[derive(Debug)]
@@ -16,13 +19,43 @@ pub struct PGMappings {
     pub classes: Vec<PGClass>,
 }
 
+impl PGMappings {
+    /// Convert to raw mappings from the primary names, e.g. Mojang's, to the secondary
+    /// (obfuscated) names.
+    pub fn into_raw(self) -> RawMappings {
+        self.classes
+            .into_iter()
+            .map(|c| RawClassMapping {
+                mapping: (c.mapping.primary_name, c.mapping.secondary_name),
+                methods: c
+                    .methods
+                    .into_iter()
+                    // The enclosing method has its own entry, and the inlined method isn't one of
+                    // the class
+                    .filter(|m| m.inlined_from.is_none())
+                    .map(|m| RawMethodMapping {
+                        descriptor: m.primary_descriptor,
+                        mapping: (m.mapping.primary_name, m.mapping.secondary_name),
+                    })
+                    .collect(),
+                fields: c
+                    .fields
+                    .into_iter()
+                    .map(|f| (f.primary_name, f.secondary_name))
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
 pub fn parse_proguard(input: &str) -> Result<PGMappings, Report<SPError>> {
+    tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "proguard");
     let res = parse_recovery_debuggable(proguard_mappings(), input);
     handle_errors(input, res, "Failed to parse proguard mappings")
 }
 
 fn proguard_mappings() -> impl CharParser<PGMappings> {
-    comment()
+    skipped_line()
         .repeated()
         .ignore_then(
             class_section()
@@ -32,9 +65,11 @@ fn proguard_mappings() -> impl CharParser<PGMappings> {
         .then_ignore(end())
 }
 
-fn comment() -> impl CharParser<()> {
-    just("#")
-        .ignore_then(eol().not().repeated())
+/// A comment or an empty line. R8 writes comments anywhere, indented with the members they are
+/// about, e.g. `      # {"id":"com.android.tools.r8.synthesized"}`.
+fn skipped_line() -> impl CharParser<()> {
+    inline_whitespace()
+        .ignore_then(just("#").ignore_then(eol().not().repeated()).or_not())
         .ignore_then(eol())
 }
 
@@ -48,20 +83,40 @@ pub struct PGMapping {
 pub struct PGClass {
     pub mapping: PGMapping,
     pub methods: Vec<PGMethod>,
-    // Ignoring fields as we don't need them. Might want them later for mapping NPEs.
-    // pub fields: HashMap<String, PGField>,
+    /// Field types are dropped, as fields are only looked up by name.
+    pub fields: Vec<PGMapping>,
+}
+
+enum PGMember {
+    Method(PGMethod),
+    Field(PGMapping),
 }
 
 fn class_section() -> impl CharParser<PGClass> {
     class_line()
         .then(
             field_line()
-                .map(|_| None)
-                .or(method_line().map(Some))
+                .map(|f| Some(PGMember::Field(f)))
+                .or(method_line().map(|m| Some(PGMember::Method(m))))
+                .or(skipped_line().map(|_| None))
                 .repeated()
-                .map(|methods| methods.into_iter().flatten().collect::<Vec<_>>()),
+                .flatten(),
         )
-        .map(|(mapping, methods)| PGClass { mapping, methods })
+        .map(|(mapping, members)| {
+            let mut methods = Vec::new();
+            let mut fields = Vec::new();
+            for member in members {
+                match member {
+                    PGMember::Method(m) => methods.push(m),
+                    PGMember::Field(f) => fields.push(f),
+                }
+            }
+            PGClass {
+                mapping,
+                methods,
+                fields,
+            }
+        })
 }
 
 fn class_line() -> impl CharParser<PGMapping> {
@@ -79,6 +134,9 @@ fn class_line() -> impl CharParser<PGMapping> {
 pub struct PGMethod {
     pub primary_descriptor: Descriptor,
     pub mapping: PGMapping,
+    /// The class of the method, if it is in another class and was inlined into the obfuscated
+    /// method, e.g. `Outer` for `Outer.bar`.
+    pub inlined_from: Option<String>,
 }
 
 fn method_line() -> impl CharParser<PGMethod> {
@@ -86,13 +144,16 @@ fn method_line() -> impl CharParser<PGMethod> {
         .ignore_then(line_data().then_ignore(just(":")).or_not())
         .ignore_then(jtype().labelled("return type").debug("return type"))
         .then_ignore(just(" "))
-        .then_ignore(
-            jtype()
-                .delimited_by(empty(), just("."))
-                .or_not()
-                .labelled("class name prefix"),
+        // Methods inlined from other classes are qualified by their class
+        .then(
+            member_name(true)
+                .map(|name| match name.rsplit_once('.') {
+                    Some((class, name)) => (Some(class.to_string()), name.to_string()),
+                    None => (None, name),
+                })
+                .labelled("original method name")
+                .debug("mname"),
         )
-        .then(jname().labelled("original method name").debug("mname"))
         .then(
             jtype()
                 .then(just(",").ignore_then(jtype()).debug("an arg").repeated())
@@ -103,41 +164,87 @@ fn method_line() -> impl CharParser<PGMethod> {
         )
         .then_ignore(just(":").then(line_data()).or_not().debug("line data 2"))
         .then_ignore(just(" -> "))
-        .then(jname().labelled("obf method name"))
-        .then_ignore(eol())
-        .map(|(((ret_type, primary_name), params), secondary_name)| {
-            let params = match params {
-                Some((first, rest)) => once(first)
-                    .chain(rest)
-                    .map(Type::from_source_name)
-                    .collect(),
-                None => Vec::new(),
-            };
-            let primary_descriptor = Descriptor {
-                params,
-                return_type: Type::from_source_name(ret_type),
-            };
-            let mapping = PGMapping {
-                primary_name,
-                secondary_name,
-            };
-            PGMethod {
-                primary_descriptor,
-                mapping,
-            }
-        })
+        .then(member_name(false).labelled("obf method name"))
+        .then_ignore(inline_whitespace().then(eol()))
+        .map(
+            |(((ret_type, (inlined_from, primary_name)), params), secondary_name)| {
+                let params = match params {
+                    Some((first, rest)) => once(first)
+                        .chain(rest)
+                        .map(Type::from_source_name)
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let primary_descriptor = Descriptor {
+                    params,
+                    return_type: Type::from_source_name(ret_type),
+                };
+                let mapping = PGMapping {
+                    primary_name,
+                    secondary_name,
+                };
+                PGMethod {
+                    primary_descriptor,
+                    mapping,
+                    inlined_from,
+                }
+            },
+        )
 }
 
-fn line_data() -> impl CharParser<(u32, u32)> {
-    u32_digits().then_ignore(just(":")).then(u32_digits())
+/// A line number or range. R8 writes a single line for methods that span one line.
+fn line_data() -> impl CharParser<(u32, Option<u32>)> {
+    u32_digits().then(just(":").ignore_then(u32_digits()).or_not())
 }
 
-fn field_line() -> impl CharParser<()> {
+fn field_line() -> impl CharParser<PGMapping> {
     just("    ")
         .ignore_then(jtype())
         .ignore_then(just(" "))
-        .ignore_then(jname())
-        .ignore_then(just(" -> "))
-        .ignore_then(jname())
-        .ignore_then(eol())
+        .ignore_then(member_name(false))
+        .then_ignore(just(" -> "))
+        .then(member_name(false))
+        .then_ignore(inline_whitespace())
+        .then_ignore(eol())
+        .map(|(primary_name, secondary_name)| PGMapping {
+            primary_name,
+            secondary_name,
+        })
+}
+
+/// A field or method name, optionally qualified by its class. Besides Java identifiers, this
+/// allows `<init>`, `<clinit>`, and the `-` of names mangled by the Kotlin compiler, e.g.
+/// `getValue-impl`.
+fn member_name(qualified: bool) -> impl CharParser<String> {
+    filter(move |&c| {
+        is_java_identifier_part(c) || c == '-' || c == '<' || c == '>' || (qualified && c == '.')
+    })
+    .repeated()
+    .at_least(1)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inlined_methods_are_not_candidates() {
+        let mappings = parse_proguard(
+            "net.minecraft.Foo -> a:\n\
+             \x20   1:5:void Outer.bar():10:14 -> b\n\
+             \x20   1:5:void tick():20 -> b\n\
+             \x20   int count -> c\n",
+        )
+        .unwrap();
+        let methods = &mappings.classes[0].methods;
+        assert_eq!(methods[0].inlined_from.as_deref(), Some("Outer"));
+        assert_eq!(methods[0].mapping.primary_name, "bar");
+        let raw = mappings.into_raw();
+        assert_eq!(raw[0].methods.len(), 1);
+        assert_eq!(
+            raw[0].methods[0].mapping,
+            ("tick".to_string(), "b".to_string())
+        );
+    }
 }
@@ -1,20 +1,61 @@
 use std::collections::HashMap;
 
+use crate::mappings::source::RawMappings;
 use crate::mappings::{
-    BaseMapper, ClassMapping, Descriptor, FnClassMapper, MapSelfOnlyClass, Mappings, MethodId,
+    BaseMapper, ClassMapper, ClassMapping, Descriptor, FnClassMapper, MapSelfOnlyClass, Mappings,
+    MethodId,
 };
 use crate::names::NamesType;
 
+/// A class mapping as given by a mappings file, before it is indexed.
+#[derive(Debug, PartialEq, Eq)]
 pub struct RawClassMapping<M> {
+    /// The `from` and `to` names of the class.
     pub mapping: (String, String),
+    /// The [`RawMethodMapping`]s of the class.
     pub methods: M,
+    /// The `from` and `to` names of the fields of the class.
+    pub fields: Vec<(String, String)>,
 }
 
+/// A method mapping as given by a mappings file.
+#[derive(Debug, PartialEq, Eq)]
 pub struct RawMethodMapping {
+    /// The descriptor of the method, in `from` names.
     pub descriptor: Descriptor,
+    /// The `from` and `to` names of the method.
     pub mapping: (String, String),
 }
 
+/// Swap the `from` and `to` names of raw mappings, re-expressing each method descriptor in the
+/// new `from` names. Flipping twice gives the original mappings.
+///
+/// Descriptor types that the mappings don't rename, e.g. `java.lang.String`, are kept as-is.
+pub fn flip_mappings(mappings: RawMappings) -> RawMappings {
+    let class_mappings = class_mapper(&mappings);
+    mappings
+        .into_iter()
+        .map(|class| RawClassMapping {
+            mapping: swap(class.mapping),
+            methods: class
+                .methods
+                .into_iter()
+                .map(|method| RawMethodMapping {
+                    descriptor: method.descriptor.map_self(&class_mappings),
+                    mapping: swap(method.mapping),
+                })
+                .collect(),
+            fields: class.fields.into_iter().map(swap).collect(),
+        })
+        .collect()
+}
+
+/// Index raw mappings from `primary_nt` to `secondary_nt` names, or the other way around if
+/// `should_flip` is set.
+///
+/// The `from` side of every [`MethodId`] in the result has its descriptor in the mapper's `from`
+/// names, and the `to` side in its `to` names. [`MultiMapper`](super::MultiMapper) relies on this
+/// to pass the mapped descriptor of one step to the next.
 #[tracing::instrument(skip(mappings), level = "debug")]
 pub fn convert_mappings<CM, M>(
     primary_nt: NamesType,
@@ -27,66 +68,167 @@ where
     CM: IntoIterator<Item = RawClassMapping<M>>,
     M: IntoIterator<Item = RawMethodMapping>,
 {
-    let mappings = mappings.into_iter();
-
-    let guess_size = mappings.size_hint().0.max(8);
-
-    let mut class_mappings = HashMap::with_capacity(guess_size);
-    let mut todo_classes = Vec::with_capacity(guess_size);
-    for class_mapping in mappings {
-        // fix inference
-        let class_mapping: RawClassMapping<M> = class_mapping;
-        class_mappings.insert(
-            class_mapping.mapping.0.clone(),
-            class_mapping.mapping.1.clone(),
-        );
-        todo_classes.push(class_mapping);
+    let mappings: RawMappings = mappings
+        .into_iter()
+        .map(|c| RawClassMapping {
+            mapping: c.mapping,
+            methods: c.methods.into_iter().collect(),
+            fields: c.fields,
+        })
+        .collect();
+    let (from, to) = if should_flip {
+        (secondary_nt, primary_nt)
+    } else {
+        (primary_nt, secondary_nt)
+    };
+    let mappings = if should_flip {
+        flip_mappings(mappings)
+    } else {
+        mappings
+    };
+    let mappings = index_mappings(mappings);
+    tracing::trace!("Converted mappings: {:#?}", mappings);
+    BaseMapper {
+        from,
+        to,
+        version,
+        mappings,
+        origin: None,
     }
-    let class_mappings =
-        FnClassMapper::new(class_mappings, |cm, name| cm.get(name).map(|s| s.as_str()));
-    let result = todo_classes
+}
+
+/// Index raw mappings by their `from` names, like [`convert_mappings`] without flipping them.
+pub fn index_mappings(mappings: RawMappings) -> Mappings {
+    let class_mappings = class_mapper(&mappings);
+    let classes = mappings
         .into_iter()
         .map(|class| {
-            let (from, to) = do_flip(should_flip, class.mapping);
             let methods = class
                 .methods
                 .into_iter()
-                .map(|method: RawMethodMapping| {
-                    let first_id = MethodId {
+                .map(|method| {
+                    let from_id = MethodId {
                         name: method.mapping.0,
                         descriptor: method.descriptor.clone(),
                     };
-                    let second_id = MethodId {
+                    let to_id = MethodId {
                         name: method.mapping.1,
                         descriptor: method.descriptor.map_self(&class_mappings),
                     };
-                    do_flip(should_flip, (first_id, second_id))
+                    (from_id, to_id)
                 })
                 .collect();
             (
-                from,
+                class.mapping.0,
                 ClassMapping {
-                    to_name: to,
+                    to_name: class.mapping.1,
                     methods,
+                    fields: class.fields,
                 },
             )
         })
         .collect();
-    let (from, to) = do_flip(should_flip, (primary_nt, secondary_nt));
-    let mappings = Mappings { classes: result };
-    tracing::trace!("Converted mappings: {:#?}", mappings);
-    BaseMapper {
-        from,
-        to,
-        version,
-        mappings,
-    }
+    Mappings { classes }
 }
 
-fn do_flip<T>(should_flip: bool, (first, second): (T, T)) -> (T, T) {
-    if should_flip {
-        (second, first)
-    } else {
-        (first, second)
+/// Map class names from the `from` to the `to` names of the mappings.
+fn class_mapper(mappings: &RawMappings) -> impl ClassMapper {
+    let class_mappings: HashMap<String, String> = mappings
+        .iter()
+        .map(|c| (c.mapping.0.clone(), c.mapping.1.clone()))
+        .collect();
+    FnClassMapper::new(class_mappings, |cm, name| cm.get(name).map(|s| s.as_str()))
+}
+
+fn swap<T>((first, second): (T, T)) -> (T, T) {
+    (second, first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mappings::Type;
+
+    fn object(name: &str) -> Type {
+        Type::Object(name.to_string())
+    }
+
+    fn mapping(from: &str, to: &str) -> (String, String) {
+        (from.to_string(), to.to_string())
+    }
+
+    /// Obfuscated to Mojang mappings of two classes, whose methods refer to each other.
+    fn obf_to_mojang() -> RawMappings {
+        vec![
+            RawClassMapping {
+                mapping: mapping("dyr", "net.minecraft.client.Minecraft"),
+                methods: vec![
+                    RawMethodMapping {
+                        descriptor: Descriptor {
+                            params: vec![],
+                            return_type: Type::Void,
+                        },
+                        mapping: mapping("e", "tick"),
+                    },
+                    RawMethodMapping {
+                        descriptor: Descriptor {
+                            params: vec![
+                                object("ach"),
+                                Type::Array(Box::new(object("ach"))),
+                                Type::Int,
+                                object("java.lang.String"),
+                            ],
+                            return_type: object("dyr"),
+                        },
+                        mapping: mapping("a", "create"),
+                    },
+                ],
+                fields: vec![mapping("b", "instance"), mapping("c", "level")],
+            },
+            RawClassMapping {
+                mapping: mapping("ach", "net.minecraft.world.level.Level"),
+                methods: vec![RawMethodMapping {
+                    descriptor: Descriptor {
+                        params: vec![object("dyr")],
+                        return_type: Type::Boolean,
+                    },
+                    mapping: mapping("a", "isClientSide"),
+                }],
+                fields: vec![mapping("a", "random")],
+            },
+        ]
+    }
+
+    #[test]
+    fn flip_twice_is_identity() {
+        assert_eq!(
+            flip_mappings(flip_mappings(obf_to_mojang())),
+            obf_to_mojang()
+        );
+    }
+
+    #[test]
+    fn flip_renames_descriptors() {
+        let flipped = flip_mappings(obf_to_mojang());
+        let minecraft = &flipped[0];
+        assert_eq!(
+            minecraft.mapping,
+            mapping("net.minecraft.client.Minecraft", "dyr")
+        );
+        assert_eq!(minecraft.fields[0], mapping("instance", "b"));
+        let create = &minecraft.methods[1];
+        assert_eq!(create.mapping, mapping("create", "a"));
+        assert_eq!(
+            create.descriptor,
+            Descriptor {
+                params: vec![
+                    object("net.minecraft.world.level.Level"),
+                    Type::Array(Box::new(object("net.minecraft.world.level.Level"))),
+                    Type::Int,
+                    object("java.lang.String"),
+                ],
+                return_type: object("net.minecraft.client.Minecraft"),
+            }
+        );
     }
 }
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use error_stack::Report;
+use once_cell::sync::OnceCell;
+
+use crate::mappings::{generate_mapper, EitherMapper};
+use crate::names::NamesType;
+use crate::SPError;
+
+type Slot = Arc<OnceCell<Arc<EitherMapper>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MapperKey {
+    version: String,
+    from: NamesType,
+    to: NamesType,
+}
+
+#[derive(Debug)]
+struct Entry {
+    slot: Slot,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<MapperKey, Entry>,
+    tick: u64,
+}
+
+/// Memoizes built mappers by version and names, so long-lived processes don't re-parse the
+/// mappings for every input.
+///
+/// Mappers for different keys are built concurrently, while concurrent requests for the same key
+/// wait for a single build. Failed builds are not remembered.
+#[derive(Debug, Default)]
+pub struct MapperRegistry {
+    max_entries: Option<NonZeroUsize>,
+    entries: Mutex<Entries>,
+}
+
+impl MapperRegistry {
+    /// Create a registry that keeps every mapper it builds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry that evicts the least recently used mapper once it holds `max_entries`.
+    pub fn with_max_entries(max_entries: NonZeroUsize) -> Self {
+        Self {
+            max_entries: Some(max_entries),
+            ..Self::default()
+        }
+    }
+
+    /// Get the mapper for the given version and names, building it if needed.
+    pub fn get(
+        &self,
+        version: &str,
+        from: NamesType,
+        to: NamesType,
+    ) -> Result<Arc<EitherMapper>, Report<SPError>> {
+        let slot = self.slot(MapperKey {
+            version: version.to_string(),
+            from,
+            to,
+        });
+        slot.get_or_try_init(|| generate_mapper(version.to_string(), from, to).map(Arc::new))
+            .cloned()
+    }
+
+    /// Drop every cached mapper.
+    pub fn clear(&self) {
+        self.lock().map.clear();
+    }
+
+    fn slot(&self, key: MapperKey) -> Slot {
+        let mut entries = self.lock();
+        entries.tick += 1;
+        let tick = entries.tick;
+        if let Some(entry) = entries.map.get_mut(&key) {
+            entry.last_used = tick;
+            return entry.slot.clone();
+        }
+        if let Some(max_entries) = self.max_entries {
+            while entries.map.len() >= max_entries.get() {
+                let oldest = entries
+                    .map
+                    .iter()
+                    .min_by_key(|(_, e)| e.last_used)
+                    .map(|(k, _)| k.clone())
+                    .expect("map is not empty");
+                tracing::debug!("Evicting mapper for {:?}", oldest);
+                entries.map.remove(&oldest);
+            }
+        }
+        let slot = Slot::default();
+        entries.map.insert(
+            key,
+            Entry {
+                slot: slot.clone(),
+                last_used: tick,
+            },
+        );
+        slot
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        // The lock is never held while building, so a panic can't leave the entries inconsistent
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,238 @@
+//! Sources of mappings, which connect the [`NamesType`]s that traces can be mapped between.
+//!
+//! The built-in sources are enabled by the `mojang` and `fabric` features, and read the Gradle
+//! cache of Fabric Loom before downloading. Others, e.g. for private modpack mappings, can be
+//! added with [`register_source`]. Tiny files with more than two namespaces can give a source for
+//! each pair of them with [`TinyFile`](super::tiny_file::TinyFile).
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+
+use error_stack::{Report, ResultExt};
+use itertools::Itertools;
+use memmap2::Mmap;
+use once_cell::sync::Lazy;
+
+use crate::http::good_error_request;
+pub use crate::mappings::raw::{flip_mappings, RawClassMapping, RawMethodMapping};
+use crate::names::NamesType;
+use crate::SPError;
+
+/// Parsed mappings, as given by [`MappingSource::parse`].
+pub type RawMappings = Vec<RawClassMapping<Vec<RawMethodMapping>>>;
+
+/// Fetched mappings, as given by [`MappingSource::fetch`]. Large files can be memory-mapped
+/// rather than read, so they are parsed without copying them into memory first. Clones share the
+/// text.
+#[derive(Debug, Clone)]
+pub struct Content(Arc<Text>);
+
+#[derive(Debug)]
+enum Text {
+    Owned(String),
+    /// Checked to be UTF-8 when mapped.
+    Mapped(Mmap),
+}
+
+impl Content {
+    /// Memory-map the `file`, checking that it is UTF-8.
+    ///
+    /// The file must not be modified while the content is in use. Removing it is fine, which is
+    /// how the mappings cache replaces its files.
+    pub fn map_file(file: &File) -> Result<Self, Report<SPError>> {
+        // SAFETY: see above, modifying the file is the caller's problem
+        let mmap = unsafe { Mmap::map(file) }
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to map file into memory")?;
+        std::str::from_utf8(&mmap)
+            .change_context(SPError::ParseError)
+            .attach_printable("Mappings are not valid UTF-8")?;
+        Ok(Self(Arc::new(Text::Mapped(mmap))))
+    }
+}
+
+impl From<String> for Content {
+    fn from(value: String) -> Self {
+        Self(Arc::new(Text::Owned(value)))
+    }
+}
+
+impl Deref for Content {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match &*self.0 {
+            Text::Owned(text) => text,
+            // SAFETY: checked in `map_file`
+            Text::Mapped(mmap) => unsafe { std::str::from_utf8_unchecked(mmap) },
+        }
+    }
+}
+
+/// Fetches and parses mappings from one [`NamesType`] to another. They are used in both
+/// directions.
+pub trait MappingSource: Debug + Send + Sync {
+    /// A short name for this source, unique among the registered ones, e.g. `mojang`. It names
+    /// the source's mappings in bundles.
+    fn name(&self) -> &str;
+
+    /// The names the parsed mappings map from.
+    fn primary_names(&self) -> NamesType;
+
+    /// The names the parsed mappings map to.
+    fn secondary_names(&self) -> NamesType;
+
+    /// Fetch the mappings for the `version`, e.g. from the cache or the network.
+    ///
+    /// Fails with [`SPError::UnknownVersion`] if this source has no mappings for the version, so
+    /// other sources for the same names can be tried.
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>>;
+
+    /// Parse the fetched mappings for the `version`.
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>>;
+
+    /// Where the mappings for the `version` would be fetched from, e.g. a URL or a path, if that
+    /// is known without fetching anything.
+    fn location(&self, _version: &str) -> Option<String> {
+        None
+    }
+
+    /// Check if the mappings for the `version` are available without network access.
+    fn is_cached(&self, _version: &str) -> bool {
+        false
+    }
+
+    /// Check if this source has mappings for the `version` without fetching them, e.g. from a
+    /// version manifest. `None` if that can't be known, e.g. because the network is unavailable.
+    fn has_version(&self, _version: &str) -> Option<bool> {
+        None
+    }
+}
+
+static SOURCES: Lazy<RwLock<Vec<Arc<dyn MappingSource>>>> = Lazy::new(|| {
+    let builtin: Vec<Arc<dyn MappingSource>> = vec![
+        #[cfg(feature = "mojang")]
+        Arc::new(crate::mappings::mojang::MojangSource),
+        #[cfg(feature = "fabric")]
+        Arc::new(crate::mappings::fabric_intermediary::FabricIntermediarySource),
+        // Later sources are tried first, so check the local caches before the network
+        #[cfg(feature = "mojang")]
+        Arc::new(crate::mappings::loom::LoomMojangSource),
+        #[cfg(feature = "fabric")]
+        Arc::new(crate::mappings::loom::LoomIntermediarySource),
+    ];
+    RwLock::new(builtin)
+});
+
+/// Add a source of mappings. It is tried before the sources registered earlier and the
+/// built-in ones, which are still used for versions it doesn't know.
+///
+/// Mappers that were already built, e.g. in a [`MapperRegistry`](super::MapperRegistry), are not
+/// affected.
+pub fn register_source(source: impl MappingSource + 'static) {
+    SOURCES
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(Arc::new(source));
+}
+
+/// The registered sources, most recently registered first.
+pub(crate) fn sources() -> Vec<Arc<dyn MappingSource>> {
+    let sources = SOURCES
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    sources.iter().rev().cloned().collect()
+}
+
+/// Use the mappings at `location`, a path or an `http(s)` URL, instead of those of the registered
+/// source named `name`, for every version. They are parsed like the source's own, e.g. a locally
+/// patched ProGuard file for `mojang`.
+///
+/// The mappings are never checked against a hash, nor cached.
+pub fn override_source(name: &str, location: impl Into<String>) -> Result<(), Report<SPError>> {
+    let all = sources();
+    let source = all
+        .iter()
+        .find(|s| s.name() == name)
+        .cloned()
+        .ok_or_else(|| {
+            Report::new(SPError::UnsupportedPath).attach_printable(format!(
+                "No source named {}, the sources are {}",
+                name,
+                all.iter().map(|s| s.name()).unique().join(", ")
+            ))
+        })?;
+    let location = location.into();
+    tracing::warn!(
+        "Using unverified mappings from {} instead of source {}",
+        location,
+        name
+    );
+    register_source(OverrideSource {
+        name: format!("{}_override", name),
+        source,
+        location,
+    });
+    Ok(())
+}
+
+/// A source whose mappings are fetched from elsewhere, see [`override_source`].
+#[derive(Debug)]
+struct OverrideSource {
+    name: String,
+    source: Arc<dyn MappingSource>,
+    location: String,
+}
+
+impl MappingSource for OverrideSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn primary_names(&self) -> NamesType {
+        self.source.primary_names()
+    }
+
+    fn secondary_names(&self) -> NamesType {
+        self.source.secondary_names()
+    }
+
+    fn fetch(&self, _version: &str) -> Result<Content, Report<SPError>> {
+        if self.is_url() {
+            good_error_request(&self.location)?
+                .text()
+                .map(Content::from)
+                .change_context(SPError::NetworkError)
+                .attach_printable_lazy(|| format!("Failed to download {}", self.location))
+        } else {
+            std::fs::read_to_string(&self.location)
+                .map(Content::from)
+                .change_context(SPError::IoError)
+                .attach_printable_lazy(|| format!("Failed to read {}", self.location))
+        }
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        self.source.parse(version, content)
+    }
+
+    fn location(&self, _version: &str) -> Option<String> {
+        Some(self.location.clone())
+    }
+
+    fn is_cached(&self, _version: &str) -> bool {
+        !self.is_url()
+    }
+
+    fn has_version(&self, _version: &str) -> Option<bool> {
+        Some(true)
+    }
+}
+
+impl OverrideSource {
+    fn is_url(&self) -> bool {
+        self.location.starts_with("http://") || self.location.starts_with("https://")
+    }
+}
@@ -1,15 +1,17 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::HashMap;
+use std::iter::once;
 use std::str;
 
-use chumsky::Parser;
+use chumsky::error::Simple;
 use chumsky::prelude::choice;
-use chumsky::primitive::{empty, end, filter, just};
-use chumsky::recursive::recursive;
+use chumsky::primitive::{end, filter, just};
+use chumsky::Parser;
 use error_stack::Report;
 
-use crate::mappings::{Descriptor, Type};
-use crate::parsing::{CharParser, eol, handle_errors, parse_recovery_debuggable};
+use crate::mappings::source::{RawClassMapping, RawMappings, RawMethodMapping};
+use crate::mappings::{Descriptor, FnClassMapper, MapSelfOnlyClass};
+use crate::parsing::{descriptor, eol, handle_errors, parse_recovery_debuggable, CharParser};
+use crate::progress::PROGRESS_TARGET;
 use crate::SPError;
 
 #[derive(Debug)]
@@ -18,7 +20,65 @@ pub struct TinyMappings {
     pub content: TinyContent,
 }
 
+impl TinyMappings {
+    /// Convert to raw mappings between two of the namespaces, e.g. `intermediary` to `named` in
+    /// a Yarn file, skipping classes and methods without a name in either.
+    ///
+    /// This borrows the mappings, so one parsed file can be converted for several pairs of its
+    /// namespaces.
+    pub fn to_raw(&self, from: &str, to: &str) -> Result<RawMappings, Report<SPError>> {
+        let from_column = self.header.column(from)?;
+        let to_column = self.header.column(to)?;
+        // Descriptors are in namespace A, so map them to the `from` namespace if it's another
+        let from_names: HashMap<String, String> = if from_column == 0 {
+            HashMap::new()
+        } else {
+            self.content
+                .classes
+                .iter()
+                .filter_map(|c| {
+                    let name = c.mapping.name(from_column)?;
+                    Some((c.mapping.primary_name.clone(), name.to_string()))
+                })
+                .collect()
+        };
+        let from_names = FnClassMapper::new(from_names, |names, name| {
+            names.get(name).map(|s| s.as_str())
+        });
+        Ok(self
+            .content
+            .classes
+            .iter()
+            .filter_map(|c| {
+                Some(RawClassMapping {
+                    mapping: c.mapping.pair(from_column, to_column)?,
+                    methods: c
+                        .methods
+                        .iter()
+                        .filter_map(|m| {
+                            Some(RawMethodMapping {
+                                descriptor: if from_column == 0 {
+                                    m.primary_desc.clone()
+                                } else {
+                                    m.primary_desc.clone().map_self(&from_names)
+                                },
+                                mapping: m.mapping.pair(from_column, to_column)?,
+                            })
+                        })
+                        .collect(),
+                    fields: c
+                        .fields
+                        .iter()
+                        .filter_map(|f| f.pair(from_column, to_column))
+                        .collect(),
+                })
+            })
+            .collect())
+    }
+}
+
 pub fn parse_tiny_v2(input: &str) -> Result<TinyMappings, Report<SPError>> {
+    tracing::info!(target: PROGRESS_TARGET, event = "parse_started", kind = "tiny_v2");
     let res = parse_recovery_debuggable(tiny_mappings(), input);
     handle_errors(input, res, "Failed to parse tiny v2 mappings")
 }
@@ -26,32 +86,45 @@ pub fn parse_tiny_v2(input: &str) -> Result<TinyMappings, Report<SPError>> {
 fn tiny_mappings() -> impl CharParser<TinyMappings> {
     header()
         .then_with(|header| {
-            let c = content(&header);
-            let header_smuggler = Rc::new(RefCell::new(Some(header)));
-            c.map(move |c| {
-                // This is a workaround since we know we are only going to be called once.
-                // We can't just move the header into the closure because `map` can legally
-                // be called multiple times, but we assert here that it won't be.
-                let smuggled_header = header_smuggler
-                    .take()
-                    .expect("content map called more than once");
-                TinyMappings {
-                    header: smuggled_header,
-                    content: c,
-                }
+            // `map` can legally be called multiple times, so each call gets its own copy.
+            // The header is tiny, so this is cheap.
+            content(&header).map(move |c| TinyMappings {
+                header: header.clone(),
+                content: c,
             })
         })
         .then_ignore(end())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TinyHeader {
     pub namespace_a: String,
     pub namespace_b: String,
     pub extra_namespaces: Vec<String>,
+    /// The names of the properties. Their values aren't needed, so they are dropped.
     pub properties: Vec<String>,
 }
 
+impl TinyHeader {
+    /// The column of the `namespace`, where namespace A is column 0.
+    pub fn column(&self, namespace: &str) -> Result<usize, Report<SPError>> {
+        once(&self.namespace_a)
+            .chain(once(&self.namespace_b))
+            .chain(&self.extra_namespaces)
+            .position(|n| n == namespace)
+            .ok_or_else(|| {
+                Report::new(SPError::ParseError)
+                    .attach_printable(format!("No namespace {} in tiny mappings", namespace))
+                    .attach_printable(format!("Header: {:?}", self))
+            })
+    }
+
+    /// Check if names use escape sequences, e.g. `\t` for a tab.
+    pub fn escaped_names(&self) -> bool {
+        self.properties.iter().any(|p| p == "escaped-names")
+    }
+}
+
 fn header() -> impl CharParser<TinyHeader> {
     just("tiny\t")
         .labelled("magic")
@@ -75,11 +148,12 @@ fn header() -> impl CharParser<TinyHeader> {
                         .labelled("extra namespaces"),
                 )
                 .then_ignore(eol())
-                // Properties
+                // Properties, each with an optional value
                 .then(
                     just("\t")
                         .labelled("Properties tab")
                         .ignore_then(safe_string())
+                        .then_ignore(just("\t").then(conf_safe_string(true)).or_not())
                         .then_ignore(eol())
                         .repeated()
                         .labelled("properties"),
@@ -101,7 +175,7 @@ pub struct TinyContent {
 }
 
 fn content(header: &TinyHeader) -> impl CharParser<TinyContent> {
-    class_section(1 + header.extra_namespaces.len())
+    class_section(1 + header.extra_namespaces.len(), header.escaped_names())
         .repeated()
         .map(|classes| TinyContent { classes })
 }
@@ -112,44 +186,78 @@ pub struct TinyMapping {
     pub mapped_names: Vec<Option<String>>,
 }
 
+impl TinyMapping {
+    /// The name in the `column`, where the primary name is column 0.
+    pub fn name(&self, column: usize) -> Option<&str> {
+        match column {
+            0 => Some(&self.primary_name),
+            _ => self.mapped_names.get(column - 1)?.as_deref(),
+        }
+    }
+
+    fn pair(&self, from_column: usize, to_column: usize) -> Option<(String, String)> {
+        Some((
+            self.name(from_column)?.to_string(),
+            self.name(to_column)?.to_string(),
+        ))
+    }
+}
+
 #[derive(Debug)]
 pub struct TinyClass {
     pub mapping: TinyMapping,
     pub methods: Vec<TinyMethod>,
-    // Ignoring fields as we don't need them. Might want them later for mapping NPEs.
-    // pub fields: HashMap<String, TinyField>,
+    /// Field descriptors are dropped, as fields are only looked up by name.
+    pub fields: Vec<TinyMapping>,
+}
+
+enum TinyMember {
+    Method(TinyMethod),
+    Field(TinyMapping),
 }
 
-fn class_section(names_count: usize) -> impl CharParser<TinyClass> {
+fn class_section(names_count: usize, escaped: bool) -> impl CharParser<TinyClass> {
     just("c\t")
-        .ignore_then(safe_string().labelled("primary class name"))
+        .ignore_then(conf_safe_string(escaped).labelled("primary class name"))
         .then(
             just("\t")
-                .ignore_then(conf_safe_string().or_not())
+                .ignore_then(conf_safe_string(escaped).or_not())
                 .repeated()
                 .exactly(names_count)
                 .labelled("mapped class names"),
         )
         .then_ignore(eol())
         .then(
-            field_section()
+            field_section(names_count, escaped)
                 .labelled("field section")
-                .map(|_| None)
-                .or(method_section(names_count)
+                .map(|f| Some(TinyMember::Field(f)))
+                .or(comment_section(1).labelled("class comment").map(|_| None))
+                .or(method_section(names_count, escaped)
                     .labelled("method section")
-                    .map(Some))
+                    .map(|m| Some(TinyMember::Method(m))))
                 .repeated()
                 .flatten(),
         )
-        .map(|((name_a, mapped_names), method_sections)| TinyClass {
-            mapping: TinyMapping {
-                primary_name: name_a.replace('/', "."),
-                mapped_names: mapped_names
-                    .into_iter()
-                    .map(|n| n.map(|name| name.replace('/', ".")))
-                    .collect(),
-            },
-            methods: method_sections,
+        .map(|((name_a, mapped_names), members)| {
+            let mut methods = Vec::new();
+            let mut fields = Vec::new();
+            for member in members {
+                match member {
+                    TinyMember::Method(m) => methods.push(m),
+                    TinyMember::Field(f) => fields.push(f),
+                }
+            }
+            TinyClass {
+                mapping: TinyMapping {
+                    primary_name: name_a.replace('/', "."),
+                    mapped_names: mapped_names
+                        .into_iter()
+                        .map(|n| n.map(|name| name.replace('/', ".")))
+                        .collect(),
+                },
+                methods,
+                fields,
+            }
         })
 }
 
@@ -164,70 +272,34 @@ pub struct TinyMethod {
     // pub variables: HashMap<String, TinyVariable>,
 }
 
-fn method_section(names_count: usize) -> impl CharParser<TinyMethod> {
+fn method_section(names_count: usize, escaped: bool) -> impl CharParser<TinyMethod> {
     just("\tm\t")
         .ignore_then(descriptor())
         .labelled("method desc a")
         .then_ignore(just("\t"))
-        .then(conf_safe_string())
+        .then(conf_safe_string(escaped))
         .labelled("method name a")
         .then(
             just("\t")
-                .ignore_then(conf_safe_string().or_not())
+                .ignore_then(conf_safe_string(escaped).or_not())
                 .repeated()
                 .exactly(names_count)
                 .labelled("mapped method names"),
         )
         .then_ignore(eol())
         .then_ignore(skip_method_subsections())
-        .map(|((primary_desc, primary_name), mapped_names)| {
-            TinyMethod {
-                primary_desc,
-                mapping: TinyMapping {
-                    primary_name,
-                    mapped_names,
-                },
-            }
-        })
-}
-
-fn descriptor() -> impl CharParser<Descriptor> {
-    descriptor_type()
-        .repeated()
-        .delimited_by(just("("), just(")"))
-        .then(descriptor_type())
-        .map(|(params, return_type)| Descriptor {
-            params,
-            return_type,
+        .map(|((primary_desc, primary_name), mapped_names)| TinyMethod {
+            primary_desc,
+            mapping: TinyMapping {
+                primary_name,
+                mapped_names,
+            },
         })
 }
 
-fn descriptor_type() -> impl CharParser<Type> {
-    recursive(|t| {
-        choice((
-            just("V").map(|_| Type::Void),
-            just("Z").map(|_| Type::Boolean),
-            just("B").map(|_| Type::Byte),
-            just("S").map(|_| Type::Short),
-            just("C").map(|_| Type::Char),
-            just("I").map(|_| Type::Int),
-            just("J").map(|_| Type::Long),
-            just("F").map(|_| Type::Float),
-            just("D").map(|_| Type::Double),
-            type_name()
-                .delimited_by(just("L"), just(";"))
-                .map(|s| s.replace('/', "."))
-                .map(Type::Object),
-            t.delimited_by(just("["), empty())
-                .map(Box::new)
-                .map(Type::Array),
-        ))
-    })
-}
-
+/// Skip the parameters, variables, and comments of a method, and their own comments.
 fn skip_method_subsections() -> impl CharParser<()> {
     // Don't really care to parse this exactly
-    // Doesn't handle comments for now.
     just("\t\t")
         .ignore_then(eol().not().repeated().ignored())
         .ignore_then(eol())
@@ -235,10 +307,35 @@ fn skip_method_subsections() -> impl CharParser<()> {
         .to(())
 }
 
-fn field_section() -> impl CharParser<()> {
-    // Don't really care to parse this exactly
-    // Doesn't handle comments for now.
-    just("\tf")
+fn field_section(names_count: usize, escaped: bool) -> impl CharParser<TinyMapping> {
+    just("\tf\t")
+        .ignore_then(safe_string())
+        .labelled("field desc a")
+        .ignore_then(just("\t"))
+        .ignore_then(conf_safe_string(escaped))
+        .labelled("field name a")
+        .then(
+            just("\t")
+                .ignore_then(conf_safe_string(escaped).or_not())
+                .repeated()
+                .exactly(names_count)
+                .labelled("mapped field names"),
+        )
+        .then_ignore(eol())
+        .then_ignore(comment_section(2).repeated())
+        .map(|(primary_name, mapped_names)| TinyMapping {
+            primary_name,
+            mapped_names,
+        })
+}
+
+/// A comment on the enclosing element, indented by `depth` tabs. Comments are always escaped, so
+/// they fit on one line.
+fn comment_section(depth: usize) -> impl CharParser<()> {
+    just("\t")
+        .repeated()
+        .exactly(depth)
+        .ignore_then(just("c\t"))
         .ignore_then(eol().not().ignored().repeated())
         .ignore_then(eol())
 }
@@ -250,14 +347,40 @@ fn safe_string() -> impl CharParser<String> {
         .collect()
 }
 
-fn type_name() -> impl CharParser<String> {
-    filter(|&c| c != '\t' && c != '\n' && c != '\r' && c != '\0' && c != '\\' && c != ';')
+/// A name, which may contain escape sequences if the `escaped-names` property is set.
+#[allow(clippy::result_large_err)]
+fn conf_safe_string(escaped: bool) -> impl CharParser<String> {
+    let escape = just('\\').ignore_then(choice((
+        just('\\').to('\\'),
+        just('n').to('\n'),
+        just('r').to('\r'),
+        just('t').to('\t'),
+        just('0').to('\0'),
+        just('u').ignore_then(
+            filter(char::is_ascii_hexdigit)
+                .repeated()
+                .exactly(4)
+                .collect::<String>()
+                .try_map(|hex, span| {
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| Simple::custom(span, "invalid unicode escape"))
+                }),
+        ),
+    )));
+    filter(|&c| c != '\t' && c != '\n' && c != '\r' && c != '\0' && c != '\\')
+        .or(escape.try_map(move |c, span| {
+            if escaped {
+                Ok(c)
+            } else {
+                Err(Simple::custom(
+                    span,
+                    "escapes need the escaped-names property",
+                ))
+            }
+        }))
         .repeated()
         .at_least(1)
         .collect()
 }
-
-fn conf_safe_string() -> impl CharParser<String> {
-    // Later may handle escaped string property.
-    safe_string()
-}
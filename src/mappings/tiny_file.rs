@@ -0,0 +1,152 @@
+//! Sources for tiny files with more than two namespaces, e.g. Yarn's `official`, `intermediary`
+//! and `named`.
+//!
+//! A [`TinyFile`] is fetched and parsed once per version, and each [`TinyColumns`] made from it
+//! is a separate [`MappingSource`] for one pair of its namespaces, i.e. an edge of the mappings
+//! graph for the file and two of its columns.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+use error_stack::{Report, ResultExt};
+use once_cell::sync::OnceCell;
+
+use crate::mappings::source::{Content, MappingSource, RawMappings};
+use crate::mappings::tiny::{parse_tiny_v2, TinyMappings};
+use crate::names::NamesType;
+use crate::SPError;
+
+type Fetch = dyn Fn(&str) -> Result<String, Report<SPError>> + Send + Sync;
+
+#[derive(Debug, Default)]
+struct Loaded {
+    content: OnceCell<Content>,
+    mappings: OnceCell<Arc<TinyMappings>>,
+}
+
+/// A tiny file shared by several [`TinyColumns`] sources. Once fetched or parsed for a version,
+/// it is kept in memory for the other sources.
+pub struct TinyFile {
+    name: String,
+    fetch: Box<Fetch>,
+    loaded: Mutex<HashMap<String, Arc<Loaded>>>,
+}
+
+impl Debug for TinyFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TinyFile")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TinyFile {
+    /// Create a file named `name`, which `fetch` fetches for a version. Like
+    /// [`MappingSource::fetch`], it should fail with [`SPError::UnknownVersion`] for versions it
+    /// has no mappings for.
+    pub fn new(
+        name: impl Into<String>,
+        fetch: impl Fn(&str) -> Result<String, Report<SPError>> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.into(),
+            fetch: Box::new(fetch),
+            loaded: Mutex::default(),
+        })
+    }
+
+    /// A source mapping from the `primary` namespace of this file to the `secondary` one, with
+    /// the names they have in the mappings graph.
+    pub fn columns(
+        self: &Arc<Self>,
+        primary: (NamesType, &str),
+        secondary: (NamesType, &str),
+    ) -> TinyColumns {
+        TinyColumns {
+            name: format!("{}_{}_{}", self.name, primary.1, secondary.1),
+            file: Arc::clone(self),
+            primary: (primary.0, primary.1.to_string()),
+            secondary: (secondary.0, secondary.1.to_string()),
+        }
+    }
+
+    fn loaded(&self, version: &str) -> Arc<Loaded> {
+        let mut loaded = self
+            .loaded
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Arc::clone(loaded.entry(version.to_string()).or_default())
+    }
+
+    fn is_fetched(&self, version: &str) -> bool {
+        let loaded = self
+            .loaded
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        loaded
+            .get(version)
+            .is_some_and(|l| l.content.get().is_some())
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        self.loaded(version)
+            .content
+            .get_or_try_init(|| (self.fetch)(version).map(Content::from))
+            .cloned()
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<Arc<TinyMappings>, Report<SPError>> {
+        self.loaded(version)
+            .mappings
+            .get_or_try_init(|| {
+                parse_tiny_v2(content)
+                    .map(Arc::new)
+                    .attach_printable_lazy(|| format!("Invalid {} for {}", self.name, version))
+            })
+            .cloned()
+    }
+}
+
+/// Two namespaces of a [`TinyFile`], as a [`MappingSource`].
+#[derive(Debug)]
+pub struct TinyColumns {
+    name: String,
+    file: Arc<TinyFile>,
+    primary: (NamesType, String),
+    secondary: (NamesType, String),
+}
+
+impl MappingSource for TinyColumns {
+    /// The name of the file and both namespaces, e.g. `yarn_intermediary_named`.
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn primary_names(&self) -> NamesType {
+        self.primary.0
+    }
+
+    fn secondary_names(&self) -> NamesType {
+        self.secondary.0
+    }
+
+    fn fetch(&self, version: &str) -> Result<Content, Report<SPError>> {
+        self.file.fetch(version)
+    }
+
+    fn parse(&self, version: &str, content: &str) -> Result<RawMappings, Report<SPError>> {
+        self.file
+            .parse(version, content)?
+            .to_raw(&self.primary.1, &self.secondary.1)
+            .attach_printable_lazy(|| format!("Invalid {} for {}", self.file.name, version))
+    }
+
+    fn is_cached(&self, version: &str) -> bool {
+        self.file.is_fetched(version)
+    }
+
+    fn has_version(&self, version: &str) -> Option<bool> {
+        self.file.is_fetched(version).then_some(true)
+    }
+}
@@ -0,0 +1,267 @@
+use itertools::Itertools;
+
+use crate::mappings::{Descriptor, MethodMapper};
+use crate::parsing::is_java_identifier_part;
+
+/// Map the names embedded in the message of an exception of the (unmapped) class `exception`,
+/// for the message families we understand.
+pub fn map_message(exception: &str, message: String, mapper: &impl MethodMapper) -> String {
+    if is_mixin_message(&message) {
+        return map_mixin_message(&message, mapper);
+    }
+    if is_field_error(exception) {
+        return map_field_message(&message, mapper);
+    }
+    message
+}
+
+fn is_mixin_message(message: &str) -> bool {
+    message.contains("mixin")
+        || message.contains("Mixin")
+        || message.contains("@Inject")
+        || message.contains("injection")
+}
+
+fn is_field_error(exception: &str) -> bool {
+    exception == "java.lang.NoSuchFieldError" || exception == "java.lang.IllegalAccessError"
+}
+
+/// `NoSuchFieldError`s give the bare field name, e.g. `field_7748`, or on newer JVMs its class
+/// too, e.g. `Class abc does not have member field 'int d'`. `IllegalAccessError`s qualify the
+/// field with its class, e.g. `tried to access field abc.d from class xyz`.
+///
+/// The names after `class` and `field` are known to be names, so unlike elsewhere in messages,
+/// unqualified (obfuscated) class names are mapped there too.
+fn map_field_message(message: &str, mapper: &impl MethodMapper) -> String {
+    if !message.is_empty() && message.chars().all(is_java_identifier_part) {
+        return map_field_name(None, message, mapper).unwrap_or_else(|| message.to_string());
+    }
+    if let Some((class, rest)) = message
+        .strip_prefix("Class ")
+        .and_then(|m| m.split_once(" does not have member field '"))
+    {
+        if let Some(((ty, name), after)) = rest
+            .split_once('\'')
+            .and_then(|(field, after)| Some((field.rsplit_once(' ')?, after)))
+        {
+            return format!(
+                "Class {} does not have member field '{} {}'{}",
+                mapper.map_class(class).unwrap_or(class),
+                mapper.map_class(ty).unwrap_or(ty),
+                map_field_name(Some(class), name, mapper).unwrap_or_else(|| name.to_string()),
+                map_class_tokens(after, mapper, false)
+            );
+        }
+    }
+    let mut output = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some((start, keyword)) = ["field ", "class ", "Class "]
+        .into_iter()
+        .filter_map(|k| Some((rest.find(k)? + k.len(), k)))
+        .min_by_key(|&(start, _)| start)
+    {
+        output.push_str(&map_class_tokens(&rest[..start], mapper, false));
+        rest = &rest[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (token, after) = rest.split_at(end);
+        // Sentences may end right after a name
+        let name = token.trim_end_matches('.');
+        let mapped = match keyword {
+            "field " => map_qualified_field(name, mapper),
+            _ => mapper.map_class(name).map(String::from),
+        };
+        match mapped {
+            Some(mapped) => {
+                output.push_str(&mapped);
+                output.push_str(&token[name.len()..]);
+            }
+            None => output.push_str(token),
+        }
+        rest = after;
+    }
+    output.push_str(&map_class_tokens(rest, mapper, false));
+    output
+}
+
+/// Map a field qualified by its class, e.g. `abc.d`. If the field isn't known, only the class is
+/// mapped.
+fn map_qualified_field(token: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let (class, name) = token.rsplit_once('.')?;
+    if name.is_empty() || !name.chars().all(is_java_identifier_part) {
+        return None;
+    }
+    let mapped = mapper
+        .map_field(Some(class), name)
+        .into_iter()
+        .unique()
+        .exactly_one()
+        .ok();
+    match mapped {
+        Some((class, name)) => Some(format!("{}.{}", class, name)),
+        None => Some(format!("{}.{}", mapper.map_class(class)?, name)),
+    }
+}
+
+/// Map a field name, in its class if that's known. The result must be unique, as only the name is
+/// kept.
+fn map_field_name(class: Option<&str>, name: &str, mapper: &impl MethodMapper) -> Option<String> {
+    mapper
+        .map_field(class, name)
+        .into_iter()
+        .map(|(_, name)| name)
+        .unique()
+        .exactly_one()
+        .ok()
+        .map(String::from)
+}
+
+/// Mixin errors quote their target selectors, e.g. `'method_1234(F)V'`, and mention classes in
+/// both source (`net.minecraft.class_310`) and internal (`Lnet/minecraft/class_310;`) form.
+fn map_mixin_message(message: &str, mapper: &impl MethodMapper) -> String {
+    let mut output = String::with_capacity(message.len());
+    let mut parts = message.split('\'').peekable();
+    let mut quoted = false;
+    while let Some(part) = parts.next() {
+        // Only treat it as quoted if there's a closing quote
+        if quoted && parts.peek().is_some() {
+            output.push_str(&map_selector(part, mapper).unwrap_or_else(|| part.to_string()));
+        } else {
+            output.push_str(&map_class_tokens(part, mapper, false));
+        }
+        if parts.peek().is_some() {
+            output.push('\'');
+        }
+        quoted = !quoted;
+    }
+    output
+}
+
+/// Map a target selector such as `Lnet/minecraft/class_310;method_1234(F)V`. The owner and
+/// descriptor are both optional.
+pub fn map_selector(selector: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let (owner, rest) = match selector.strip_prefix('L').and_then(|s| s.split_once(';')) {
+        Some((owner, rest)) => (Some(owner.replace('/', ".")), rest),
+        None => (None, selector),
+    };
+    let (name, desc) = match rest.find('(') {
+        Some(i) => (&rest[..i], Some(&rest[i..])),
+        None => (rest, None),
+    };
+    if name.is_empty() || !name.chars().all(is_java_identifier_part) {
+        return None;
+    }
+    let desc = match desc {
+        Some(desc) => Some(Descriptor::parse_internal(desc).ok()?),
+        None => None,
+    };
+    let candidates = mapper.map_method(owner.as_deref().unwrap_or(""), name, desc.as_ref());
+    // Without a descriptor, overloads will match too. That's fine as long as the name is unique.
+    let (class, id) = candidates
+        .into_iter()
+        .unique_by(|(class, id)| {
+            (
+                *class,
+                &id.name,
+                desc.as_ref().map(|_| id.descriptor.clone()),
+            )
+        })
+        .exactly_one()
+        .ok()?;
+
+    let mut mapped = String::new();
+    if owner.is_some() {
+        mapped.push_str(&format!("L{};", class.replace('.', "/")));
+    }
+    mapped.push_str(&id.name);
+    if desc.is_some() {
+        mapped.push_str(&id.descriptor.internal_descriptor());
+    }
+    Some(mapped)
+}
+
+/// Map every class name in internal form in the text, e.g. `net/minecraft/class_310` or `abc` in
+/// `(Labc;)V`, keeping the form. These are specific enough to find in any text, such as the lines
+/// of a log.
+pub fn map_internal_class_tokens(text: &str, mapper: &impl MethodMapper) -> String {
+    map_class_tokens(text, mapper, true)
+}
+
+/// Map every qualified class name in the text, in either source or internal form, or only
+/// internal form if `internal_only` is set.
+fn map_class_tokens(text: &str, mapper: &impl MethodMapper, internal_only: bool) -> String {
+    let is_token_char = |c: char| is_java_identifier_part(c) || c == '.' || c == '/';
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_token_char) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_token_char(c)).unwrap_or(rest.len());
+        let (token, after) = rest.split_at(end);
+        // Sentences may end right after a class name
+        let name = token.trim_end_matches('.');
+        let mapped = if !name.contains(['/', '.']) {
+            map_descriptor_token(name, output.chars().last(), after.chars().next(), mapper)
+        } else if internal_only && !name.contains('/') {
+            None
+        } else {
+            map_class_token(name, mapper)
+        };
+        match mapped {
+            Some(mapped) => {
+                output.push_str(&mapped);
+                output.push_str(&token[name.len()..]);
+            }
+            None => output.push_str(token),
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Map a class in the default package in a descriptor, e.g. `abc` in `(ILabc;)V`, as obfuscated
+/// classes were before 1.17. Without a `/`, it can only be told from a word by being in a
+/// descriptor, so it must follow its start, an array or another class, and end with `;`.
+fn map_descriptor_token(
+    token: &str,
+    before: Option<char>,
+    after: Option<char>,
+    mapper: &impl MethodMapper,
+) -> Option<String> {
+    if after != Some(';') || !matches!(before, Some('(' | ')' | '[' | ';')) {
+        return None;
+    }
+    // Primitive parameters may come first, e.g. `FI` in `(FILabc;)V`
+    let start = token.find('L')?;
+    if !token[..start].chars().all(|c| "ZBCSIJFD".contains(c)) {
+        return None;
+    }
+    let mapped = mapper.map_class(&token[start + 1..])?;
+    Some(format!("{}L{}", &token[..start], mapped.replace('.', "/")))
+}
+
+pub fn map_class_token(token: &str, mapper: &impl MethodMapper) -> Option<String> {
+    if token.is_empty() {
+        return None;
+    }
+    if token.contains('/') {
+        // Internal names may be glued to the preceding descriptor, e.g. `(FLnet/minecraft/a;)V`,
+        // so try each possible `L` prefix.
+        let starts = std::iter::once(0).chain(token.match_indices('L').map(|(i, _)| i + 1));
+        for start in starts {
+            let name = &token[start..];
+            if !name.contains('/') {
+                continue;
+            }
+            if let Some(mapped) = mapper.map_class(&name.replace('/', ".")) {
+                return Some(format!("{}{}", &token[..start], mapped.replace('.', "/")));
+            }
+        }
+        return None;
+    }
+    // Only qualified names, otherwise we'd end up mapping ordinary words
+    if !token.contains('.') {
+        return None;
+    }
+    mapper.map_class(token).map(String::from)
+}
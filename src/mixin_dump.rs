@@ -0,0 +1,190 @@
+//! The boxes Mixin prints about callback injectors, e.g. the locals of the target method with
+//! `LocalCapture.PRINT` or when capturing them fails:
+//!
+//! ```text
+//! /*  Target Class : net.minecraft.class_757            */
+//! /*  Target Method : method_3188(FJLnet/minecraft/class_4587;)V */
+//! /*           [  3]    [  0]  class_4587  matrices       */
+//! /*    private void onRender(float f, long l, class_4587 matrices, CallbackInfo ci) { */
+//! ```
+//!
+//! Each line is wrapped in `/* ... */`, possibly after a logger prefix. The target class is
+//! qualified, but the types of the locals and of the expected callback signature are simple
+//! names.
+
+use itertools::Itertools;
+
+use crate::mappings::MethodMapper;
+use crate::messages::{map_class_token, map_internal_class_tokens, map_selector};
+use crate::parsing::is_java_identifier_part;
+
+/// The package of Fabric's intermediary classes, which is left out of their simple names.
+const INTERMEDIARY_PACKAGE: &str = "net.minecraft.";
+
+/// Maps the lines of Mixin's boxes, remembering the target class of the current box for the
+/// lines that name its members.
+#[derive(Debug, Default)]
+pub struct MixinDump {
+    target_class: Option<String>,
+}
+
+impl MixinDump {
+    /// Map a line if it is part of a box, keeping the `*/` that ends it in place where possible.
+    /// Any other line ends the current box.
+    pub fn map_line(&mut self, line: &str, mapper: &impl MethodMapper) -> Option<String> {
+        let Some((start, end)) = box_content(line) else {
+            self.target_class = None;
+            return None;
+        };
+        let content = &line[start..end];
+        let mapped = self.map_content(content, mapper)?;
+        // Boxes are padded to a fixed width, so keep it if the names changed length
+        let mapped_trimmed = mapped.trim_end();
+        let padding = (content.len().saturating_sub(mapped_trimmed.len())).max(1);
+        Some(format!(
+            "{}{}{}{}",
+            &line[..start],
+            mapped_trimmed,
+            " ".repeat(padding),
+            &line[end..]
+        ))
+    }
+
+    fn map_content(&mut self, content: &str, mapper: &impl MethodMapper) -> Option<String> {
+        if let Some((key, value)) = content.split_once(" : ") {
+            let value = value.trim_end();
+            match key.trim() {
+                "Target Class" => {
+                    self.target_class = Some(value.to_string());
+                    let mapped = mapper
+                        .map_class(value)
+                        .map(String::from)
+                        .or_else(|| map_class_token(value, mapper))?;
+                    return Some(format!("{} : {}", key, mapped));
+                }
+                "Target Method" => {
+                    let mapped = match &self.target_class {
+                        Some(class) => {
+                            let owner = format!("L{};", class.replace('.', "/"));
+                            map_selector(&format!("{}{}", owner, value), mapper)?
+                                .strip_prefix('L')?
+                                .split_once(';')?
+                                .1
+                                .to_string()
+                        }
+                        None => map_selector(value, mapper)?,
+                    };
+                    return Some(format!("{} : {}", key, mapped));
+                }
+                _ => {}
+            }
+        }
+        let trimmed = content.trim_start();
+        let mapped = if trimmed.starts_with('[') {
+            map_local_row(content, mapper)
+        } else if content.contains('(') && content.contains(')') {
+            map_signature(content, mapper)
+        } else {
+            None
+        };
+        // Descriptors are in internal form anywhere
+        let mapped = map_internal_class_tokens(mapped.as_deref().unwrap_or(content), mapper);
+        (mapped != content).then_some(mapped)
+    }
+}
+
+/// Where the content of a box line is, between its `/*` and `*/`.
+fn box_content(line: &str) -> Option<(usize, usize)> {
+    let start = line.find("/*")? + 2;
+    let end = line.trim_end().strip_suffix("*/")?.len();
+    (end >= start).then_some((start, end))
+}
+
+/// Check if the line is part of one of Mixin's boxes.
+pub(crate) fn is_box_line(line: &str) -> bool {
+    box_content(line).is_some()
+}
+
+/// Map the type of a row of the locals table, e.g. `[  3]    [  0]  class_4587  matrices`, which
+/// follows its index and ordinal.
+fn map_local_row(content: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let (_, after_index) = content.split_once(']')?;
+    let (_, after_ordinal) = after_index.split_once(']')?;
+    let type_start = content.len() - after_ordinal.trim_start().len();
+    let type_end = type_start
+        + content[type_start..]
+            .find(char::is_whitespace)
+            .unwrap_or(content.len() - type_start);
+    let mapped = map_type(&content[type_start..type_end], mapper)?;
+    // The types are aligned to the right, so pad or take from the spaces before them
+    let before = &content[..type_start];
+    let width = type_end - type_start;
+    let before = if mapped.len() < width {
+        format!("{}{}", before, " ".repeat(width - mapped.len()))
+    } else {
+        let spaces = before.len() - before.trim_end().len();
+        let removed = (mapped.len() - width).min(spaces.saturating_sub(1));
+        before[..before.len() - removed].to_string()
+    };
+    Some(format!("{}{}{}", before, mapped, &content[type_end..]))
+}
+
+/// Map the types of the parameters of a method signature, e.g.
+/// `private void onRender(float f, class_4587 matrices, CallbackInfoReturnable<class_1799> cir)`.
+fn map_signature(content: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let open = content.find('(')?;
+    let close = open + content[open..].find(')')?;
+    let mut mapped_any = false;
+    let parameters = content[open + 1..close]
+        .split(',')
+        .map(|parameter| {
+            // The type is everything but the name
+            let Some(name_start) = parameter.trim_end().rfind(' ') else {
+                return parameter.to_string();
+            };
+            let (ty, name) = parameter.split_at(name_start);
+            match map_type(ty, mapper) {
+                Some(mapped) => {
+                    mapped_any = true;
+                    format!("{}{}", mapped, name)
+                }
+                None => parameter.to_string(),
+            }
+        })
+        .join(",");
+    mapped_any.then(|| format!("{}{}{}", &content[..=open], parameters, &content[close..]))
+}
+
+/// Map the simple class names in a type, e.g. both in `Map<class_2338, class_2680>[]`.
+fn map_type(ty: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let mut output = String::with_capacity(ty.len());
+    let mut rest = ty;
+    let mut mapped_any = false;
+    while let Some(start) = rest.find(is_java_identifier_part) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c| !is_java_identifier_part(c))
+            .unwrap_or(rest.len());
+        let (name, after) = rest.split_at(end);
+        match map_simple_class(name, mapper) {
+            Some(mapped) => {
+                output.push_str(&mapped);
+                mapped_any = true;
+            }
+            None => output.push_str(name),
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    mapped_any.then_some(output)
+}
+
+/// Map a simple class name to the simple name of its mapping. Obfuscated classes have no package,
+/// and intermediary classes are all in `net.minecraft`, so those can be found.
+fn map_simple_class(name: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let mapped = mapper
+        .map_class(name)
+        .or_else(|| mapper.map_class(&format!("{}{}", INTERMEDIARY_PACKAGE, name)))?;
+    Some(mapped.rsplit('.').next().unwrap_or(mapped).to_string())
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use error_stack::{Report, ResultExt};
+use serde_json::Value;
+use stacked_portrayals::refmap::RefmapSelectors;
+use stacked_portrayals::SPError;
+use zip::ZipArchive;
+
+/// Load the refmaps of every mod jar in a mods folder.
+///
+/// The refmaps are found through the mods' mixin configs, and by their `.refmap.json` extension.
+/// Jars that can't be read are skipped with a warning, since they often aren't needed.
+pub fn load_refmaps(dir: &Path) -> Result<RefmapSelectors, Report<SPError>> {
+    let mut selectors = RefmapSelectors::new();
+    let entries = std::fs::read_dir(dir)
+        .change_context(SPError::IoError)
+        .attach_printable_lazy(|| format!("Failed to read mods folder {}", dir.display()))?;
+    let mut jars = Vec::new();
+    for entry in entries {
+        let path = entry
+            .change_context(SPError::IoError)
+            .attach_printable_lazy(|| format!("Failed to read mods folder {}", dir.display()))?
+            .path();
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("jar"))
+        {
+            jars.push(path);
+        }
+    }
+    // Sort so conflicting selectors resolve the same way every time
+    jars.sort();
+    for jar in jars {
+        if let Err(e) = add_jar_refmaps(&jar, &mut selectors) {
+            tracing::warn!("Skipping refmaps of {}: {:?}", jar.display(), e);
+        }
+    }
+    tracing::info!(
+        "Loaded {} mixin selectors from {}",
+        selectors.len(),
+        dir.display()
+    );
+    Ok(selectors)
+}
+
+fn add_jar_refmaps(jar: &Path, selectors: &mut RefmapSelectors) -> Result<(), Report<SPError>> {
+    let mut archive = File::open(jar)
+        .change_context(SPError::IoError)
+        .and_then(|f| ZipArchive::new(f).change_context(SPError::IoError))
+        .attach_printable_lazy(|| format!("Failed to open {}", jar.display()))?;
+
+    let mut refmaps = BTreeSet::new();
+    let root_jsons: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.contains('/') && name.ends_with(".json"))
+        .map(String::from)
+        .collect();
+    for name in root_jsons {
+        if name.ends_with(".refmap.json") {
+            refmaps.insert(name);
+            continue;
+        }
+        // Mixin configs name their refmap, which may not follow the naming convention
+        let Ok(config) = serde_json::from_str::<Value>(&read_entry(&mut archive, &name)?) else {
+            continue;
+        };
+        if let Some(refmap) = config.get("refmap").and_then(Value::as_str) {
+            refmaps.insert(refmap.to_string());
+        }
+    }
+
+    for name in refmaps {
+        let json = match read_entry(&mut archive, &name) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::debug!("Missing refmap {} in {}: {:?}", name, jar.display(), e);
+                continue;
+            }
+        };
+        selectors
+            .add_refmap(&json)
+            .attach_printable_lazy(|| format!("Refmap {} in {}", name, jar.display()))?;
+    }
+    Ok(())
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<String, Report<SPError>> {
+    let mut content = String::new();
+    archive
+        .by_name(name)
+        .change_context(SPError::IoError)
+        .and_then(|mut entry| {
+            entry
+                .read_to_string(&mut content)
+                .change_context(SPError::IoError)
+        })
+        .attach_printable_lazy(|| format!("Failed to read {}", name))?;
+    Ok(content)
+}
@@ -10,6 +10,9 @@ pub struct VersionManifest {
 #[derive(Deserialize, Debug)]
 pub struct Version {
     pub id: String,
+    /// `release`, `snapshot`, `old_beta` or `old_alpha`.
+    #[serde(rename = "type")]
+    pub kind: String,
     pub url: String,
 }
 
@@ -21,7 +24,8 @@ pub struct VersionInfo {
 #[derive(Deserialize, Debug)]
 pub struct Downloads {
     // Use client_mappings because it should include the server too.
-    pub client_mappings: Download,
+    /// Missing for versions before 1.14.4.
+    pub client_mappings: Option<Download>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -2,6 +2,8 @@ use derive_more::Display;
 use error_stack::{Context, Report};
 use std::str::FromStr;
 
+use crate::parsing::is_java_identifier_part;
+
 #[derive(Debug, Display)]
 pub struct NamesFromStrError;
 
@@ -29,3 +31,22 @@ impl FromStr for NamesType {
         }
     }
 }
+
+/// Find a name of MCP-era Forge's SRG names in the text, e.g. `func_71407_l`, `field_71439_g` or
+/// `p_77624_1_`. None of the [`NamesType`]s are SRG names, so these can't be mapped, but they tell
+/// that the text is from a Forge version before 1.17.
+pub fn find_srg_name(text: &str) -> Option<&str> {
+    text.split(|c: char| !is_java_identifier_part(c))
+        .find(|token| is_srg_name(token))
+}
+
+fn is_srg_name(token: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    match token.split('_').collect::<Vec<_>>().as_slice() {
+        ["func" | "field", id, suffix] | ["func" | "field", id, suffix, ""] => {
+            is_number(id) && !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_alphabetic())
+        }
+        ["p", id, index, ""] => is_number(id) && is_number(index),
+        _ => false,
+    }
+}
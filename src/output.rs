@@ -0,0 +1,183 @@
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use error_stack::{Report, ResultExt};
+use serde::Deserialize;
+use stacked_portrayals::transport::{send, Request, Response};
+use stacked_portrayals::SPError;
+
+/// Where mclo.gs takes new logs.
+const MCLOGS_URL: &str = "https://api.mclo.gs/1/log";
+
+/// A destination for the mapped output.
+pub trait OutputSink {
+    /// Deliver the whole mapped `output`.
+    fn write(&mut self, output: &str) -> Result<(), Report<SPError>>;
+}
+
+/// Print the output, as usual.
+pub struct Stdout;
+
+impl OutputSink for Stdout {
+    fn write(&mut self, output: &str) -> Result<(), Report<SPError>> {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(output.as_bytes())
+            .and_then(|_| stdout.flush())
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to write to stdout")
+    }
+}
+
+/// Write the output to a file, replacing it.
+pub struct FileSink(pub PathBuf);
+
+impl OutputSink for FileSink {
+    fn write(&mut self, output: &str) -> Result<(), Report<SPError>> {
+        std::fs::write(&self.0, output)
+            .change_context(SPError::IoError)
+            .attach_printable_lazy(|| format!("Failed to write {}", self.0.display()))?;
+        tracing::info!("Wrote mapped output to {}", self.0.display());
+        Ok(())
+    }
+}
+
+/// Copy the output to the clipboard, with the clipboard tool of the platform.
+pub struct Clipboard;
+
+impl Clipboard {
+    /// The tools to try in order, with their arguments.
+    const TOOLS: &'static [(&'static str, &'static [&'static str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+}
+
+impl OutputSink for Clipboard {
+    fn write(&mut self, output: &str) -> Result<(), Report<SPError>> {
+        for &(tool, args) in Self::TOOLS {
+            let mut child = match Command::new(tool)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn()
+            {
+                Ok(child) => child,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(Report::new(e)
+                        .change_context(SPError::IoError)
+                        .attach_printable(format!("Failed to run {}", tool)))
+                }
+            };
+            let written = child
+                .stdin
+                .take()
+                .expect("stdin is piped")
+                .write_all(output.as_bytes());
+            let status = child.wait();
+            written
+                .and(status)
+                .change_context(SPError::IoError)
+                .and_then(|status| {
+                    if status.success() {
+                        Ok(())
+                    } else {
+                        Err(Report::new(SPError::IoError)
+                            .attach_printable(format!("{} exited with {}", tool, status)))
+                    }
+                })
+                .attach_printable("Failed to copy to the clipboard")?;
+            tracing::info!("Copied mapped output to the clipboard");
+            return Ok(());
+        }
+        Err(Report::new(SPError::IoError).attach_printable(format!(
+            "No clipboard tool found, install one of {}",
+            Self::TOOLS
+                .iter()
+                .map(|(tool, _)| *tool)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))
+    }
+}
+
+/// Upload the output to mclo.gs, printing the link to it.
+pub struct Mclogs;
+
+#[derive(Deserialize)]
+struct MclogsResponse {
+    success: bool,
+    url: Option<String>,
+    error: Option<String>,
+}
+
+impl OutputSink for Mclogs {
+    fn write(&mut self, output: &str) -> Result<(), Report<SPError>> {
+        let body = format!("content={}", form_encode(output));
+        let response: MclogsResponse = post(MCLOGS_URL, "application/x-www-form-urlencoded", body)
+            .and_then(|response| {
+                serde_json::from_reader(response.body).change_context(SPError::NetworkError)
+            })
+            .attach_printable("Failed to upload to mclo.gs")?;
+        match response.url {
+            Some(url) if response.success => {
+                println!("{}", url);
+                Ok(())
+            }
+            _ => Err(Report::new(SPError::NetworkError).attach_printable(format!(
+                "mclo.gs rejected the upload: {}",
+                response.error.as_deref().unwrap_or("no reason given")
+            ))),
+        }
+    }
+}
+
+/// Post the output as plain text to a URL, e.g. a bot or a webhook relay.
+pub struct HttpCallback(pub String);
+
+impl OutputSink for HttpCallback {
+    fn write(&mut self, output: &str) -> Result<(), Report<SPError>> {
+        post(&self.0, "text/plain; charset=utf-8", output)?;
+        tracing::info!("Posted mapped output to {}", self.0);
+        Ok(())
+    }
+}
+
+fn post(
+    url: &str,
+    content_type: &str,
+    body: impl Into<Vec<u8>>,
+) -> Result<Response, Report<SPError>> {
+    let response = send(&Request::post(url, content_type, body))
+        .attach_printable_lazy(|| format!("Failed to post to {}", url))?;
+    if response.status >= 400 {
+        return Err(Report::new(SPError::NetworkError)
+            .attach_printable(format!("HTTP status {}", response.status))
+            .attach_printable(format!("Failed to post to {}", url)));
+    }
+    Ok(response)
+}
+
+/// Encode a value of an `application/x-www-form-urlencoded` body.
+fn form_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'*' => {
+                encoded.push(byte as char)
+            }
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -1,10 +1,15 @@
+use std::borrow::Cow;
+
 use ariadne::{Color, Label, Source};
 use chumsky::error::Simple;
-use chumsky::primitive::{filter, just};
+use chumsky::prelude::choice;
+use chumsky::primitive::{empty, filter, just};
+use chumsky::recursive::recursive;
 use chumsky::text::Character;
 use chumsky::{text, Error, Parser, Stream};
 use error_stack::Report;
 
+use crate::mappings::{Descriptor, Type};
 use crate::SPError;
 
 pub fn parse_recovery_debuggable<'a, P, I: Clone, O, E: Error<I>, Iter, S>(
@@ -34,7 +39,7 @@ pub fn handle_errors<O>(
     if errors.is_empty() {
         Ok(output.unwrap())
     } else {
-        Err(Report::new(SPError)
+        Err(Report::new(SPError::ParseError)
             .attach_printable(message.to_string())
             .attach(ParseErrors {
                 source: source.to_string(),
@@ -65,6 +70,8 @@ impl ParseErrors {
     }
 }
 
+/// A parser of text. Its `try_map` closures return [`Simple`] directly, which is large enough for
+/// `clippy::result_large_err`, so the parsers with them allow it.
 pub trait CharParser<T>: Parser<char, T, Error = Simple<char>> {}
 
 impl<T, P> CharParser<T> for P where P: Parser<char, T, Error = Simple<char>> {}
@@ -103,13 +110,78 @@ pub fn inline_whitespace() -> impl CharParser<()> {
         .ignored()
 }
 
+/// A line ending, `\n`, `\r\n` or a lone `\r` from old macOS tools.
 pub fn eol() -> impl CharParser<()> {
-    just("\n").or(just("\r\n")).ignored()
+    just("\n").or(just("\r\n")).or(just("\r")).ignored()
+}
+
+/// Replace the lone `\r` line endings in `input` with `\n`, so line-based processing sees the
+/// same lines as [`eol`]. `\r\n` is kept.
+pub fn normalize_line_endings(input: &str) -> Cow<'_, str> {
+    let bytes = input.as_bytes();
+    let is_lone_cr = |i: &usize| bytes[*i] == b'\r' && bytes.get(i + 1) != Some(&b'\n');
+    let mut lone_crs = (0..bytes.len()).filter(is_lone_cr).peekable();
+    if lone_crs.peek().is_none() {
+        return Cow::Borrowed(input);
+    }
+    let mut output = String::with_capacity(input.len());
+    let mut start = 0;
+    for i in lone_crs {
+        output.push_str(&input[start..i]);
+        output.push('\n');
+        start = i + 1;
+    }
+    output.push_str(&input[start..]);
+    Cow::Owned(output)
 }
 
+#[allow(clippy::result_large_err)]
 pub fn u32_digits() -> impl CharParser<u32> {
     text::digits(10).try_map(|s: String, span| {
         s.parse::<u32>()
             .map_err(|e| Simple::custom(span, format!("{}", e)))
     })
 }
+
+/// A JVM method descriptor, e.g. `(ILjava/lang/String;)V`.
+pub fn descriptor() -> impl CharParser<Descriptor> {
+    descriptor_type()
+        .repeated()
+        .delimited_by(just("("), just(")"))
+        .then(descriptor_type())
+        .map(|(params, return_type)| Descriptor {
+            params,
+            return_type,
+        })
+}
+
+/// A JVM field descriptor, e.g. `[Ljava/lang/String;`.
+pub fn descriptor_type() -> impl CharParser<Type> {
+    recursive(|t| {
+        choice((
+            just("V").map(|_| Type::Void),
+            just("Z").map(|_| Type::Boolean),
+            just("B").map(|_| Type::Byte),
+            just("S").map(|_| Type::Short),
+            just("C").map(|_| Type::Char),
+            just("I").map(|_| Type::Int),
+            just("J").map(|_| Type::Long),
+            just("F").map(|_| Type::Float),
+            just("D").map(|_| Type::Double),
+            type_name()
+                .delimited_by(just("L"), just(";"))
+                .map(|s| s.replace('/', "."))
+                .map(Type::Object),
+            t.delimited_by(just("["), empty())
+                .map(Box::new)
+                .map(Type::Array),
+        ))
+    })
+}
+
+fn type_name() -> impl CharParser<String> {
+    filter(|&c| c != '\t' && c != '\n' && c != '\r' && c != '\0' && c != '\\' && c != ';')
+        .repeated()
+        .at_least(1)
+        .collect()
+}
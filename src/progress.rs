@@ -0,0 +1,24 @@
+//! Progress reporting for slow work, such as downloading and parsing mappings.
+//!
+//! Progress is reported as `tracing` events with the [`PROGRESS_TARGET`] target and an `event`
+//! field naming what happened, so any subscriber can pick them up. The events are:
+//! - `download_started`, with the `kind` of mappings and the `url`
+//! - `download_finished`, with the `kind`, `url`, and size in `bytes`
+//! - `parse_started`, with the `kind` of input being parsed
+//! - `mapping_done`, with the number of `frames`, how many were `mapped`, and how many of those
+//!   were `ambiguous`, if known
+
+//!
+//! The phases of the work are also wrapped in spans with the [`TIMINGS_TARGET`] target, so their
+//! durations can be measured. The spans are:
+//! - `fetch_manifest`, for version manifests and other metadata, such as checksums
+//! - `download`, for downloading mappings into the cache
+//! - `parse_mappings`, for parsing fetched mappings
+//! - `build_mapper`, for indexing parsed mappings into a mapper
+//! - `map_frame`, for mapping a single frame
+
+/// The target of progress events.
+pub const PROGRESS_TARGET: &str = "stacked_portrayals::progress";
+
+/// The target of the spans for timing the phases of the work.
+pub const TIMINGS_TARGET: &str = "stacked_portrayals::timings";
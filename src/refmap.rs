@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use error_stack::{Report, ResultExt};
+use serde_json::Value;
+
+use crate::SPError;
+
+/// Member selectors from mixin refmaps, to resolve the selectors that mods were compiled to back
+/// to the ones their developers wrote.
+///
+/// Mixin errors quote the compiled selectors, e.g. `Lnet/minecraft/class_310;method_1574()V`,
+/// which mean little to the mod's developer. Plain class references are not resolved, since those
+/// also appear in frames, which are mapped as usual.
+#[derive(Debug, Default)]
+pub struct RefmapSelectors {
+    /// Compiled selectors to the original ones.
+    selectors: HashMap<String, String>,
+}
+
+impl RefmapSelectors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the selectors of a refmap, e.g. a mod's `modid.refmap.json`. The selectors of earlier
+    /// refmaps win if they conflict.
+    pub fn add_refmap(&mut self, json: &str) -> Result<(), Report<SPError>> {
+        let refmap: Value = serde_json::from_str(json)
+            .change_context(SPError::ParseError)
+            .attach_printable("Failed to parse refmap")?;
+        // The `data` tables are per obfuscation environment, `mappings` is the default one
+        let tables = refmap
+            .get("mappings")
+            .into_iter()
+            .chain(
+                refmap
+                    .get("data")
+                    .and_then(Value::as_object)
+                    .into_iter()
+                    .flat_map(|envs| envs.values()),
+            )
+            .filter_map(Value::as_object);
+        for mixins in tables {
+            for (original, compiled) in mixins
+                .values()
+                .filter_map(Value::as_object)
+                .flat_map(|selectors| selectors.iter())
+            {
+                let Some(compiled) = compiled.as_str() else {
+                    continue;
+                };
+                if compiled != original && is_member_selector(compiled) {
+                    self.selectors
+                        .entry(compiled.to_string())
+                        .or_insert_with(|| original.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.selectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selectors.is_empty()
+    }
+
+    /// Replace every compiled selector in the text with the original one.
+    pub fn resolve<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_empty() {
+            return text.into();
+        }
+        // Longest first, so a selector is never partially replaced by a shorter one
+        let mut found: Vec<_> = self
+            .selectors
+            .iter()
+            .filter(|(compiled, _)| text.contains(compiled.as_str()))
+            .collect();
+        if found.is_empty() {
+            return text.into();
+        }
+        found.sort_by_key(|(compiled, _)| std::cmp::Reverse(compiled.len()));
+        // A single pass, so the original selectors that were put in are never replaced again
+        let mut resolved = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            match found
+                .iter()
+                .find(|(compiled, _)| rest.starts_with(compiled.as_str()))
+            {
+                Some((compiled, original)) => {
+                    resolved.push_str(original);
+                    rest = &rest[compiled.len()..];
+                }
+                None => {
+                    resolved.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        resolved.into()
+    }
+}
+
+/// Check if a selector refers to a field or method, e.g. `Lfoo/Bar;baz()V` or `baz:I`, rather
+/// than just a class.
+fn is_member_selector(selector: &str) -> bool {
+    selector.contains(['(', ':'])
+        || selector
+            .split_once(';')
+            .is_some_and(|(_, member)| !member.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolved_selectors_are_not_replaced_again() {
+        let mut selectors = RefmapSelectors::new();
+        selectors
+            .add_refmap(
+                r#"{"mappings": {"a.Mixin": {
+                    "tick()V": "Lclass_1;method_1()V",
+                    "Lclass_1;method_1()V": "Lclass_22;method_22()V"
+                }}}"#,
+            )
+            .unwrap();
+        assert_eq!(
+            selectors.resolve("at Lclass_1;method_1()V and Lclass_22;method_22()V"),
+            "at tick()V and Lclass_1;method_1()V"
+        );
+    }
+}
@@ -0,0 +1,669 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use derive_more::Display;
+use error_stack::{Context, Report};
+use itertools::Itertools;
+
+use crate::mappings::MethodId;
+use crate::names::NamesType;
+use crate::stacktrace::{Cause, Frame, OmittedStyle, Stacktrace};
+
+/// Default packages folded by a bare `--fold`.
+pub const DEFAULT_FOLD: &[&str] = &[
+    "java.*",
+    "javax.*",
+    "jdk.*",
+    "sun.*",
+    "com.sun.*",
+    "io.netty.*",
+];
+
+#[derive(Debug, Display)]
+pub struct ClassPatternFromStrError;
+
+impl Context for ClassPatternFromStrError {}
+
+/// A class name pattern, either an exact name or a prefix ending in `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassPattern {
+    Exact(String),
+    Prefix(String),
+}
+
+impl ClassPattern {
+    pub fn matches(&self, class: &str) -> bool {
+        match self {
+            Self::Exact(name) => class == name,
+            Self::Prefix(prefix) => class.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+impl FromStr for ClassPattern {
+    type Err = Report<ClassPatternFromStrError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(Report::new(ClassPatternFromStrError).attach_printable("Empty pattern"));
+        }
+        Ok(match s.strip_suffix('*') {
+            Some(prefix) => Self::Prefix(prefix.to_string()),
+            None => Self::Exact(s.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Display)]
+pub struct FrameTemplateFromStrError;
+
+impl Context for FrameTemplateFromStrError {}
+
+/// A template for rendering frames, e.g. `{class}#{method} ({file}:{line})`.
+///
+/// Braces can be escaped by doubling them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Module,
+    Class,
+    Method,
+    File,
+    Line,
+    Candidates,
+    Confidence,
+    Original,
+}
+
+impl FrameTemplate {
+    pub fn render(&self, frame: &Frame) -> String {
+        let mut output = String::new();
+        self.write(&mut output, frame, &frame.class)
+            .expect("writing to a String can't fail");
+        output
+    }
+
+    /// Write the `frame`, with the `class` shown instead of its own.
+    fn write(&self, f: &mut impl std::fmt::Write, frame: &Frame, class: &str) -> std::fmt::Result {
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => f.write_str(text)?,
+                TemplatePart::Module => f.write_str(frame.module.as_deref().unwrap_or(""))?,
+                TemplatePart::Class => f.write_str(class)?,
+                TemplatePart::Method => f.write_str(&frame.method)?,
+                TemplatePart::File => f.write_str(&frame.file)?,
+                TemplatePart::Line => {
+                    if let Some(line) = frame.line {
+                        write!(f, "{}", line)?;
+                    }
+                }
+                TemplatePart::Candidates => write!(f, "{}", frame.candidates.len())?,
+                TemplatePart::Original => {
+                    if let Some(original) = &frame.original {
+                        write!(f, "{}", original)?;
+                    }
+                }
+                TemplatePart::Confidence => {
+                    if let Some(confidence) = frame.confidence {
+                        write!(f, "{}", confidence)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FrameTemplate {
+    type Err = Report<FrameTemplateFromStrError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(Report::new(FrameTemplateFromStrError)
+                                    .attach_printable(format!("Unclosed placeholder {{{}", name)))
+                            }
+                        }
+                    }
+                    let part = match name.as_str() {
+                        "module" => TemplatePart::Module,
+                        "class" => TemplatePart::Class,
+                        "method" => TemplatePart::Method,
+                        "file" => TemplatePart::File,
+                        "line" => TemplatePart::Line,
+                        "candidates" => TemplatePart::Candidates,
+                        "confidence" => TemplatePart::Confidence,
+                        "original" => TemplatePart::Original,
+                        _ => {
+                            return Err(Report::new(FrameTemplateFromStrError)
+                                .attach_printable(format!("Unknown placeholder {{{}}}", name)))
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                '}' => {
+                    return Err(Report::new(FrameTemplateFromStrError)
+                        .attach_printable("Unmatched } in template, use }} for a literal brace"))
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+}
+
+/// Options controlling how a [`Stacktrace`] is rendered as text.
+#[derive(Debug, Default)]
+pub struct RenderOptions {
+    /// If set, runs of frames with classes matching any of these patterns are folded.
+    pub fold: Option<Vec<ClassPattern>>,
+    /// If set, frames are rendered with this template instead of the standard `at ...` form.
+    pub frame_template: Option<FrameTemplate>,
+    /// If set, callers that have the original text should rewrite the mapped names in it instead
+    /// of rendering, keeping everything else as-is. See [`crate::rewrite`].
+    pub exact: bool,
+    /// If set, mapped methods in frames of the standard form link to their details.
+    pub links: Option<Links>,
+    /// If set, mapped frames of the standard form end with how confident their mapping is, e.g.
+    /// `[medium]`.
+    pub confidence: bool,
+    /// If set, mapped methods in frames of the standard form show the descriptor of each
+    /// candidate, instead of each distinct name once.
+    pub show_descriptors: bool,
+    /// If set, mapped frames of the standard form end with their names before mapping, e.g.
+    /// `[was: dyr.e]`.
+    pub annotate_original: bool,
+    /// If set, frames are rendered in the plain form IntelliJ's "Analyze Stacktrace" links to its
+    /// sources, see [`write_ide_frame`]. Other frame options are ignored.
+    pub ide: bool,
+    /// The separator between the candidates of ambiguous methods, `/` if unset.
+    pub candidate_separator: Option<String>,
+    /// If set, ambiguous methods show at most this many candidates, followed by how many more
+    /// there are, e.g. `tick/tickEntities (+3 more)`.
+    pub max_candidates: Option<usize>,
+    /// How the classes of frames and the types of exceptions are shown. Frames in the IDE form
+    /// are always fully qualified.
+    pub class_names: ClassNames,
+    /// If set, runs of frames in a cause that were already printed further up, identical once
+    /// mapped, are collapsed into `... n common frames omitted ...`.
+    pub collapse_common: bool,
+}
+
+/// How class names are shown when rendering, e.g. shorter for chat messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClassNames {
+    /// Fully qualified, e.g. `net.minecraft.client.Minecraft`.
+    #[default]
+    Qualified,
+    /// Without the package, e.g. `Minecraft`.
+    Simple,
+    /// With every package but the innermost abbreviated to its first letter, e.g.
+    /// `n.m.client.Minecraft`.
+    Abbreviated,
+}
+
+impl ClassNames {
+    /// Show the fully qualified `class` in this style.
+    pub fn apply<'a>(&self, class: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Qualified => class.into(),
+            Self::Simple => class.rsplit('.').next().unwrap_or(class).into(),
+            Self::Abbreviated => {
+                let parts = class.split('.').collect::<Vec<_>>();
+                if parts.len() <= 2 {
+                    return class.into();
+                }
+                let (packages, rest) = parts.split_at(parts.len() - 2);
+                packages
+                    .iter()
+                    .map(|p| p.chars().next().map_or(String::new(), String::from))
+                    .chain(rest.iter().map(|p| p.to_string()))
+                    .join(".")
+                    .into()
+            }
+        }
+    }
+}
+
+/// How mapped methods link to a [Linkie](https://linkie.shedaniel.dev) lookup of their details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Links {
+    pub style: LinkStyle,
+    /// The Linkie namespace to look names up in, e.g. `mojang`.
+    pub namespace: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// OSC 8 hyperlinks, which many terminals make clickable without showing the URL.
+    Hyperlink,
+    /// The URL after the frame.
+    Url,
+}
+
+impl Links {
+    /// Link to the details of names in `names` for the `version`.
+    pub fn new(style: LinkStyle, names: NamesType, version: impl Into<String>) -> Self {
+        let namespace = match names {
+            // Linkie shows intermediary names with Yarn's
+            NamesType::FabricIntermediary => "yarn",
+            NamesType::Mojang | NamesType::Obfuscated => "mojang",
+        };
+        Self {
+            style,
+            namespace: namespace.to_string(),
+            version: version.into(),
+        }
+    }
+
+    /// The URL of a Linkie search, e.g. for `Minecraft.tick`.
+    pub fn url(&self, search: &str) -> String {
+        format!(
+            "https://linkie.shedaniel.dev/mappings?namespace={}&version={}&search={}",
+            encode_query(&self.namespace),
+            encode_query(&self.version),
+            encode_query(search)
+        )
+    }
+}
+
+fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+impl RenderOptions {
+    /// The method of a frame in the standard form, listing the candidates of an ambiguous method
+    /// as these options say.
+    pub fn method_text<'a>(&self, frame: &'a Frame) -> Cow<'a, str> {
+        let plain = self.candidate_separator.is_none() && self.max_candidates.is_none();
+        if frame.candidates.is_empty() || (plain && !self.show_descriptors) {
+            return frame.method.as_str().into();
+        }
+        let candidates: Vec<String> = if self.show_descriptors {
+            frame
+                .candidates
+                .iter()
+                .map(|c| {
+                    format!(
+                        "{}{}",
+                        c.method.name,
+                        c.method.descriptor.internal_descriptor()
+                    )
+                })
+                // The same method may be in several classes
+                .dedup()
+                .collect()
+        } else {
+            // Overloads have the same name, which only needs to be shown once
+            frame
+                .candidates
+                .iter()
+                .map(|c| c.method.name.clone())
+                .dedup()
+                .collect()
+        };
+        let limit = self.max_candidates.unwrap_or(usize::MAX);
+        let mut text = candidates
+            .iter()
+            .take(limit)
+            .join(self.candidate_separator.as_deref().unwrap_or("/"));
+        if candidates.len() > limit {
+            text.push_str(&format!(" (+{} more)", candidates.len() - limit));
+        }
+        text.into()
+    }
+
+    /// Check if two mapped frames render the same.
+    fn same_frame(&self, a: &Frame, b: &Frame) -> bool {
+        a.module == b.module
+            && a.class == b.class
+            && a.file == b.file
+            && a.line == b.line
+            && a.suffix == b.suffix
+            && self.method_text(a) == self.method_text(b)
+    }
+
+    /// The length of the longest run at the start of `frames` that is also in one of the
+    /// `printed` traces.
+    fn common_run(&self, frames: &[Frame], printed: &[&[Frame]]) -> usize {
+        printed
+            .iter()
+            .flat_map(|trace| (0..trace.len()).map(move |start| &trace[start..]))
+            .map(|run| {
+                frames
+                    .iter()
+                    .zip(run)
+                    .take_while(|(a, b)| self.same_frame(a, b))
+                    .count()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_folded(&self, frame: &Frame) -> bool {
+        self.fold
+            .as_ref()
+            .is_some_and(|patterns| patterns.iter().any(|p| p.matches(&frame.class)))
+    }
+}
+
+/// Write a frame, without the `at `, in the plain form IntelliJ's "Analyze Stacktrace" links to
+/// its sources, e.g. `net.minecraft.client.Minecraft.run(Minecraft.java:123)`.
+///
+/// Ambiguous methods get a single candidate, as the IDE can't follow `tick/tickEntities`: one in
+/// the frame's own class if there is one, then the most confident, then the first by name, so the
+/// same trace always renders the same. Only the candidate's method name is used, as the frame's
+/// class is the one the code runs in, and the line belongs to it. Obfuscated frames only name
+/// `SourceFile`, which the IDE can't find, so the file is named after the outermost class.
+/// Suffixes are left out.
+pub fn write_ide_frame(f: &mut impl std::fmt::Write, frame: &Frame) -> std::fmt::Result {
+    if let Some(module) = &frame.module {
+        write!(f, "{}/", module)?;
+    }
+    let candidate = frame.candidates.iter().min_by_key(|c| {
+        (
+            c.class != frame.class,
+            Reverse(c.confidence),
+            &c.method.name,
+        )
+    });
+    let method = candidate.map_or(frame.method.as_str(), |c| c.method.name.as_str());
+    write!(f, "{}.{}(", frame.class, method)?;
+    if frame.file.contains('.') || frame.file == "Native Method" {
+        f.write_str(&frame.file)?;
+    } else {
+        let simple_class = frame.class.rsplit('.').next().unwrap_or(&frame.class);
+        let outer_class = simple_class.split('$').next().unwrap_or(simple_class);
+        write!(f, "{}.java", outer_class)?;
+    }
+    if let Some(line) = frame.line {
+        write!(f, ":{}", line)?;
+    }
+    f.write_str(")")
+}
+
+pub struct RenderedStacktrace<'a> {
+    stacktrace: &'a Stacktrace,
+    options: &'a RenderOptions,
+}
+
+impl<'a> RenderedStacktrace<'a> {
+    pub fn new(stacktrace: &'a Stacktrace, options: &'a RenderOptions) -> Self {
+        Self {
+            stacktrace,
+            options,
+        }
+    }
+}
+
+impl RenderedStacktrace<'_> {
+    fn write_frame(&self, f: &mut Formatter<'_>, frame: &Frame) -> std::fmt::Result {
+        if self.options.ide {
+            f.write_str("\tat ")?;
+            write_ide_frame(f, frame)?;
+            return writeln!(f);
+        }
+        let class = self.options.class_names.apply(&frame.class);
+        match &self.options.frame_template {
+            Some(template) => {
+                f.write_str("\t")?;
+                template.write(f, frame, &class)?;
+            }
+            None => {
+                let method = self.options.method_text(frame);
+                match (&self.options.links, frame.candidates.first()) {
+                    (Some(links), Some(candidate)) => Self::write_linked_frame(
+                        f,
+                        self.options,
+                        frame,
+                        &method,
+                        links,
+                        &candidate.method,
+                    )?,
+                    _ => {
+                        f.write_str("\tat ")?;
+                        frame.fmt_with_names(f, &class, &method)?;
+                    }
+                }
+            }
+        }
+        if let (true, None, Some(original)) = (
+            self.options.annotate_original,
+            &self.options.frame_template,
+            &frame.original,
+        ) {
+            write!(f, " [was: {}]", original)?;
+        }
+        if let (true, None, Some(confidence)) = (
+            self.options.confidence,
+            &self.options.frame_template,
+            frame.confidence,
+        ) {
+            write!(f, " [{}]", confidence)?;
+        }
+        writeln!(f)
+    }
+
+    fn write_linked_frame(
+        f: &mut Formatter<'_>,
+        options: &RenderOptions,
+        frame: &Frame,
+        method: &str,
+        links: &Links,
+        candidate: &MethodId,
+    ) -> std::fmt::Result {
+        let simple_class = frame.class.rsplit('.').next().unwrap_or(&frame.class);
+        let url = links.url(&format!("{}.{}", simple_class, candidate.name));
+        let class = options.class_names.apply(&frame.class);
+        match links.style {
+            LinkStyle::Hyperlink => {
+                f.write_str("\tat ")?;
+                if let Some(module) = &frame.module {
+                    write!(f, "{}/", module)?;
+                }
+                write!(
+                    f,
+                    "\x1b]8;;{}\x1b\\{}.{}\x1b]8;;\x1b\\({}",
+                    url, class, method, frame.file
+                )?;
+                if let Some(line) = frame.line {
+                    write!(f, ":{}", line)?;
+                }
+                write!(f, ")")?;
+                f.write_str(frame.suffix.as_deref().unwrap_or(""))
+            }
+            LinkStyle::Url => {
+                f.write_str("\tat ")?;
+                frame.fmt_with_names(f, &class, method)?;
+                write!(f, " <{}>", url)
+            }
+        }
+    }
+
+    fn write_header(
+        &self,
+        f: &mut Formatter<'_>,
+        ty: &impl Display,
+        message: &Option<String>,
+    ) -> std::fmt::Result {
+        f.write_str(&self.options.class_names.apply(&ty.to_string()))?;
+        if let Some(message) = message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+
+    fn write_trace<'a>(
+        &self,
+        f: &mut Formatter<'_>,
+        st: &'a Stacktrace,
+        printed: &mut Vec<&'a [Frame]>,
+    ) -> std::fmt::Result {
+        self.write_header(f, &st.ty, &st.message)?;
+        writeln!(f)?;
+        let mut i = 0;
+        while i < st.frames.len() {
+            let frame = &st.frames[i];
+            if self.options.collapse_common {
+                let common = self.options.common_run(&st.frames[i..], printed);
+                // Like folding, collapsing a single frame would only make the output longer.
+                if common > 1 {
+                    writeln!(f, "\t... {} common frames omitted ...", common)?;
+                    i += common;
+                    continue;
+                }
+            }
+            if !self.options.is_folded(frame) {
+                self.write_frame(f, frame)?;
+                i += 1;
+                continue;
+            }
+            let folded = st.frames[i..]
+                .iter()
+                .take_while(|f| self.options.is_folded(f))
+                .count();
+            // Folding a single frame would only make the output longer.
+            if folded == 1 {
+                self.write_frame(f, frame)?;
+            } else {
+                writeln!(f, "\t... {} library frames ...", folded)?;
+            }
+            i += folded;
+        }
+        if let Some(omitted) = st.omitted_frames {
+            match st.omitted_style {
+                OmittedStyle::More => writeln!(f, "\t... {} more", omitted)?,
+                OmittedStyle::CommonFramesOmitted => {
+                    writeln!(f, "\t... {} common frames omitted", omitted)?
+                }
+            }
+        }
+        printed.push(&st.frames);
+        if let Some(cause) = &st.cause {
+            write!(f, "Caused by: ")?;
+            match &**cause {
+                Cause::Trace(trace) => self.write_trace(f, trace, printed)?,
+                Cause::Circular { ty, message, .. } => {
+                    write!(f, "[CIRCULAR REFERENCE: ")?;
+                    self.write_header(f, ty, message)?;
+                    writeln!(f, "]")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for RenderedStacktrace<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(thread) = &self.stacktrace.thread {
+            write!(f, "Exception in thread \"{}\" ", thread)?;
+        }
+        self.write_trace(f, self.stacktrace, &mut Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mappings::{Confidence, Descriptor, Type};
+    use crate::stacktrace::{parse_frame, Candidate};
+
+    #[test]
+    fn template_placeholders() {
+        let template: FrameTemplate = "{class}#{method} {{{line}}}".parse().unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                TemplatePart::Class,
+                TemplatePart::Literal("#".into()),
+                TemplatePart::Method,
+                TemplatePart::Literal(" {".into()),
+                TemplatePart::Line,
+                TemplatePart::Literal("}".into()),
+            ]
+        );
+    }
+
+    fn candidate(class: &str, name: &str, confidence: Confidence) -> Candidate {
+        Candidate {
+            method: MethodId {
+                name: name.to_string(),
+                descriptor: Descriptor {
+                    params: vec![],
+                    return_type: Type::Void,
+                },
+            },
+            class: class.to_string(),
+            confidence,
+            provenance: "mojang".to_string(),
+        }
+    }
+
+    fn ide_frame(frame: &Frame) -> String {
+        let mut output = String::new();
+        write_ide_frame(&mut output, frame).unwrap();
+        output
+    }
+
+    #[test]
+    fn ide_frame_prefers_own_class() {
+        let mut frame = parse_frame("\tat net.minecraft.Foo.a(SourceFile:12)").unwrap();
+        frame.candidates = vec![
+            candidate("net.minecraft.Bar", "aaa", Confidence::Medium),
+            candidate("net.minecraft.Foo", "tick", Confidence::Medium),
+        ];
+        assert_eq!(ide_frame(&frame), "net.minecraft.Foo.tick(Foo.java:12)");
+    }
+
+    #[test]
+    fn ide_frame_keeps_own_class() {
+        let mut frame = parse_frame("\tat net.minecraft.Foo$Inner.a(SourceFile:12)").unwrap();
+        frame.candidates = vec![candidate("net.minecraft.Bar", "tick", Confidence::Low)];
+        assert_eq!(
+            ide_frame(&frame),
+            "net.minecraft.Foo$Inner.tick(Foo.java:12)"
+        );
+    }
+
+    #[test]
+    fn template_unclosed_placeholder() {
+        assert!("{class} {class".parse::<FrameTemplate>().is_err());
+        assert!("{class".parse::<FrameTemplate>().is_err());
+    }
+}
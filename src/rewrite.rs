@@ -0,0 +1,98 @@
+use std::iter::once;
+
+use crate::mappings::Type;
+use crate::stacktrace::{Cause, Frame, HeaderSpans, Span, Stacktrace};
+
+/// Rewrite the text a stacktrace was parsed from with the names of its mapped form, keeping
+/// everything else byte-for-byte.
+///
+/// Only the parts that were parsed from `input` are rewritten, so `mapped` must come from parsing
+/// exactly this text.
+pub fn rewrite_stacktrace(input: &str, mapped: &Stacktrace) -> String {
+    let mut edits = Vec::new();
+    trace_edits(mapped, &mut edits);
+    apply_edits(input, edits)
+}
+
+/// Rewrite the text a single frame was parsed from, like [`rewrite_stacktrace`].
+pub fn rewrite_frame(input: &str, mapped: &Frame) -> String {
+    let mut edits = Vec::new();
+    frame_edits(mapped, &mut edits);
+    apply_edits(input, edits)
+}
+
+struct Edit {
+    span: Span,
+    replacement: String,
+}
+
+fn trace_edits(trace: &Stacktrace, edits: &mut Vec<Edit>) {
+    if let Some(spans) = &trace.spans {
+        header_edits(&trace.ty, trace.message.as_deref(), spans, edits);
+    }
+    for frame in &trace.frames {
+        frame_edits(frame, edits);
+    }
+    match trace.cause.as_deref() {
+        Some(Cause::Trace(cause)) => trace_edits(cause, edits),
+        Some(Cause::Circular {
+            ty,
+            message,
+            spans: Some(spans),
+        }) => header_edits(ty, message.as_deref(), spans, edits),
+        _ => {}
+    }
+}
+
+fn header_edits(ty: &Type, message: Option<&str>, spans: &HeaderSpans, edits: &mut Vec<Edit>) {
+    edits.push(Edit {
+        span: spans.ty.clone(),
+        replacement: ty.to_string(),
+    });
+    if let (Some(message), Some(span)) = (message, &spans.message) {
+        edits.push(Edit {
+            span: span.clone(),
+            replacement: message.to_string(),
+        });
+    }
+}
+
+fn frame_edits(frame: &Frame, edits: &mut Vec<Edit>) {
+    let Some(spans) = &frame.spans else {
+        return;
+    };
+    edits.extend([
+        Edit {
+            span: spans.class.clone(),
+            replacement: frame.class.clone(),
+        },
+        Edit {
+            span: spans.method.clone(),
+            replacement: frame.method.clone(),
+        },
+        Edit {
+            span: spans.file.clone(),
+            replacement: frame.file.clone(),
+        },
+    ]);
+}
+
+fn apply_edits(input: &str, mut edits: Vec<Edit>) -> String {
+    // Spans count characters, so find where each one starts in bytes
+    let offsets: Vec<usize> = input
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(once(input.len()))
+        .collect();
+    edits.sort_by_key(|e| e.span.start);
+    let mut output = String::with_capacity(input.len());
+    let mut copied = 0;
+    for edit in edits {
+        let start = offsets[edit.span.start];
+        output.push_str(&input[copied..start]);
+        output.push_str(&edit.replacement);
+        copied = offsets[edit.span.end];
+    }
+    output.push_str(&input[copied..]);
+    output
+}
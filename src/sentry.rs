@@ -0,0 +1,113 @@
+use error_stack::{Report, ResultExt};
+use serde_json::Value;
+
+use crate::mappings::{MapSelf, MethodMapper};
+use crate::messages::map_message;
+use crate::stacktrace::Frame;
+use crate::SPError;
+
+/// Check if the input is a Sentry event with exceptions.
+pub fn is_sentry_event(input: &str) -> bool {
+    serde_json::from_str::<Value>(input).is_ok_and(|v| exceptions(&v).is_some())
+}
+
+/// Map the exceptions in a Sentry event, re-emitting the whole event.
+///
+/// Java SDKs put the class name in the frame `module`, the method in `function`, and the file in
+/// `filename`. The exception class is split into `module` (the package) and `type`.
+pub fn map_sentry_event(
+    input: &str,
+    mapper: &impl MethodMapper,
+) -> Result<String, Report<SPError>> {
+    let mut event: Value = serde_json::from_str(input)
+        .change_context(SPError::ParseError)
+        .attach_printable("Failed to parse Sentry event")?;
+    let exceptions = exceptions_mut(&mut event).ok_or_else(|| {
+        Report::new(SPError::ParseError).attach_printable("Sentry event has no exceptions")
+    })?;
+    for exception in exceptions {
+        map_exception(exception, mapper);
+    }
+    let mut output = serde_json::to_string_pretty(&event)
+        .change_context(SPError::ParseError)
+        .attach_printable("Failed to write Sentry event")?;
+    output.push('\n');
+    Ok(output)
+}
+
+/// Exceptions are usually in `exception.values`, but older events have a plain list.
+fn exceptions(event: &Value) -> Option<&Vec<Value>> {
+    let exception = event.get("exception")?;
+    exception.get("values").unwrap_or(exception).as_array()
+}
+
+fn exceptions_mut(event: &mut Value) -> Option<&mut Vec<Value>> {
+    let exception = event.get_mut("exception")?;
+    if exception.get("values").is_some() {
+        exception.get_mut("values")?.as_array_mut()
+    } else {
+        exception.as_array_mut()
+    }
+}
+
+fn map_exception(exception: &mut Value, mapper: &impl MethodMapper) {
+    let package = exception.get("module").and_then(Value::as_str);
+    let class = exception
+        .get("type")
+        .and_then(Value::as_str)
+        .map(|ty| match package {
+            Some(package) => format!("{}.{}", package, ty),
+            None => ty.to_string(),
+        });
+    if let Some(class) = &class {
+        if let Some(mapped) = mapper.map_class(class) {
+            let (package, ty) = match mapped.rsplit_once('.') {
+                Some((package, ty)) => (Some(package), ty),
+                None => (None, mapped),
+            };
+            exception["type"] = ty.into();
+            if let Some(package) = package {
+                exception["module"] = package.into();
+            }
+        }
+    }
+    if let Some(value) = exception.get_mut("value") {
+        if let Some(message) = value.as_str() {
+            let class = class.as_deref().unwrap_or_default();
+            *value = map_message(class, message.to_string(), mapper).into();
+        }
+    }
+    let frames = exception
+        .get_mut("stacktrace")
+        .and_then(|s| s.get_mut("frames"))
+        .and_then(Value::as_array_mut);
+    for frame in frames.into_iter().flatten() {
+        map_frame(frame, mapper);
+    }
+}
+
+fn map_frame(frame: &mut Value, mapper: &impl MethodMapper) {
+    let str_field = |name: &str| frame.get(name).and_then(Value::as_str).map(String::from);
+    let (Some(class), Some(method)) = (str_field("module"), str_field("function")) else {
+        return;
+    };
+    let mapped = Frame {
+        module: None,
+        class,
+        method,
+        file: str_field("filename").unwrap_or_default(),
+        line: None,
+        suffix: None,
+        jar: None,
+        candidates: Vec::new(),
+        confidence: None,
+        original: None,
+        spans: None,
+    }
+    .map_self(mapper);
+    frame["module"] = mapped.class.into();
+    frame["function"] = mapped.method.into();
+    if frame.get("filename").is_some() {
+        frame["filename"] = mapped.file.into();
+    }
+}
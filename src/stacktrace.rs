@@ -1,80 +1,393 @@
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
 use chumsky::error::Simple;
 use chumsky::prelude::end;
-use chumsky::primitive::just;
+use chumsky::primitive::{filter, just};
+use chumsky::recursive::recursive;
 use chumsky::text::whitespace;
 use chumsky::Parser;
 use error_stack::Report;
 use itertools::Itertools;
+use sha1::{Digest, Sha1};
 
-use crate::mappings::{MapSelf, MapSelfOnlyClass, MethodMapper, Type};
+use crate::mappings::{
+    ClassMapper, Confidence, EitherMapper, MapSelf, MapSelfOnlyClass, MethodId, MethodMapper, Type,
+};
+use crate::messages::map_message;
 use crate::parsing::{
-    eol, handle_errors, inline_whitespace, jtype, parse_recovery_debuggable, u32_digits, CharParser,
+    eol, handle_errors, inline_whitespace, is_java_identifier_part, jtype,
+    parse_recovery_debuggable, u32_digits, CharParser,
 };
+use crate::progress::TIMINGS_TARGET;
+use crate::render::{RenderOptions, RenderedStacktrace};
 use crate::SPError;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stacktrace {
+    /// The thread name, if the trace was printed by the default uncaught exception handler.
+    pub thread: Option<String>,
     pub ty: Type,
-    pub message: String,
+    /// The exception message, absent if the header had no `: message` part.
+    pub message: Option<String>,
     pub frames: Vec<Frame>,
+    /// The number of frames shared with the enclosing trace, from the `... n more` line.
+    pub omitted_frames: Option<u32>,
+    /// How the line with [`omitted_frames`](Self::omitted_frames) was worded, so it's rendered
+    /// the same way.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub omitted_style: OmittedStyle,
+    pub cause: Option<Box<Cause>>,
+    /// Where the header was found, if this was parsed from text.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub spans: Option<HeaderSpans>,
+}
+
+/// The wording of the line that ends a trace with the number of frames it shares with the
+/// enclosing trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OmittedStyle {
+    /// `... n more`, as printed by the JVM.
+    #[default]
+    More,
+    /// `... n common frames omitted`, as printed by logback.
+    CommonFramesOmitted,
+}
+
+/// A range of characters (not bytes) in the text that was parsed.
+pub type Span = Range<usize>;
+
+/// Where the parts of a trace header were found in the parsed text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderSpans {
+    pub ty: Span,
+    pub message: Option<Span>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Cause {
+    Trace(Stacktrace),
+    /// A cause that was already printed further up, rendered as `[CIRCULAR REFERENCE: ...]`.
+    Circular {
+        ty: Type,
+        message: Option<String>,
+        /// Where the reference was found, if this was parsed from text.
+        #[cfg_attr(feature = "serde", serde(skip))]
+        spans: Option<HeaderSpans>,
+    },
+}
+
+impl MapSelf for Cause {
+    fn map_self(self, mapper: &impl MethodMapper) -> Self {
+        match self {
+            Self::Trace(trace) => Self::Trace(trace.map_self(mapper)),
+            Self::Circular { ty, message, spans } => Self::Circular {
+                message: message.map(|m| map_message(&ty.to_string(), m, mapper)),
+                ty: ty.map_self(mapper),
+                spans,
+            },
+        }
+    }
+}
+
+impl Stacktrace {
+    pub fn render<'a>(&'a self, options: &'a RenderOptions) -> RenderedStacktrace<'a> {
+        RenderedStacktrace::new(self, options)
+    }
+
+    /// Check if this trace already uses the names the `mapper` maps to, i.e. none of its classes
+    /// can be mapped but some are already mapped names. For example, Paper servers since 1.20.5
+    /// run with Mojang names, so their traces need no mapping.
+    pub fn uses_target_names(&self, mapper: &EitherMapper) -> bool {
+        let frames = self.all_frames().filter(|f| !f.is_generated());
+        let mut any_target = false;
+        for frame in frames {
+            if mapper.map_class(&frame.class).is_some() {
+                return false;
+            }
+            any_target |= mapper.is_target_class(&frame.class);
+        }
+        any_target
+    }
+
+    /// Count the frames of this trace and its causes, and how they were mapped.
+    pub fn stats(&self) -> MappingStats {
+        let mut stats = MappingStats::default();
+        for frame in self.all_frames() {
+            stats.frames += 1;
+            if !frame.candidates.is_empty() {
+                stats.mapped += 1;
+            }
+            if frame.candidates.len() > 1 {
+                stats.ambiguous += 1;
+            }
+        }
+        stats
+    }
+
+    /// The innermost cause of this trace, or the trace itself. Circular references don't count,
+    /// as their frames were printed further up.
+    pub fn root_cause(&self) -> &Stacktrace {
+        let mut trace = self;
+        while let Some(Cause::Trace(cause)) = trace.cause.as_deref() {
+            trace = cause;
+        }
+        trace
+    }
+
+    /// A stable hash of the type and the top `frames` frames of the root cause, to tell whether
+    /// two reports are of the same crash. Fingerprint mapped traces, so reports from different
+    /// versions can match.
+    ///
+    /// Messages and line numbers are left out, as they often differ between reports of the same
+    /// crash, and so are frames in classes generated at runtime.
+    pub fn fingerprint(&self, frames: usize) -> String {
+        let root = self.root_cause();
+        let mut hasher = Sha1::new();
+        hasher.update(root.ty.to_string());
+        for frame in root
+            .frames
+            .iter()
+            .filter(|f| !f.is_generated())
+            .take(frames)
+        {
+            hasher.update(format!("\n{}.{}", frame.class, frame.method));
+        }
+        // As short as a Git commit, plenty to keep the crashes of a modpack apart
+        format!("{:x}", hasher.finalize())[..12].to_string()
+    }
+
+    /// The frames of this trace and its causes.
+    pub fn all_frames(&self) -> impl Iterator<Item = &Frame> {
+        std::iter::successors(Some(self), |t| match t.cause.as_deref() {
+            Some(Cause::Trace(cause)) => Some(cause),
+            _ => None,
+        })
+        .flat_map(|t| &t.frames)
+    }
+}
+
+/// How many frames of a trace were mapped, see [`Stacktrace::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MappingStats {
+    pub frames: usize,
+    /// Frames with at least one candidate mapping for their method.
+    pub mapped: usize,
+    /// Frames with more than one candidate mapping for their method.
+    pub ambiguous: usize,
 }
 
 impl Display for Stacktrace {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}: {}", self.ty, self.message)?;
-        for frame in &self.frames {
-            writeln!(f, "\tat {}", frame)?;
-        }
-        Ok(())
+        Display::fmt(&self.render(&RenderOptions::default()), f)
     }
 }
 
 impl MapSelf for Stacktrace {
     fn map_self(self, mapper: &impl MethodMapper) -> Self {
         Self {
+            thread: self.thread,
+            message: self
+                .message
+                .map(|m| map_message(&self.ty.to_string(), m, mapper)),
             ty: self.ty.map_self(mapper),
-            message: self.message,
             frames: self
                 .frames
                 .into_iter()
                 .map(|f| f.map_self(mapper))
                 .collect(),
+            omitted_frames: self.omitted_frames,
+            omitted_style: self.omitted_style,
+            cause: self.cause.map(|c| Box::new(c.map_self(mapper))),
+            spans: self.spans,
         }
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Frame {
     pub module: Option<String>,
     pub class: String,
     pub method: String,
     pub file: String,
     pub line: Option<u32>,
+    /// Unknown text after the location, e.g. from async frameworks, kept as-is. A `~[...]` suffix
+    /// with the JAR of the class is kept in [`jar`](Self::jar) instead.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub suffix: Option<String>,
+    /// The contents of the `~[...]` suffix printed by Log4j, naming the JAR of the class and its
+    /// version, e.g. `examplemod-1.0.jar:?`. It isn't rendered.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub jar: Option<String>,
+    /// The candidate mappings for the method, empty if it wasn't mapped.
+    pub candidates: Vec<Candidate>,
+    /// How confident the mapping of the method is, if it was mapped.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub confidence: Option<Confidence>,
+    /// The names before mapping, if the class or method was mapped.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub original: Option<OriginalNames>,
+    /// Where the frame's parts were found, if this was parsed from text.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub spans: Option<FrameSpans>,
+}
+
+/// A candidate mapping for the method of a [`Frame`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candidate {
+    /// The mapped name and descriptor of the method.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub method: MethodId,
+    /// The mapped class the method is in. For a method found by its name alone, this may not be
+    /// the class of the frame.
+    pub class: String,
+    /// How confident the mapping is, the same for every candidate of a frame.
+    pub confidence: Confidence,
+    /// The mappings the candidate came from, see [`MethodMapper::provenance`].
+    pub provenance: String,
+}
+
+/// The names of a [`Frame`] before it was mapped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OriginalNames {
+    pub class: String,
+    pub method: String,
+}
+
+impl Display for OriginalNames {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.class, self.method)
+    }
+}
+
+/// Where the parts of a frame were found in the parsed text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSpans {
+    pub class: Span,
+    pub method: Span,
+    pub file: Span,
 }
 
 impl Display for Frame {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_with_names(f, &self.class, &self.method)
+    }
+}
+
+impl Frame {
+    /// Write the frame like its [`Display`] form, but with another `class` and `method`.
+    pub(crate) fn fmt_with_names(
+        &self,
+        f: &mut Formatter<'_>,
+        class: &str,
+        method: &str,
+    ) -> std::fmt::Result {
         if let Some(module) = &self.module {
             write!(f, "{}/", module)?;
         }
-        write!(f, "{}.{}({}", self.class, self.method, self.file)?;
+        write!(f, "{}.{}({}", class, method, self.file)?;
         if let Some(line) = self.line {
             write!(f, ":{}", line)?;
         }
-        write!(f, ")")
+        write!(f, ")")?;
+        if let Some(suffix) = &self.suffix {
+            f.write_str(suffix)?;
+        }
+        Ok(())
     }
 }
 
+/// Prefixes of classes generated at runtime by reflection and proxies.
+const GENERATED_CLASS_PREFIXES: &[&str] = &[
+    "jdk.internal.reflect.GeneratedMethodAccessor",
+    "jdk.internal.reflect.GeneratedConstructorAccessor",
+    "jdk.internal.reflect.GeneratedSerializationConstructorAccessor",
+    "sun.reflect.GeneratedMethodAccessor",
+    "sun.reflect.GeneratedConstructorAccessor",
+    "sun.reflect.GeneratedSerializationConstructorAccessor",
+    "com.sun.proxy.$Proxy",
+    "java.lang.invoke.LambdaForm$",
+];
+
+impl Frame {
+    /// Check if this frame is in a class generated at runtime, i.e. a reflection accessor, a
+    /// proxy, or a hidden class. These never have mappings.
+    pub fn is_generated(&self) -> bool {
+        GENERATED_CLASS_PREFIXES
+            .iter()
+            .any(|p| self.class.starts_with(p))
+            // Hidden classes, e.g. lambdas and lambda forms
+            || self.class.contains("/0x")
+            // Proxies in named modules, e.g. `jdk.proxy2.$Proxy17`
+            || self.class.contains(".$Proxy")
+    }
+}
+
+/// Map a nested class the mappings don't have, e.g. the anonymous `abc$1` of the obfuscated
+/// `abc`, by its innermost enclosing class that they do have.
+fn map_nested_class(class: &str, mapper: &impl MethodMapper) -> Option<String> {
+    let mut outer = class;
+    while let Some((enclosing, _)) = outer.rsplit_once('$') {
+        if let Some(mapped) = mapper.map_class(enclosing) {
+            return Some(format!("{}{}", mapped, &class[enclosing.len()..]));
+        }
+        outer = enclosing;
+    }
+    None
+}
+
 impl MapSelf for Frame {
     fn map_self(self, mapper: &impl MethodMapper) -> Self {
-        let methods = mapper.map_method(&self.class.to_string(), &self.method, None);
-        let method = if methods.is_empty() {
+        if self.is_generated() {
+            return self;
+        }
+        let _span = tracing::info_span!(target: TIMINGS_TARGET, "map_frame").entered();
+        let (candidates, confidence) =
+            mapper.map_method_with_confidence(&self.class.to_string(), &self.method, None);
+        let provenance = mapper.provenance(&self.class);
+        let mut candidates: Vec<Candidate> = candidates
+            .into_iter()
+            .map(|(class, method)| Candidate {
+                method: method.clone(),
+                class: class.to_string(),
+                confidence,
+                provenance: provenance.clone(),
+            })
+            .collect();
+        // Chained mappers often reach the same method through several paths
+        candidates.sort_by_cached_key(|c| {
+            (
+                c.method.name.clone(),
+                c.method.descriptor.internal_descriptor(),
+                c.class.clone(),
+            )
+        });
+        candidates.dedup();
+        let confidence = (!candidates.is_empty()).then_some(confidence);
+        let class = mapper
+            .map_class(&self.class)
+            .map(String::from)
+            .or_else(|| map_nested_class(&self.class, mapper));
+        // Keep the first names if the frame is mapped again
+        let original = match self.original {
+            None if class.is_some() || !candidates.is_empty() => Some(OriginalNames {
+                class: self.class.clone(),
+                method: self.method.clone(),
+            }),
+            original => original,
+        };
+        let method = if candidates.is_empty() {
             self.method
         } else {
-            methods.into_iter().map(|(_, m)| &m.name).join("/")
+            // Overloads have the same name, which only needs to be shown once
+            candidates.iter().map(|c| &c.method.name).dedup().join("/")
         };
         let mapped_file = self.file.split_once('.').and_then(|(name, ext)| {
             let as_class_name: Cow<str> = match self.class.rsplit_once('.') {
@@ -95,12 +408,16 @@ impl MapSelf for Frame {
         });
         Self {
             module: self.module,
-            class: mapper
-                .map_class(&self.class)
-                .map_or(self.class, |c| c.into()),
+            class: class.unwrap_or(self.class),
             method,
             file: mapped_file.unwrap_or(self.file),
             line: self.line,
+            suffix: self.suffix,
+            jar: self.jar,
+            candidates,
+            confidence,
+            original,
+            spans: self.spans,
         }
     }
 }
@@ -110,43 +427,184 @@ pub fn parse_stacktrace(input: &str) -> Result<Stacktrace, Report<SPError>> {
     handle_errors(input, res, "Failed to parse stacktrace")
 }
 
+/// Parse a single `at ...` frame line, which may be missing its line ending.
+pub fn parse_frame(line: &str) -> Option<Frame> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    frame()
+        .then_ignore(end())
+        .parse(format!("{}\n", line).as_str())
+        .ok()
+}
+
+/// Map frames one at a time as they are consumed, e.g. frames parsed line by line from a huge log
+/// with [`parse_frame`], without collecting them into a [`Stacktrace`] first.
+pub fn map_frames<'a, M: MethodMapper>(
+    frames: impl IntoIterator<Item = Frame> + 'a,
+    mapper: &'a M,
+) -> impl Iterator<Item = Frame> + 'a {
+    frames.into_iter().map(move |frame| frame.map_self(mapper))
+}
+
 fn stacktrace() -> impl CharParser<Stacktrace> {
+    thread_prefix()
+        .or_not()
+        .then(enclosed_stacktrace())
+        .then_ignore(whitespace().then(end()))
+        .map(|(thread, stacktrace)| Stacktrace {
+            thread,
+            ..stacktrace
+        })
+}
+
+/// A stacktrace without the thread prefix, which is only printed for the outermost trace.
+fn enclosed_stacktrace() -> impl CharParser<Stacktrace> {
+    recursive(|stacktrace| {
+        header()
+            .then(frame().repeated())
+            .then(omitted_frames().or_not())
+            .then(
+                just("Caused by: ")
+                    .ignore_then(
+                        circular_reference()
+                            .or(stacktrace.map(Cause::Trace))
+                            .labelled("cause"),
+                    )
+                    .or_not(),
+            )
+            .map(
+                |((((ty, message, spans), frames), omitted), cause)| Stacktrace {
+                    thread: None,
+                    ty,
+                    message,
+                    frames,
+                    omitted_frames: omitted.map(|(count, _)| count),
+                    omitted_style: omitted.map_or_else(OmittedStyle::default, |(_, style)| style),
+                    cause: cause.map(Box::new),
+                    spans: Some(spans),
+                },
+            )
+    })
+}
+
+fn header() -> impl CharParser<(Type, Option<String>, HeaderSpans)> {
     jtype()
-        .map(Type::from_source_name)
+        .map_with_span(|ty, span| (Type::from_source_name(ty), span))
         .labelled("type")
-        .then_ignore(just(": "))
-        .then(eol().not().repeated().collect().labelled("message"))
+        .then(
+            just(": ")
+                .ignore_then(
+                    eol()
+                        .not()
+                        .repeated()
+                        .collect::<String>()
+                        .map_with_span(|message, span| (message, span))
+                        .labelled("message"),
+                )
+                .or_not(),
+        )
         .then_ignore(eol())
-        .then(frame().repeated())
-        .then_ignore(whitespace().then(end()))
-        .map(|((ty, message), frames)| Stacktrace {
-            ty,
-            message,
-            frames,
+        .map(|((ty, ty_span), message)| {
+            let (message, message_span) = message.unzip();
+            let spans = HeaderSpans {
+                ty: ty_span,
+                message: message_span,
+            };
+            (ty, message, spans)
+        })
+}
+
+fn omitted_frames() -> impl CharParser<(u32, OmittedStyle)> {
+    inline_whitespace()
+        .ignore_then(just("... "))
+        .ignore_then(u32_digits().labelled("omitted frame count"))
+        .then(
+            just(" more")
+                .to(OmittedStyle::More)
+                .or(just(" common frames omitted").to(OmittedStyle::CommonFramesOmitted)),
+        )
+        .then_ignore(eol())
+}
+
+#[allow(clippy::result_large_err)]
+fn circular_reference() -> impl CharParser<Cause> {
+    just("[CIRCULAR REFERENCE: ")
+        .ignore_then(
+            eol()
+                .not()
+                .repeated()
+                .collect::<String>()
+                .map_with_span(|reference, span| (reference, span)),
+        )
+        .then_ignore(eol())
+        .try_map(|(reference, reference_span), span| {
+            // The message may contain `]`, so only the last one is the end of the marker
+            let header = reference
+                .strip_suffix(']')
+                .ok_or_else(|| Simple::custom(span, "unterminated circular reference"))?;
+            let (ty, message) = match header.split_once(": ") {
+                Some((ty, message)) => (ty, Some(message.to_string())),
+                None => (header, None),
+            };
+            let ty_end = reference_span.start + ty.chars().count();
+            let spans = HeaderSpans {
+                ty: reference_span.start..ty_end,
+                message: message
+                    .as_ref()
+                    .map(|m| ty_end + 2..ty_end + 2 + m.chars().count()),
+            };
+            Ok(Cause::Circular {
+                ty: Type::from_source_name(ty.to_string()),
+                message,
+                spans: Some(spans),
+            })
         })
+        .labelled("circular reference")
+}
+
+/// The `Exception in thread "name" ` prefix printed by the default uncaught exception handler.
+fn thread_prefix() -> impl CharParser<String> {
+    just("Exception in thread \"")
+        .ignore_then(filter(|&c| c != '"' && c != '\n').repeated().collect())
+        .then_ignore(just("\" "))
+        .labelled("thread name")
 }
 
+#[allow(clippy::result_large_err)]
 fn frame() -> impl CharParser<Frame> {
     inline_whitespace()
         .ignore_then(just("at "))
-        .ignore_then(jtype().labelled("module").then_ignore(just("/")).or_not())
-        .then(
-            // This is a little tricky, since there is no clear delimiter between the class and the method.
-            // Use jtype, which will read the method too, and split it on the last '.'.
-            jtype()
-                .labelled("class+method")
-                .try_map(|class_method, span| {
-                    let last_dot = class_method
-                        .rfind('.')
-                        .ok_or_else(|| Simple::custom(span, "no class name found in stacktrace"))?;
-                    let (class, method) = class_method.split_at(last_dot);
-                    let method = &method[1..];
-                    Ok((class.to_string(), method.to_string()))
-                }),
+        .ignore_then(
+            // This is a little tricky, since there is no clear delimiter between the class and the method,
+            // and hidden classes contain a `/` just like the module prefix does.
+            // Read everything up to the location and split it up afterwards.
+            filter(|&c| {
+                is_java_identifier_part(c)
+                    || matches!(c, '.' | '[' | ']' | '-' | '/' | '@' | '<' | '>')
+            })
+            .repeated()
+            .at_least(1)
+            .collect::<String>()
+            .labelled("module+class+method")
+            .try_map(|location, span: Span| {
+                let (module, class, method) = split_frame_location(&location).ok_or_else(|| {
+                    Simple::custom(span.clone(), "no class name found in stacktrace")
+                })?;
+                let class_start = span.start + module.as_ref().map_or(0, |m| m.chars().count() + 1);
+                let class_end = class_start + class.chars().count();
+                let spans = (
+                    class_start..class_end,
+                    class_end + 1..class_end + 1 + method.chars().count(),
+                );
+                Ok(((module, class, method), spans))
+            }),
         )
         .then(
-            // jtype is basically good for this too
-            jtype()
+            // jtype is basically good for this too, apart from the special locations
+            just("Native Method")
+                .or(just("Unknown Source"))
+                .map(String::from)
+                .or(jtype())
+                .map_with_span(|file, span| (file, span))
                 .labelled("file")
                 .then(
                     just(":")
@@ -155,19 +613,86 @@ fn frame() -> impl CharParser<Frame> {
                 )
                 .delimited_by(just("("), just(")")),
         )
-        .then_ignore(
+        .then(
             just("]")
                 .not()
                 .repeated()
+                .collect::<String>()
                 .delimited_by(just(" ~["), just("]"))
                 .or_not(),
         )
+        .then(
+            // Anything else after the location, e.g. the carrier thread of a virtual thread, is kept
+            filter(|&c| c != '\n' && c != '\r')
+                .repeated()
+                .collect::<String>()
+                .labelled("suffix"),
+        )
         .then_ignore(eol())
-        .map(|((module, (class, method)), (file, line))| Frame {
-            module,
-            class,
-            method: method.to_string(),
-            file,
-            line,
-        })
+        .map(
+            |(
+                (
+                    (
+                        ((module, class, method), (class_span, method_span)),
+                        ((file, file_span), line),
+                    ),
+                    jar,
+                ),
+                suffix,
+            )| {
+                Frame {
+                    module,
+                    class,
+                    method,
+                    file,
+                    line,
+                    suffix: (!suffix.trim().is_empty()).then_some(suffix),
+                    jar,
+                    candidates: Vec::new(),
+                    confidence: None,
+                    original: None,
+                    spans: Some(FrameSpans {
+                        class: class_span,
+                        method: method_span,
+                        file: file_span,
+                    }),
+                }
+            },
+        )
+}
+
+/// Split `module/class.method` into its parts. The class may be a hidden class, which is suffixed
+/// with `/0x<address>`, or in internal form, e.g. `net/minecraft/class_310.run`, which is
+/// normalized to source form.
+fn split_frame_location(location: &str) -> Option<(Option<String>, String, String)> {
+    let (module, class_method) = match location.rsplit_once('/') {
+        Some((before, suffix)) if suffix.starts_with("0x") => {
+            let (module, class) = match before.rsplit_once('/') {
+                Some((module, class)) => (Some(module), class),
+                None => (None, before),
+            };
+            (module, format!("{}/{}", class, suffix))
+        }
+        // Modules are dotted, e.g. `java.base`, or empty after a class loader, e.g. `app//`, so
+        // plain names before an unqualified class are its packages
+        Some((packages, class_method))
+            if class_method.matches('.').count() == 1
+                && packages
+                    .split('/')
+                    .all(|p| !p.is_empty() && p.chars().all(is_java_identifier_part)) =>
+        {
+            (
+                None,
+                format!("{}.{}", packages.replace('/', "."), class_method),
+            )
+        }
+        Some((module, class_method)) => (Some(module), class_method.to_string()),
+        None => (None, location.to_string()),
+    };
+    let (class, method) = class_method.rsplit_once('.')?;
+    Some((
+        module.map(String::from),
+        class.to_string(),
+        method.to_string(),
+    ))
 }
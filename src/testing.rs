@@ -0,0 +1,415 @@
+//! [`Arbitrary`] implementations for property testing.
+//!
+//! Generated values are well-formed, so they survive a round trip through their text form, e.g.
+//! rendering a [`Stacktrace`] and parsing it again gives the same text.
+//! The tests below check this, and need `cargo test --features testing` to run.
+
+use std::iter::once;
+
+use itertools::Itertools;
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::mappings::proguard::{PGClass, PGMapping, PGMappings, PGMethod};
+use crate::mappings::tiny::{
+    TinyClass, TinyContent, TinyHeader, TinyMapping, TinyMappings, TinyMethod,
+};
+use crate::mappings::{Confidence, Descriptor, MethodId, Type};
+use crate::stacktrace::{Candidate, Cause, Frame, OmittedStyle, Stacktrace};
+
+const IDENTIFIER: &str = "[a-zA-Z_$][a-zA-Z0-9_$]{0,11}";
+const PACKAGE: &str = "[a-z][a-z0-9_]{0,7}";
+const PRIMITIVES: &[&str] = &[
+    "void", "boolean", "byte", "char", "short", "int", "long", "float", "double",
+];
+
+/// A qualified class name in source form, e.g. `net.minecraft.class_310`.
+pub fn class_name() -> impl Strategy<Value = String> + Clone {
+    (vec(PACKAGE, 0..4), IDENTIFIER)
+        .prop_map(|(packages, name)| packages.into_iter().chain(once(name)).join("."))
+        // These would read back as primitive types
+        .prop_filter("primitive type name", |name| {
+            !PRIMITIVES.contains(&name.as_str())
+        })
+}
+
+/// A method name, including constructors and static initializers.
+pub fn method_name() -> impl Strategy<Value = String> + Clone {
+    prop_oneof![
+        8 => IDENTIFIER.prop_map(String::from),
+        1 => Just("<init>".to_string()),
+        1 => Just("<clinit>".to_string()),
+    ]
+}
+
+/// Never `void`, which is only valid as a return type.
+impl Arbitrary for Type {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let leaf = prop_oneof![
+            Just(Type::Boolean),
+            Just(Type::Byte),
+            Just(Type::Char),
+            Just(Type::Short),
+            Just(Type::Int),
+            Just(Type::Long),
+            Just(Type::Float),
+            Just(Type::Double),
+            class_name().prop_map(Type::Object),
+        ];
+        leaf.prop_recursive(3, 3, 1, |inner| {
+            inner.prop_map(|t| Type::Array(Box::new(t)))
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for Descriptor {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            vec(any::<Type>(), 0..4),
+            prop_oneof![1 => Just(Type::Void), 3 => any::<Type>()],
+        )
+            .prop_map(|(params, return_type)| Descriptor {
+                params,
+                return_type,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for MethodId {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (method_name(), any::<Descriptor>())
+            .prop_map(|(name, descriptor)| MethodId { name, descriptor })
+            .boxed()
+    }
+}
+
+/// The candidates, confidence, and original names are never part of the text form, so they are
+/// lost in a round trip.
+impl Arbitrary for Frame {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            // Modules are dotted, as a plain name would read back as a package
+            option::of(
+                "[a-z][a-z0-9]{0,7}(\\.[a-z][a-z0-9]{0,7}){1,2}(@[0-9]{1,2}(\\.[0-9]{1,2}){0,2})?",
+            ),
+            class_name(),
+            method_name(),
+            prop_oneof![
+                1 => Just("Native Method".to_string()),
+                1 => Just("Unknown Source".to_string()),
+                8 => "[A-Za-z][A-Za-z0-9_$]{0,11}\\.(java|kt)",
+            ],
+            option::of(any::<u32>()),
+            vec((any::<MethodId>(), class_name()), 0..3),
+        )
+            .prop_map(|(module, class, method, file, line, candidates)| Frame {
+                module,
+                class,
+                method,
+                file,
+                line,
+                suffix: None,
+                jar: None,
+                confidence: (!candidates.is_empty()).then_some(Confidence::Medium),
+                candidates: candidates
+                    .into_iter()
+                    .map(|(method, class)| Candidate {
+                        method,
+                        class,
+                        confidence: Confidence::Medium,
+                        provenance: "proptest".to_string(),
+                    })
+                    .collect(),
+                original: None,
+                spans: None,
+            })
+            .boxed()
+    }
+}
+
+/// Exception types are always classes.
+fn exception_type() -> impl Strategy<Value = Type> {
+    class_name().prop_map(Type::Object)
+}
+
+fn message() -> impl Strategy<Value = Option<String>> {
+    option::of("[ -~]{0,40}")
+}
+
+fn circular_cause() -> impl Strategy<Value = Cause> {
+    (exception_type(), message()).prop_map(|(ty, message)| Cause::Circular {
+        ty,
+        message,
+        spans: None,
+    })
+}
+
+/// A trace without a thread name, with up to `depth` nested causes.
+fn enclosed_stacktrace(depth: u32) -> BoxedStrategy<Stacktrace> {
+    let cause = if depth == 0 {
+        Just(()).prop_map(|_| None).boxed()
+    } else {
+        option::of(prop_oneof![
+            3 => enclosed_stacktrace(depth - 1).prop_map(Cause::Trace),
+            1 => circular_cause(),
+        ])
+        .boxed()
+    };
+    (
+        exception_type(),
+        message(),
+        vec(any::<Frame>(), 0..6),
+        option::of(any::<u32>()),
+        prop_oneof![
+            Just(OmittedStyle::More),
+            Just(OmittedStyle::CommonFramesOmitted)
+        ],
+        cause,
+    )
+        .prop_map(
+            |(ty, message, frames, omitted_frames, omitted_style, cause)| Stacktrace {
+                thread: None,
+                ty,
+                message,
+                frames,
+                omitted_frames,
+                omitted_style,
+                cause: cause.map(Box::new),
+                spans: None,
+            },
+        )
+        .boxed()
+}
+
+impl Arbitrary for Cause {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            3 => enclosed_stacktrace(1).prop_map(Cause::Trace),
+            1 => circular_cause(),
+        ]
+        .boxed()
+    }
+}
+
+/// Only the outermost trace has a thread name, as printed by the default uncaught exception
+/// handler.
+impl Arbitrary for Stacktrace {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (option::of("[ !#-~]{0,20}"), enclosed_stacktrace(2))
+            .prop_map(|(thread, stacktrace)| Stacktrace {
+                thread,
+                ..stacktrace
+            })
+            .boxed()
+    }
+}
+
+fn tiny_name() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9_-]{0,11}"
+}
+
+impl Arbitrary for TinyHeader {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            tiny_name(),
+            tiny_name(),
+            vec(tiny_name(), 0..3),
+            vec(tiny_name(), 0..3),
+        )
+            .prop_map(
+                |(namespace_a, namespace_b, extra_namespaces, properties)| TinyHeader {
+                    namespace_a,
+                    namespace_b,
+                    extra_namespaces,
+                    properties,
+                },
+            )
+            .boxed()
+    }
+}
+
+/// The parameter is the number of mapped names, which must match the header. At least one is
+/// always generated.
+impl Arbitrary for TinyMapping {
+    type Parameters = usize;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(names_count: Self::Parameters) -> Self::Strategy {
+        tiny_mapping(class_name(), names_count)
+    }
+}
+
+fn tiny_mapping(
+    name: impl Strategy<Value = String> + Clone + 'static,
+    names_count: usize,
+) -> BoxedStrategy<TinyMapping> {
+    (name.clone(), vec(option::of(name), names_count.max(1)))
+        .prop_map(|(primary_name, mapped_names)| TinyMapping {
+            primary_name,
+            mapped_names,
+        })
+        .boxed()
+}
+
+/// The parameter is the number of mapped names, as for [`TinyMapping`].
+impl Arbitrary for TinyMethod {
+    type Parameters = usize;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(names_count: Self::Parameters) -> Self::Strategy {
+        (
+            any::<Descriptor>(),
+            tiny_mapping(method_name(), names_count),
+        )
+            .prop_map(|(primary_desc, mapping)| TinyMethod {
+                primary_desc,
+                mapping,
+            })
+            .boxed()
+    }
+}
+
+/// The parameter is the number of mapped names, as for [`TinyMapping`].
+impl Arbitrary for TinyClass {
+    type Parameters = usize;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(names_count: Self::Parameters) -> Self::Strategy {
+        (
+            any_with::<TinyMapping>(names_count),
+            vec(any_with::<TinyMethod>(names_count), 0..4),
+            vec(tiny_mapping(IDENTIFIER, names_count), 0..4),
+        )
+            .prop_map(|(mapping, methods, fields)| TinyClass {
+                mapping,
+                methods,
+                fields,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for TinyMappings {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<TinyHeader>()
+            .prop_flat_map(|header| {
+                let names_count = 1 + header.extra_namespaces.len();
+                (Just(header), vec(any_with::<TinyClass>(names_count), 0..6))
+            })
+            .prop_map(|(header, classes)| TinyMappings {
+                header,
+                content: TinyContent { classes },
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for PGMapping {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (class_name(), class_name())
+            .prop_map(|(primary_name, secondary_name)| PGMapping {
+                primary_name,
+                secondary_name,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for PGMethod {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<Descriptor>(), method_name(), IDENTIFIER)
+            .prop_map(
+                |(primary_descriptor, primary_name, secondary_name)| PGMethod {
+                    primary_descriptor,
+                    mapping: PGMapping {
+                        primary_name,
+                        secondary_name,
+                    },
+                    inlined_from: None,
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for PGClass {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let field = (IDENTIFIER, IDENTIFIER).prop_map(|(primary_name, secondary_name)| PGMapping {
+            primary_name,
+            secondary_name,
+        });
+        (
+            any::<PGMapping>(),
+            vec(any::<PGMethod>(), 0..4),
+            vec(field, 0..4),
+        )
+            .prop_map(|(mapping, methods, fields)| PGClass {
+                mapping,
+                methods,
+                fields,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for PGMappings {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        vec(any::<PGClass>(), 0..6)
+            .prop_map(|classes| PGMappings { classes })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stacktrace::parse_stacktrace;
+
+    proptest! {
+        #[test]
+        fn stacktrace_round_trip(trace in any::<Stacktrace>()) {
+            let text = trace.to_string();
+            let parsed = parse_stacktrace(&text).map_err(|e| TestCaseError::fail(format!("{:?}", e)))?;
+            prop_assert_eq!(parsed.to_string(), text);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::span::Id;
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// The time spent in each phase, by span name, in the order they were first seen.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    phases: Arc<Mutex<Vec<Phase>>>,
+}
+
+#[derive(Debug)]
+struct Phase {
+    name: &'static str,
+    total: Duration,
+    count: u32,
+}
+
+/// When a span was entered, kept in its extensions.
+struct Entered(Instant);
+
+impl Timings {
+    /// A layer adding the time spent in each span to these timings.
+    pub fn layer(&self) -> TimingsLayer {
+        TimingsLayer {
+            timings: self.clone(),
+        }
+    }
+
+    fn record(&self, name: &'static str, elapsed: Duration) {
+        let mut phases = self
+            .phases
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let phase = match phases.iter().position(|p| p.name == name) {
+            Some(i) => &mut phases[i],
+            None => {
+                phases.push(Phase {
+                    name,
+                    total: Duration::ZERO,
+                    count: 0,
+                });
+                phases.last_mut().unwrap()
+            }
+        };
+        phase.total += elapsed;
+        phase.count += 1;
+    }
+
+    /// Print the time spent in each phase to stderr, so it doesn't mix with the output.
+    pub fn print(&self) {
+        let phases = self
+            .phases
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if phases.is_empty() {
+            eprintln!("Timings: nothing was timed");
+            return;
+        }
+        eprintln!("Timings:");
+        for phase in phases.iter() {
+            eprintln!(
+                "  {:<16} {:>10.1?} over {}, {:.1?} each",
+                phase.name,
+                phase.total,
+                phase.count,
+                phase.total / phase.count
+            );
+        }
+    }
+}
+
+/// See [`Timings::layer`].
+pub struct TimingsLayer {
+    timings: Timings,
+}
+
+impl<S> Layer<S> for TimingsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Entered(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let Some(Entered(start)) = span.extensions_mut().remove::<Entered>() else {
+            return;
+        };
+        self.timings.record(span.name(), start.elapsed());
+    }
+}
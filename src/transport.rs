@@ -0,0 +1,313 @@
+//! How mappings and manifests are requested over HTTP.
+//!
+//! Requests go through [`reqwest`] by default. [`set_transport`] replaces that for the whole
+//! process, e.g. with a preconfigured client, an internal artifact proxy, or recorded responses
+//! for hermetic tests. [`RecordingTransport`] and [`ReplayTransport`] record and replay those.
+
+use std::fmt::{Debug, Formatter};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use error_stack::{Report, ResultExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::SPError;
+
+/// Sends HTTP requests for the library.
+pub trait Transport: Debug + Send + Sync {
+    /// Send the `request`. Responses with error statuses are returned like any other, it should
+    /// only fail with [`SPError::NetworkError`] if there is no response at all.
+    fn send(&self, request: &Request) -> Result<Response, Report<SPError>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+}
+
+impl Method {
+    fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+        }
+    }
+}
+
+/// A request for a [`Transport`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    /// Extra headers, e.g. `Range`.
+    pub headers: Vec<(String, String)>,
+    /// The body, only sent for `POST` requests.
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// A `GET` request for the `url`, without extra headers.
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: Method::Get,
+            url: url.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A `POST` request of the `body` to the `url`, with its `content_type`.
+    pub fn post(url: impl Into<String>, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            method: Method::Post,
+            url: url.into(),
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+}
+
+/// A response from a [`Transport`].
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl Debug for Response {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Response {
+    /// The value of the first header with the `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Read the whole body as text.
+    pub fn text(mut self) -> Result<String, Report<SPError>> {
+        let mut text = String::new();
+        self.body
+            .read_to_string(&mut text)
+            .change_context(SPError::NetworkError)
+            .attach_printable("Failed to read response body")?;
+        Ok(text)
+    }
+
+    /// Copy the body to the `writer`, giving the number of bytes copied.
+    pub fn copy_to(&mut self, writer: &mut impl Write) -> Result<u64, Report<SPError>> {
+        std::io::copy(&mut self.body, writer)
+            .change_context(SPError::NetworkError)
+            .attach_printable("Failed to read response body")
+    }
+}
+
+static TRANSPORT: Lazy<RwLock<Arc<dyn Transport>>> =
+    Lazy::new(|| RwLock::new(Arc::new(ReqwestTransport::default())));
+
+/// Send every later request of the library through the `transport`.
+pub fn set_transport(transport: impl Transport + 'static) {
+    *TRANSPORT
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(transport);
+}
+
+/// Send the `request` with the transport of the library, e.g. so requests of an application are
+/// recorded along with those of the library.
+pub fn send(request: &Request) -> Result<Response, Report<SPError>> {
+    transport().send(request)
+}
+
+/// The transport set with [`set_transport`], or [`ReqwestTransport`].
+pub(crate) fn transport() -> Arc<dyn Transport> {
+    Arc::clone(
+        &TRANSPORT
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+    )
+}
+
+/// The default transport, with a blocking [`reqwest`] client.
+#[derive(Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::blocking::Client,
+}
+
+impl ReqwestTransport {
+    /// Use the `client`, e.g. one with a proxy or extra root certificates.
+    pub fn new(client: reqwest::blocking::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: &Request) -> Result<Response, Report<SPError>> {
+        let mut builder = match request.method {
+            Method::Get => self.client.get(&request.url),
+            Method::Head => self.client.head(&request.url),
+            Method::Post => self.client.post(&request.url).body(request.body.clone()),
+        };
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder.send().change_context(SPError::NetworkError)?;
+        Ok(Response {
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: Box::new(response),
+        })
+    }
+}
+
+/// A transport that saves every response of another one to a directory, for
+/// [`ReplayTransport`].
+///
+/// Each response is saved as `<key>.json` with the request, status and headers, and
+/// `<key>.body`, where the key is a hash of the request.
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    dir: PathBuf,
+}
+
+impl RecordingTransport {
+    /// Record the responses of `inner` to `dir`, creating it if needed.
+    pub fn new(
+        inner: impl Transport + 'static,
+        dir: impl Into<PathBuf>,
+    ) -> Result<Self, Report<SPError>> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .change_context(SPError::IoError)
+            .attach_printable_lazy(|| format!("Failed to create {}", dir.display()))?;
+        Ok(Self {
+            inner: Arc::new(inner),
+            dir,
+        })
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send(&self, request: &Request) -> Result<Response, Report<SPError>> {
+        let mut response = self.inner.send(request)?;
+        let mut body = Vec::new();
+        response.copy_to(&mut body)?;
+        let recorded = Recorded {
+            method: request.method.as_str().to_string(),
+            url: request.url.clone(),
+            request_headers: request.headers.clone(),
+            status: response.status,
+            headers: response.headers.clone(),
+        };
+        let base = record_path(&self.dir, request);
+        serde_json::to_vec_pretty(&recorded)
+            .change_context(SPError::IoError)
+            .and_then(|json| {
+                std::fs::write(base.with_extension("json"), json).change_context(SPError::IoError)
+            })
+            .and_then(|_| {
+                std::fs::write(base.with_extension("body"), &body).change_context(SPError::IoError)
+            })
+            .attach_printable_lazy(|| {
+                format!(
+                    "Failed to record response of {} to {}",
+                    request.url,
+                    self.dir.display()
+                )
+            })?;
+        tracing::debug!("Recorded {} {}", recorded.method, request.url);
+        response.body = Box::new(Cursor::new(body));
+        Ok(response)
+    }
+}
+
+/// A transport that answers requests with the responses saved by a [`RecordingTransport`],
+/// without any network access. Requests that weren't recorded fail.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    /// Replay the responses recorded to `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, Report<SPError>> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(Report::new(SPError::IoError)
+                .attach_printable(format!("No recorded responses at {}", dir.display())));
+        }
+        Ok(Self { dir })
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: &Request) -> Result<Response, Report<SPError>> {
+        let base = record_path(&self.dir, request);
+        let recorded: Recorded = std::fs::read(base.with_extension("json"))
+            .change_context(SPError::NetworkError)
+            .attach_printable_lazy(|| {
+                format!(
+                    "No recorded response for {} {} in {}",
+                    request.method.as_str(),
+                    request.url,
+                    self.dir.display()
+                )
+            })
+            .and_then(|json| {
+                serde_json::from_slice(&json)
+                    .change_context(SPError::NetworkError)
+                    .attach_printable_lazy(|| {
+                        format!("Invalid recorded response for {}", request.url)
+                    })
+            })?;
+        let body = std::fs::read(base.with_extension("body"))
+            .change_context(SPError::NetworkError)
+            .attach_printable_lazy(|| format!("Missing recorded body for {}", request.url))?;
+        Ok(Response {
+            status: recorded.status,
+            headers: recorded.headers,
+            body: Box::new(Cursor::new(body)),
+        })
+    }
+}
+
+/// What is saved of a request and its response, besides the body.
+#[derive(Debug, Serialize, Deserialize)]
+struct Recorded {
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// The path of the files of a request in `dir`, without an extension.
+fn record_path(dir: &Path, request: &Request) -> PathBuf {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{} {}\n", request.method.as_str(), request.url));
+    for (name, value) in &request.headers {
+        hasher.update(format!("{}: {}\n", name.to_ascii_lowercase(), value));
+    }
+    hasher.update(&request.body);
+    dir.join(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,260 @@
+use std::io::stdout;
+
+use error_stack::fmt::ColorMode;
+use error_stack::{Report, ResultExt};
+use ratatui::crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers,
+};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use stacked_portrayals::lenient;
+use stacked_portrayals::mappings::{MapSelf, MapperRegistry};
+use stacked_portrayals::names::NamesType;
+use stacked_portrayals::render::RenderOptions;
+use stacked_portrayals::stacktrace::parse_stacktrace;
+use stacked_portrayals::SPError;
+
+const NAMES: [NamesType; 3] = [
+    NamesType::Obfuscated,
+    NamesType::Mojang,
+    NamesType::FabricIntermediary,
+];
+
+const HELP: &str =
+    "Ctrl+R map | F2/F3 from/to names | Up/Down select frame | Ctrl+L clear | Esc quit";
+
+/// Run the interactive UI until the user quits, mapping pasted stacktraces with the mappers of
+/// `version`. Mappers stay in memory, so switching names back and forth is instant.
+pub fn run(version: String, from: NamesType, to: NamesType) -> Result<(), Report<SPError>> {
+    // Reports are shown in a widget, which can't render escape codes
+    Report::set_color_mode(ColorMode::None);
+    let mut terminal = ratatui::init();
+    let result = execute!(stdout(), EnableBracketedPaste)
+        .change_context(SPError::IoError)
+        .and_then(|_| App::new(version, from, to).run(&mut terminal));
+    // Restore the terminal even if the UI failed, so the error is readable
+    let _ = execute!(stdout(), DisableBracketedPaste);
+    ratatui::restore();
+    result.attach_printable("Terminal UI failed")
+}
+
+/// A mapped frame, for the candidate inspector.
+struct MappedFrame {
+    original: String,
+    mapped: String,
+    candidates: Vec<String>,
+}
+
+struct App {
+    version: String,
+    from: NamesType,
+    to: NamesType,
+    input: String,
+    output: String,
+    frames: Vec<MappedFrame>,
+    selected: ListState,
+    status: String,
+    registry: MapperRegistry,
+}
+
+impl App {
+    fn new(version: String, from: NamesType, to: NamesType) -> Self {
+        Self {
+            version,
+            from,
+            to,
+            input: String::new(),
+            output: String::new(),
+            frames: Vec::new(),
+            selected: ListState::default(),
+            status: "Paste a stacktrace".to_string(),
+            registry: MapperRegistry::new(),
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<(), Report<SPError>> {
+        loop {
+            self.draw(terminal)?;
+            let event = event::read()
+                .change_context(SPError::IoError)
+                .attach_printable("Failed to read terminal event")?;
+            let remap = match event {
+                Event::Paste(text) => {
+                    self.input
+                        .push_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+                    true
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => match self.handle_key(key) {
+                    Some(remap) => remap,
+                    None => return Ok(()),
+                },
+                _ => false,
+            };
+            if remap && !self.input.is_empty() {
+                // Generating a mapper may download mappings, so show that something is happening
+                self.status = format!(
+                    "Mapping with {} {} -> {}...",
+                    self.version, self.from, self.to
+                );
+                self.draw(terminal)?;
+                self.map();
+            }
+        }
+    }
+
+    /// Handle a key press, returning whether to map the input again, or `None` to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<bool> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return None,
+            KeyCode::Char('c') if ctrl => return None,
+            KeyCode::Char('r') if ctrl => return Some(true),
+            KeyCode::Char('l') if ctrl => {
+                self.input.clear();
+                self.output.clear();
+                self.frames.clear();
+                self.selected.select(None);
+                self.status = "Paste a stacktrace".to_string();
+            }
+            KeyCode::F(2) => {
+                self.from = next_names(self.from);
+                return Some(true);
+            }
+            KeyCode::F(3) => {
+                self.to = next_names(self.to);
+                return Some(true);
+            }
+            KeyCode::Up => self.selected.select_previous(),
+            KeyCode::Down => self.selected.select_next(),
+            KeyCode::Enter => self.input.push('\n'),
+            KeyCode::Tab => self.input.push('\t'),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) if !ctrl => self.input.push(c),
+            _ => {}
+        }
+        Some(false)
+    }
+
+    fn map(&mut self) {
+        self.frames.clear();
+        self.selected.select(None);
+        match self.try_map() {
+            Ok(output) => {
+                self.output = output;
+                let mapped = self
+                    .frames
+                    .iter()
+                    .filter(|f| !f.candidates.is_empty())
+                    .count();
+                self.status = format!("Mapped {} of {} frames", mapped, self.frames.len());
+                if !self.frames.is_empty() {
+                    self.selected.select(Some(0));
+                }
+            }
+            Err(e) => {
+                self.output = format!("{:?}", e);
+                self.status = format!("Failed: {}", e.current_context());
+            }
+        }
+    }
+
+    fn try_map(&mut self) -> Result<String, Report<SPError>> {
+        // Pasted traces are often mangled by chat clients
+        let input = lenient::normalize(&self.input);
+        let original = parse_stacktrace(&input)?;
+        let mapper = self.registry.get(&self.version, self.from, self.to)?;
+        // Mapping consumes the trace, so parse it again to keep the original frames
+        let mapped = parse_stacktrace(&input)?.map_self(mapper.as_ref());
+        self.frames = original
+            .all_frames()
+            .zip(mapped.all_frames())
+            .map(|(original, mapped)| MappedFrame {
+                original: format!("{}.{}", original.class, original.method),
+                mapped: format!("{}.{}", mapped.class, mapped.method),
+                candidates: mapped
+                    .candidates
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{}{} in {}",
+                            c.method.name,
+                            c.method.descriptor.internal_descriptor(),
+                            c.class
+                        )
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(mapped.render(&RenderOptions::default()).to_string())
+    }
+
+    fn draw(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Report<SPError>> {
+        terminal
+            .draw(|frame| self.render(frame))
+            .change_context(SPError::IoError)
+            .attach_printable("Failed to draw terminal UI")?;
+        Ok(())
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(2)]).areas(frame.area());
+        let [input_area, right] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(main);
+        let [output_area, inspector_area] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+        // Keep the end of the input in view, where the user is typing
+        let input_lines = self.input.lines().count() as u16;
+        let input_scroll = input_lines.saturating_sub(input_area.height.saturating_sub(2));
+        frame.render_widget(
+            Paragraph::new(self.input.as_str())
+                .scroll((input_scroll, 0))
+                .block(Block::bordered().title(" Input ")),
+            input_area,
+        );
+        frame.render_widget(
+            Paragraph::new(self.output.as_str()).block(Block::bordered().title(format!(
+                " Mapped ({} {} -> {}) ",
+                self.version, self.from, self.to
+            ))),
+            output_area,
+        );
+
+        let items = self.frames.iter().map(|f| {
+            let mut lines = vec![Line::from(format!("{} -> {}", f.original, f.mapped))];
+            if f.candidates.is_empty() {
+                lines.push(Line::from("    no candidates"));
+            }
+            lines.extend(
+                f.candidates
+                    .iter()
+                    .map(|c| Line::from(format!("    {}", c))),
+            );
+            lines
+        });
+        frame.render_stateful_widget(
+            List::new(items.map(ratatui::text::Text::from))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+                .block(Block::bordered().title(" Frames and candidates ")),
+            inspector_area,
+            &mut self.selected,
+        );
+
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.status.as_str()), Line::from(HELP)]),
+            footer,
+        );
+    }
+}
+
+fn next_names(names: NamesType) -> NamesType {
+    let i = NAMES.iter().position(|&n| n == names).unwrap_or(0);
+    NAMES[(i + 1) % NAMES.len()]
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use error_stack::{Report, ResultExt};
+use notify::{EventKind, RecursiveMode, Watcher};
+use stacked_portrayals::SPError;
+
+use crate::batch::{read_file, write_next_to};
+
+/// How long a file must go without changes before we consider it completely written.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Watch `dir` for new files, and write a mapped copy of each next to it with the `suffix`.
+///
+/// This runs until the watcher fails. Failing to map a file is only logged.
+pub fn watch_dir(
+    dir: &Path,
+    suffix: &str,
+    mut map_input: impl FnMut(&str) -> Result<String, Report<SPError>>,
+) -> Result<(), Report<SPError>> {
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .change_context(SPError::IoError)
+        .attach_printable("Failed to create file watcher")?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .change_context(SPError::IoError)
+        .attach_printable_lazy(|| format!("Failed to watch {}", dir.display()))?;
+    tracing::info!("Watching {} for new files...", dir.display());
+
+    // Files are usually written in several steps, so wait for them to settle before mapping.
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        match rx.recv_timeout(SETTLE_TIME / 2) {
+            Ok(event) => {
+                let event = event
+                    .change_context(SPError::IoError)
+                    .attach_printable("File watcher failed")?;
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        // Ignore our own output
+                        if !path.to_string_lossy().ends_with(suffix) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Report::new(SPError::IoError).attach_printable("File watcher stopped"));
+            }
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            if !path.is_file() {
+                continue;
+            }
+            tracing::info!("Mapping {}...", path.display());
+            let result = read_file(&path)
+                .and_then(|input| map_input(&input))
+                .and_then(|output| write_next_to(&path, suffix, &output));
+            if let Err(e) = result {
+                tracing::error!("Failed to map {}: {:?}", path.display(), e);
+            }
+        }
+    }
+}